@@ -0,0 +1,425 @@
+// Unsigned integers of any size and arithmetic modulo an odd number, just
+// enough to verify signatures. Only public values go through here, so
+// nothing tries to run in constant time.
+
+use std::cmp::Ordering;
+
+// Little-endian 64-bit limbs without high zero limbs, so zero has none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn from_u64(value: u64) -> BigUint {
+        BigUint::from_limbs(vec![value])
+    }
+
+    fn from_limbs(mut limbs: Vec<u64>) -> BigUint {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint { limbs }
+    }
+
+    pub fn from_be_bytes(bytes: &[u8]) -> BigUint {
+        let limbs = bytes
+            .rchunks(8)
+            .map(|chunk| {
+                let mut limb = [0u8; 8];
+                limb[8 - chunk.len()..].copy_from_slice(chunk);
+                u64::from_be_bytes(limb)
+            })
+            .collect();
+        BigUint::from_limbs(limbs)
+    }
+
+    pub fn from_le_bytes(bytes: &[u8]) -> BigUint {
+        let reversed: Vec<u8> = bytes.iter().rev().copied().collect();
+        BigUint::from_be_bytes(&reversed)
+    }
+
+    // Big-endian in exactly `length` bytes, `None` when it does not fit
+    pub fn to_be_bytes(&self, length: usize) -> Option<Vec<u8>> {
+        if self.bits() > length * 8 {
+            return None;
+        }
+        let mut bytes = vec![0u8; length];
+        for (i, byte) in bytes.iter_mut().rev().enumerate() {
+            *byte = (self.limbs.get(i / 8).unwrap_or(&0) >> (8 * (i % 8))) as u8;
+        }
+        Some(bytes)
+    }
+
+    pub fn to_le_bytes(&self, length: usize) -> Option<Vec<u8>> {
+        let mut bytes = self.to_be_bytes(length)?;
+        bytes.reverse();
+        Some(bytes)
+    }
+
+    pub fn from_hex(hex: &str) -> BigUint {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("valid hex constant"))
+            .collect();
+        BigUint::from_be_bytes(&bytes)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 64 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.limbs
+            .get(i / 64)
+            .is_some_and(|limb| limb >> (i % 64) & 1 == 1)
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let length = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(length + 1);
+        let mut carry = 0u128;
+        for i in 0..length {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u128
+                + *other.limbs.get(i).unwrap_or(&0) as u128
+                + carry;
+            limbs.push(sum as u64);
+            carry = sum >> 64;
+        }
+        limbs.push(carry as u64);
+        BigUint::from_limbs(limbs)
+    }
+
+    // `other` must not be larger
+    pub fn sub(&self, other: &BigUint) -> BigUint {
+        let mut limbs = self.limbs.clone();
+        let borrow = sub_in_place(&mut limbs, &other.limbs);
+        assert!(!borrow, "subtraction underflow");
+        BigUint::from_limbs(limbs)
+    }
+
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let product = a as u128 * b as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = product as u64;
+                carry = product >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+        BigUint::from_limbs(limbs)
+    }
+
+    // Remainder of the division by `modulus`, one bit at a time
+    pub fn rem(&self, modulus: &BigUint) -> BigUint {
+        assert!(!modulus.is_zero(), "division by zero");
+        if self < modulus {
+            return self.clone();
+        }
+
+        let mut remainder: Vec<u64> = vec![0; modulus.limbs.len() + 1];
+        for i in (0..self.bits()).rev() {
+            // remainder = remainder * 2 + bit
+            let mut carry = self.bit(i) as u64;
+            for limb in remainder.iter_mut() {
+                let next = *limb >> 63;
+                *limb = (*limb << 1) | carry;
+                carry = next;
+            }
+            if compare(&remainder, &modulus.limbs) != Ordering::Less {
+                sub_in_place(&mut remainder, &modulus.limbs);
+            }
+        }
+        BigUint::from_limbs(remainder)
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.limbs, &other.limbs)
+    }
+}
+
+// Compares limb slices of any length, high zero limbs included
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    let length = a.len().max(b.len());
+    for i in (0..length).rev() {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+// a -= b, returning the borrow out of the top limb
+fn sub_in_place(a: &mut [u64], b: &[u64]) -> bool {
+    let mut borrow = false;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let (difference, borrow1) = limb.overflowing_sub(*b.get(i).unwrap_or(&0));
+        let (difference, borrow2) = difference.overflowing_sub(borrow as u64);
+        *limb = difference;
+        borrow = borrow1 || borrow2;
+    }
+    borrow || b.iter().skip(a.len()).any(|&limb| limb != 0)
+}
+
+// a += b, returning the carry out of the top limb
+fn add_in_place(a: &mut [u64], b: &[u64]) -> bool {
+    let mut carry = false;
+    for (limb, &other) in a.iter_mut().zip(b) {
+        let (sum, carry1) = limb.overflowing_add(other);
+        let (sum, carry2) = sum.overflowing_add(carry as u64);
+        *limb = sum;
+        carry = carry1 || carry2;
+    }
+    carry
+}
+
+// A number modulo the modulus, kept in Montgomery form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Residue {
+    limbs: Vec<u64>,
+}
+
+// Arithmetic modulo an odd number in Montgomery form, with R = 2^(64 * limbs)
+#[derive(Debug, Clone)]
+pub struct Modulus {
+    modulus: Vec<u64>,
+    // -modulus^-1 mod 2^64
+    inverse: u64,
+    // R^2 mod modulus, to move numbers into Montgomery form
+    r2: Vec<u64>,
+}
+
+impl Modulus {
+    // `None` for an even modulus or one below 3
+    pub fn new(modulus: &BigUint) -> Option<Modulus> {
+        if !modulus.bit(0) || modulus.bits() < 2 {
+            return None;
+        }
+
+        let length = modulus.limbs.len();
+        // Newton's iteration doubles the correct low bits every step
+        let low = modulus.limbs[0];
+        let mut inverse: u64 = 1;
+        for _ in 0..6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(low.wrapping_mul(inverse)));
+        }
+
+        let mut r2 = vec![0u64; 2 * length];
+        r2.push(1);
+        let r2 = BigUint::from_limbs(r2).rem(modulus);
+
+        Some(Modulus {
+            modulus: modulus.limbs.clone(),
+            inverse: inverse.wrapping_neg(),
+            r2: padded(&r2.limbs, length),
+        })
+    }
+
+    pub fn modulus(&self) -> BigUint {
+        BigUint::from_limbs(self.modulus.clone())
+    }
+
+    pub fn residue(&self, value: &BigUint) -> Residue {
+        let reduced = padded(&value.rem(&self.modulus()).limbs, self.modulus.len());
+        Residue {
+            limbs: self.montgomery(&reduced, &self.r2),
+        }
+    }
+
+    pub fn value(&self, residue: &Residue) -> BigUint {
+        let one = padded(&[1], self.modulus.len());
+        BigUint::from_limbs(self.montgomery(&residue.limbs, &one))
+    }
+
+    pub fn zero(&self) -> Residue {
+        Residue {
+            limbs: vec![0; self.modulus.len()],
+        }
+    }
+
+    pub fn one(&self) -> Residue {
+        self.residue(&BigUint::from_u64(1))
+    }
+
+    pub fn is_zero(&self, a: &Residue) -> bool {
+        a.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    pub fn add(&self, a: &Residue, b: &Residue) -> Residue {
+        let mut limbs = a.limbs.clone();
+        let carry = add_in_place(&mut limbs, &b.limbs);
+        if carry || compare(&limbs, &self.modulus) != Ordering::Less {
+            sub_in_place(&mut limbs, &self.modulus);
+        }
+        Residue { limbs }
+    }
+
+    pub fn sub(&self, a: &Residue, b: &Residue) -> Residue {
+        let mut limbs = a.limbs.clone();
+        if sub_in_place(&mut limbs, &b.limbs) {
+            add_in_place(&mut limbs, &self.modulus);
+        }
+        Residue { limbs }
+    }
+
+    pub fn neg(&self, a: &Residue) -> Residue {
+        self.sub(&self.zero(), a)
+    }
+
+    pub fn mul(&self, a: &Residue, b: &Residue) -> Residue {
+        Residue {
+            limbs: self.montgomery(&a.limbs, &b.limbs),
+        }
+    }
+
+    pub fn square(&self, a: &Residue) -> Residue {
+        self.mul(a, a)
+    }
+
+    pub fn pow(&self, base: &Residue, exponent: &BigUint) -> Residue {
+        let mut result = self.one();
+        for i in (0..exponent.bits()).rev() {
+            result = self.square(&result);
+            if exponent.bit(i) {
+                result = self.mul(&result, base);
+            }
+        }
+        result
+    }
+
+    // Only right for a prime modulus (Fermat's little theorem)
+    pub fn invert(&self, a: &Residue) -> Residue {
+        let exponent = self.modulus().sub(&BigUint::from_u64(2));
+        self.pow(a, &exponent)
+    }
+
+    // a * b / R mod modulus, coarsely integrated operand scanning
+    fn montgomery(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let length = self.modulus.len();
+        let mut t = vec![0u64; length + 2];
+
+        for &a_limb in a {
+            let mut carry = 0u128;
+            for j in 0..length {
+                let sum = t[j] as u128 + a_limb as u128 * b[j] as u128 + carry;
+                t[j] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = t[length] as u128 + carry;
+            t[length] = sum as u64;
+            t[length + 1] = (sum >> 64) as u64;
+
+            let m = t[0].wrapping_mul(self.inverse);
+            let mut carry = (t[0] as u128 + m as u128 * self.modulus[0] as u128) >> 64;
+            for j in 1..length {
+                let sum = t[j] as u128 + m as u128 * self.modulus[j] as u128 + carry;
+                t[j - 1] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = t[length] as u128 + carry;
+            t[length - 1] = sum as u64;
+            t[length] = t[length + 1] + (sum >> 64) as u64;
+        }
+
+        let overflow = t[length] != 0;
+        t.truncate(length);
+        if overflow || compare(&t, &self.modulus) != Ordering::Less {
+            sub_in_place(&mut t, &self.modulus);
+        }
+        t
+    }
+}
+
+fn padded(limbs: &[u64], length: usize) -> Vec<u64> {
+    let mut limbs = limbs.to_vec();
+    limbs.resize(length, 0);
+    limbs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let value = BigUint::from_be_bytes(&[0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(value.bits(), 65);
+        assert_eq!(
+            value.to_be_bytes(10).unwrap(),
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+        );
+        assert_eq!(value.to_be_bytes(8), None);
+        assert_eq!(BigUint::from_le_bytes(&[9, 8, 7, 6, 5, 4, 3, 2, 1]), value);
+        assert!(BigUint::from_be_bytes(&[0, 0]).is_zero());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = BigUint::from_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF");
+        let b = BigUint::from_hex("0123456789ABCDEF0123456789ABCDEF");
+        assert_eq!(
+            a.add(&b),
+            BigUint::from_hex("010123456789ABCDEF0123456789ABCDEE")
+        );
+        assert_eq!(a.add(&b).sub(&b), a);
+        assert_eq!(
+            a.mul(&b),
+            BigUint::from_hex("0123456789ABCDEF0123456789ABCDEEFEDCBA9876543210FEDCBA9876543211")
+        );
+        assert_eq!(a.mul(&b).rem(&a), BigUint::zero());
+        assert_eq!(
+            a.rem(&BigUint::from_u64(1_000_000_007)),
+            BigUint::from_u64(0x10aa_d994)
+        );
+    }
+
+    #[test]
+    fn test_modular_arithmetic() {
+        // the Curve25519 prime, two limbs short of a multiple of 64 bits
+        let p =
+            BigUint::from_hex("7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED");
+        let field = Modulus::new(&p).unwrap();
+        let a = field.residue(&BigUint::from_u64(121666));
+        let b = field.residue(&p.add(&BigUint::from_u64(5)));
+
+        assert_eq!(field.value(&b), BigUint::from_u64(5));
+        assert_eq!(
+            field.value(&field.mul(&a, &b)),
+            BigUint::from_u64(121666 * 5)
+        );
+        assert_eq!(field.mul(&a, &field.invert(&a)), field.one());
+        assert_eq!(
+            field.value(&field.sub(&b, &a)),
+            p.sub(&BigUint::from_u64(121661))
+        );
+        assert_eq!(field.add(&field.sub(&b, &a), &a), b);
+        assert!(field.is_zero(&field.add(&a, &field.neg(&a))));
+        // 2^(p - 1) = 1
+        let two = field.residue(&BigUint::from_u64(2));
+        assert_eq!(field.pow(&two, &p.sub(&BigUint::from_u64(1))), field.one());
+
+        assert!(Modulus::new(&BigUint::from_u64(10)).is_none());
+    }
+}
@@ -0,0 +1,848 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    field::{Class, QType},
+    name::Name,
+    packet::Packet,
+    question::Question,
+    rdata::RData,
+    resource_records::ResourceRecord,
+};
+
+// Longest CNAME chain followed through the cache
+const MAX_CNAME_CHAIN: usize = 8;
+
+const NXDOMAIN: u16 = 3;
+
+// TTL of answers served past their expiry (RFC 8767 4)
+pub const STALE_TTL: u32 = 30;
+
+// An entry read this many times is refreshed ahead of expiry once less than
+// a tenth of its TTL is left
+const PREFETCH_HITS: u32 = 3;
+const PREFETCH_FRACTION: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    name: Name,
+    qtype: QType,
+    class: Class,
+}
+
+impl Key {
+    fn new(name: &Name, qtype: QType, class: Class) -> Self {
+        Self {
+            name: name.clone(),
+            qtype,
+            class,
+        }
+    }
+
+    // A name that does not exist has no RRsets of any type, so NXDOMAIN is
+    // kept under ANY, which never names a real RRset
+    fn nxdomain(name: &Name, class: Class) -> Self {
+        Self::new(name, QType::ANY, class)
+    }
+}
+
+// What is known about a key. RRsets are kept with their RRSIGs, negative
+// entries hold the SOA record that comes with them (RFC 2308 5) followed by
+// any NSEC or NSEC3 records proving them.
+#[derive(Debug, Clone)]
+enum Cached {
+    RRset(Vec<ResourceRecord>),
+    NoData(Vec<ResourceRecord>),
+    NxDomain(Vec<ResourceRecord>),
+}
+
+impl Cached {
+    fn with_ttl(&self, ttl: u32) -> Self {
+        let set_ttl = |record: &ResourceRecord| ResourceRecord {
+            ttl,
+            ..record.clone()
+        };
+        match self {
+            Cached::RRset(records) => Cached::RRset(records.iter().map(set_ttl).collect()),
+            Cached::NoData(records) => Cached::NoData(records.iter().map(set_ttl).collect()),
+            Cached::NxDomain(records) => Cached::NxDomain(records.iter().map(set_ttl).collect()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    data: Cached,
+    expires: Instant,
+    ttl: u32,
+    // validated as secure
+    secure: bool,
+    hits: u32,
+    // a refresh was asked for already
+    prefetching: bool,
+    // position in `Inner::recency`
+    used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<Key, Entry>,
+    // least recently used first
+    recency: BTreeMap<u64, Key>,
+    tick: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, key: &Key) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.used);
+            entry.used = self.tick;
+            self.recency.insert(self.tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+}
+
+// A reply put together from the cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answer {
+    pub answers: Vec<ResourceRecord>,
+    // the SOA record of a negative answer, and its proofs
+    pub authorities: Vec<ResourceRecord>,
+    // the name does not exist, as opposed to only lacking the asked for type
+    pub nxdomain: bool,
+    // every part of it was validated as secure
+    pub secure: bool,
+    // popular and about to expire, the caller should refresh it
+    pub prefetch: bool,
+}
+
+// RRsets and negative answers from upstream replies, each kept until its TTL
+// runs out and handed out with the TTL counted down. Past `capacity` entries
+// the least recently used one makes room.
+//
+// Expired entries are kept for `stale_window` longer, for when upstream
+// cannot be reached (RFC 8767).
+#[derive(Debug)]
+pub struct Cache {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    min_ttl: u32,
+    max_ttl: u32,
+    stale_window: Duration,
+}
+
+impl Cache {
+    pub fn new(capacity: usize, min_ttl: u32, max_ttl: u32) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity,
+            min_ttl,
+            max_ttl,
+            stale_window: Duration::ZERO,
+        }
+    }
+
+    pub fn with_stale_window(self, stale_window: Duration) -> Self {
+        Self {
+            stale_window,
+            ..self
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Inner> {
+        match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn put(&self, key: Key, data: Cached, ttl: u32, secure: bool, now: Instant) {
+        let ttl = ttl.max(self.min_ttl).min(self.max_ttl);
+        if ttl == 0 || self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner();
+        inner.remove(&key);
+        if inner.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = inner.recency.pop_first() {
+                inner.entries.remove(&oldest);
+            }
+        }
+
+        inner.tick += 1;
+        let used = inner.tick;
+        inner.recency.insert(used, key.clone());
+        inner.entries.insert(
+            key,
+            Entry {
+                data,
+                expires: now + Duration::from_secs(ttl as u64),
+                ttl,
+                secure,
+                hits: 0,
+                prefetching: false,
+                used,
+            },
+        );
+    }
+
+    // The entry with its TTLs set to what is left of them, whether it is
+    // secure and whether it is time to refresh it. With `stale` an expired
+    // entry still in the stale window is returned too.
+    fn get(&self, key: &Key, now: Instant, stale: bool) -> Option<(Cached, bool, bool)> {
+        let mut inner = self.inner();
+
+        let expires = inner.entries.get(key)?.expires;
+        if expires + self.stale_window <= now {
+            inner.remove(key);
+            return None;
+        }
+        if expires <= now && !stale {
+            return None;
+        }
+        inner.touch(key);
+
+        let entry = inner.entries.get_mut(key)?;
+        entry.hits += 1;
+        let fresh = now < expires;
+        let left = expires.saturating_duration_since(now).as_secs() as u32;
+        let prefetch = !entry.prefetching
+            && entry.hits >= PREFETCH_HITS
+            && fresh
+            && left < entry.ttl / PREFETCH_FRACTION;
+        entry.prefetching |= prefetch;
+
+        // under a second left still counts as fresh
+        let ttl = if fresh { left.max(1) } else { STALE_TTL };
+        Some((entry.data.with_ttl(ttl), entry.secure, prefetch))
+    }
+
+    // Stores the RRsets among `records`. An RRset lives as long as its
+    // shortest TTL (RFC 2181 5.2).
+    pub fn insert(&self, records: &[ResourceRecord], now: Instant) {
+        self.insert_rrsets(records, false, now);
+    }
+
+    fn insert_rrsets(&self, records: &[ResourceRecord], secure: bool, now: Instant) {
+        let mut rrsets: Vec<(Key, Vec<ResourceRecord>)> = Vec::new();
+        for record in records {
+            // RRSIGs go with the RRset they cover
+            let qtype = match &record.rdata {
+                RData::RRSIG(rrsig) => rrsig.type_covered,
+                _ => record.qtype(),
+            };
+            let key = Key::new(&record.name, qtype, record.class);
+            match rrsets.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, rrset)) => rrset.push(record.clone()),
+                None => rrsets.push((key, vec![record.clone()])),
+            }
+        }
+
+        for (key, records) in rrsets {
+            // the name exists after all
+            self.inner().remove(&Key::nxdomain(&key.name, key.class));
+
+            let ttl = records.iter().map(|record| record.ttl).min().unwrap_or(0);
+            self.put(key, Cached::RRset(records), ttl, secure, now);
+        }
+    }
+
+    // Stores what `reply` says about `question`: its answer RRsets and, for
+    // NXDOMAIN or NODATA, the negative answer for wherever the CNAMEs in it
+    // lead. Negative answers without an SOA record are not cached (RFC 2308 5).
+    // The AD bit of `reply` tells whether it was validated as secure.
+    pub fn store(&self, question: &Question, reply: &Packet, now: Instant) {
+        let rcode = reply.rcode();
+        // a truncated reply may be missing records of its RRsets
+        if reply.header.truncated_msg || (rcode != 0 && rcode != NXDOMAIN) {
+            return;
+        }
+        let secure = reply.header.authentic_data;
+
+        let mut name = question.name.clone();
+        let mut chain = vec![name.clone()];
+        if !matches!(question.qtype, QType::CNAME | QType::ANY) {
+            for _ in 0..MAX_CNAME_CHAIN {
+                let target = reply.answers.iter().find_map(|record| match &record.rdata {
+                    RData::CNAME(target) if record.name == name => Some(target.clone()),
+                    _ => None,
+                });
+                match target {
+                    Some(target) => {
+                        name = target;
+                        chain.push(name.clone());
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        // anything else in the answer was not asked for, and whoever sent it
+        // may not speak for those names
+        let answers: Vec<ResourceRecord> = reply
+            .answers
+            .iter()
+            .filter(|record| chain.contains(&record.name))
+            .cloned()
+            .collect();
+        self.insert_rrsets(&answers, secure, now);
+
+        if question.qtype == QType::ANY {
+            return;
+        }
+
+        let answered = reply
+            .answers
+            .iter()
+            .any(|record| record.name == name && record.qtype() == question.qtype);
+        if rcode == 0 && answered {
+            return;
+        }
+
+        let Some((soa, minimum)) = reply
+            .authorities
+            .iter()
+            .find_map(|record| match record.rdata {
+                RData::SOA { minimum, .. } if name.is_subdomain_of(&record.name) => {
+                    Some((record, minimum))
+                }
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        let mut records = vec![soa.clone()];
+        records.extend(
+            reply
+                .authorities
+                .iter()
+                .filter(|record| is_proof(record))
+                .cloned(),
+        );
+
+        // RFC 2308 5
+        let ttl = soa.ttl.min(minimum);
+        if rcode == NXDOMAIN {
+            let key = Key::nxdomain(&name, question.class);
+            self.put(key, Cached::NxDomain(records), ttl, secure, now);
+        } else {
+            let key = Key::new(&name, question.qtype, question.class);
+            self.put(key, Cached::NoData(records), ttl, secure, now);
+        }
+    }
+
+    // Reply to `question`, following CNAMEs, or `None` unless everything up
+    // to the final answer is cached
+    pub fn lookup(&self, question: &Question, now: Instant) -> Option<Answer> {
+        self.find(question, now, false)
+    }
+
+    // Like `lookup` but falling back on expired entries, for when upstream
+    // failed to answer
+    pub fn lookup_stale(&self, question: &Question, now: Instant) -> Option<Answer> {
+        self.find(question, now, true)
+    }
+
+    fn find(&self, question: &Question, now: Instant, stale: bool) -> Option<Answer> {
+        // ANY asks for everything, which the cache cannot know it holds
+        if question.qtype == QType::ANY {
+            return None;
+        }
+
+        let mut answer = Answer {
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            nxdomain: false,
+            secure: true,
+            prefetch: false,
+        };
+        let mut name = question.name.clone();
+        let mut get = |key: &Key| {
+            let (cached, secure, prefetch) = self.get(key, now, stale)?;
+            answer.secure &= secure;
+            answer.prefetch |= prefetch;
+            Some(cached)
+        };
+
+        for _ in 0..=MAX_CNAME_CHAIN {
+            let found = get(&Key::new(&name, question.qtype, question.class))
+                .or_else(|| get(&Key::nxdomain(&name, question.class)));
+            match found {
+                Some(Cached::RRset(rrset)) => {
+                    answer.answers.extend(rrset);
+                    return Some(answer);
+                }
+                Some(Cached::NoData(records)) => {
+                    answer.authorities.extend(records);
+                    return Some(answer);
+                }
+                Some(Cached::NxDomain(records)) => {
+                    answer.authorities.extend(records);
+                    answer.nxdomain = true;
+                    return Some(answer);
+                }
+                None => {}
+            }
+            if question.qtype == QType::CNAME {
+                return None;
+            }
+
+            let Some(Cached::RRset(cname)) = get(&Key::new(&name, QType::CNAME, question.class))
+            else {
+                return None;
+            };
+            name = cname.iter().find_map(|record| match &record.rdata {
+                RData::CNAME(target) => Some(target.clone()),
+                _ => None,
+            })?;
+            answer.answers.extend(cname);
+        }
+
+        None
+    }
+}
+
+// The NSEC and NSEC3 records of a negative answer and the RRSIGs over them
+// and the SOA record
+fn is_proof(record: &ResourceRecord) -> bool {
+    match &record.rdata {
+        RData::NSEC(_) | RData::NSEC3(_) => true,
+        RData::RRSIG(rrsig) => {
+            matches!(rrsig.type_covered, QType::SOA | QType::NSEC | QType::NSEC3)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{
+        dnssec::{Nsec, Rrsig},
+        header::Header,
+        testing::{cname, question, record, soa, soa_with_ttls},
+    };
+
+    // TTLs are what most of these tests are about
+    fn a(name: &str, ttl: u32, last_octet: u8) -> ResourceRecord {
+        ResourceRecord {
+            ttl,
+            ..record(name, RData::A(Ipv4Addr::new(10, 0, 0, last_octet)))
+        }
+    }
+
+    fn reply(rcode: u16, answers: Vec<ResourceRecord>, authorities: Vec<ResourceRecord>) -> Packet {
+        let mut packet = Packet::new(Header::default());
+        packet.set_rcode(rcode);
+        packet.answers = answers;
+        packet.authorities = authorities;
+        packet
+    }
+
+    #[test]
+    fn test_cache_counts_ttl_down() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        cache.insert(&[a("example.com", 300, 1), a("example.com", 60, 2)], now);
+
+        let rrset = cache
+            .lookup(&question("EXAMPLE.com", QType::A), now)
+            .unwrap()
+            .answers;
+        assert_eq!(rrset.len(), 2);
+        // the whole RRset takes the shortest TTL
+        assert!(rrset.iter().all(|record| record.ttl == 60));
+
+        let later = now + Duration::from_secs(45);
+        let rrset = cache
+            .lookup(&question("example.com", QType::A), later)
+            .unwrap()
+            .answers;
+        assert!(rrset.iter().all(|record| record.ttl == 15));
+
+        let expired = now + Duration::from_secs(60);
+        assert!(cache
+            .lookup(&question("example.com", QType::A), expired)
+            .is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_follows_cnames() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        cache.insert(
+            &[
+                cname("www.example.com", "web.example.com"),
+                cname("web.example.com", "host.example.net"),
+                a("host.example.net", 300, 1),
+            ],
+            now,
+        );
+
+        let answers = cache
+            .lookup(&question("www.example.com", QType::A), now)
+            .unwrap()
+            .answers;
+        assert_eq!(answers.len(), 3);
+        assert_eq!(answers[2].rdata, RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+
+        assert_eq!(
+            cache
+                .lookup(&question("www.example.com", QType::CNAME), now)
+                .unwrap()
+                .answers
+                .len(),
+            1
+        );
+        // the chain does not lead to an AAAA RRset
+        assert!(cache
+            .lookup(&question("www.example.com", QType::AAAA), now)
+            .is_none());
+        assert!(cache
+            .lookup(&question("www.example.com", QType::ANY), now)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_stores_only_the_asked_for_chain() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        let poisoned = reply(
+            0,
+            vec![
+                cname("app.corp.internal", "host.corp.internal"),
+                a("host.corp.internal", 300, 1),
+                a("www.bank.com", 300, 66),
+            ],
+            vec![],
+        );
+        cache.store(&question("app.corp.internal", QType::A), &poisoned, now);
+
+        assert_eq!(
+            cache
+                .lookup(&question("app.corp.internal", QType::A), now)
+                .unwrap()
+                .answers
+                .len(),
+            2
+        );
+        assert!(cache
+            .lookup(&question("www.bank.com", QType::A), now)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = Cache::new(2, 0, 86400);
+        let now = Instant::now();
+        cache.insert(&[a("a.com", 300, 1)], now);
+        cache.insert(&[a("b.com", 300, 2)], now);
+
+        // reading a.com makes b.com the oldest
+        assert!(cache.lookup(&question("a.com", QType::A), now).is_some());
+        cache.insert(&[a("c.com", 300, 3)], now);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup(&question("a.com", QType::A), now).is_some());
+        assert!(cache.lookup(&question("b.com", QType::A), now).is_none());
+        assert!(cache.lookup(&question("c.com", QType::A), now).is_some());
+
+        // replacing an RRset does not evict anything
+        cache.insert(&[a("c.com", 300, 4)], now);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache
+                .lookup(&question("c.com", QType::A), now)
+                .unwrap()
+                .answers[0]
+                .rdata,
+            RData::A(Ipv4Addr::new(10, 0, 0, 4))
+        );
+
+        let disabled = Cache::new(0, 0, 86400);
+        disabled.insert(&[a("a.com", 300, 1)], now);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_cache_clamps_ttl() {
+        let cache = Cache::new(10, 30, 3600);
+        let now = Instant::now();
+        cache.insert(&[a("short.com", 5, 1), a("long.com", 604800, 2)], now);
+
+        assert_eq!(
+            cache
+                .lookup(&question("short.com", QType::A), now)
+                .unwrap()
+                .answers[0]
+                .ttl,
+            30
+        );
+        assert_eq!(
+            cache
+                .lookup(&question("long.com", QType::A), now)
+                .unwrap()
+                .answers[0]
+                .ttl,
+            3600
+        );
+
+        // without a minimum, TTL 0 records are not cached at all
+        let cache = Cache::new(10, 0, 3600);
+        cache.insert(&[a("zero.com", 0, 1)], now);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_nxdomain() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        // the negative TTL is the lower of the SOA TTL and minimum
+        let nxdomain = reply(
+            NXDOMAIN,
+            vec![],
+            vec![soa_with_ttls("example.com", 3600, 300)],
+        );
+        cache.store(&question("missing.example.com", QType::A), &nxdomain, now);
+
+        // a missing name lacks every type
+        let answer = cache
+            .lookup(&question("missing.example.com", QType::AAAA), now)
+            .unwrap();
+        assert!(answer.nxdomain);
+        assert!(answer.answers.is_empty());
+        assert_eq!(answer.authorities.len(), 1);
+        assert_eq!(answer.authorities[0].qtype(), QType::SOA);
+        assert_eq!(answer.authorities[0].ttl, 300);
+
+        let later = now + Duration::from_secs(100);
+        let answer = cache
+            .lookup(&question("missing.example.com", QType::A), later)
+            .unwrap();
+        assert_eq!(answer.authorities[0].ttl, 200);
+
+        // until the name turns up
+        cache.insert(&[a("missing.example.com", 300, 1)], now);
+        assert!(cache
+            .lookup(&question("missing.example.com", QType::AAAA), now)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_nodata() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        let nodata = reply(
+            0,
+            vec![cname("www.example.com", "host.example.com")],
+            vec![soa_with_ttls("example.com", 60, 600)],
+        );
+        cache.store(&question("www.example.com", QType::AAAA), &nodata, now);
+
+        // NODATA is cached for where the CNAME leads, and only for AAAA
+        let answer = cache
+            .lookup(&question("www.example.com", QType::AAAA), now)
+            .unwrap();
+        assert!(!answer.nxdomain);
+        assert_eq!(answer.answers.len(), 1);
+        assert_eq!(answer.authorities[0].ttl, 60);
+        assert!(cache
+            .lookup(&question("host.example.com", QType::AAAA), now)
+            .is_some());
+        assert!(cache
+            .lookup(&question("host.example.com", QType::A), now)
+            .is_none());
+
+        // without an SOA record there is no negative TTL to go by
+        let cache = Cache::new(10, 0, 86400);
+        cache.store(
+            &question("missing.example.com", QType::A),
+            &reply(NXDOMAIN, vec![], vec![]),
+            now,
+        );
+        assert!(cache.is_empty());
+
+        // nor are failures cached
+        cache.store(
+            &question("missing.example.com", QType::A),
+            &reply(2, vec![], vec![soa_with_ttls("example.com", 60, 600)]),
+            now,
+        );
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_serves_stale() {
+        let cache = Cache::new(10, 0, 86400).with_stale_window(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert(&[a("example.com", 10, 1)], now);
+
+        // fresh entries are served as usual
+        let answer = cache
+            .lookup_stale(&question("example.com", QType::A), now)
+            .unwrap();
+        assert_eq!(answer.answers[0].ttl, 10);
+
+        // nor does under a second left make an entry stale
+        let almost = now + Duration::from_millis(9500);
+        let answer = cache
+            .lookup(&question("example.com", QType::A), almost)
+            .unwrap();
+        assert_eq!(answer.answers[0].ttl, 1);
+
+        let expired = now + Duration::from_secs(15);
+        assert!(cache
+            .lookup(&question("example.com", QType::A), expired)
+            .is_none());
+        let answer = cache
+            .lookup_stale(&question("example.com", QType::A), expired)
+            .unwrap();
+        assert_eq!(answer.answers[0].ttl, STALE_TTL);
+
+        let past_window = now + Duration::from_secs(70);
+        assert!(cache
+            .lookup_stale(&question("example.com", QType::A), past_window)
+            .is_none());
+        assert!(cache.is_empty());
+
+        // without a window nothing is kept past expiry
+        let cache = Cache::new(10, 0, 86400);
+        cache.insert(&[a("example.com", 10, 1)], now);
+        assert!(cache
+            .lookup_stale(&question("example.com", QType::A), expired)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_prefetches_popular_entries() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        cache.insert(&[a("example.com", 100, 1), a("rare.com", 100, 2)], now);
+
+        for _ in 0..3 {
+            let answer = cache
+                .lookup(&question("example.com", QType::A), now)
+                .unwrap();
+            assert!(!answer.prefetch);
+        }
+
+        // asked for once, and only once a tenth of the TTL is left
+        let expiring = now + Duration::from_secs(95);
+        assert!(
+            cache
+                .lookup(&question("example.com", QType::A), expiring)
+                .unwrap()
+                .prefetch
+        );
+        assert!(
+            !cache
+                .lookup(&question("example.com", QType::A), expiring)
+                .unwrap()
+                .prefetch
+        );
+        assert!(
+            !cache
+                .lookup(&question("rare.com", QType::A), expiring)
+                .unwrap()
+                .prefetch
+        );
+
+        // the refreshed RRset starts over
+        cache.insert(&[a("example.com", 100, 1)], expiring);
+        assert!(
+            !cache
+                .lookup(&question("example.com", QType::A), expiring)
+                .unwrap()
+                .prefetch
+        );
+    }
+
+    #[test]
+    fn test_cache_keeps_dnssec_records() {
+        let cache = Cache::new(10, 0, 86400);
+        let now = Instant::now();
+        let rrsig = |name: &str, type_covered| {
+            let rrsig = Rrsig {
+                type_covered,
+                algorithm: 15,
+                labels: 2,
+                original_ttl: 300,
+                expiration: 2,
+                inception: 1,
+                key_tag: 1,
+                signer: "example.com".parse().unwrap(),
+                signature: vec![0; 64],
+            };
+            ResourceRecord::new(name.parse().unwrap(), Class::IN, 300, RData::RRSIG(rrsig))
+        };
+
+        // signatures come back with the RRset they cover
+        let mut secure = reply(
+            0,
+            vec![
+                rrsig("www.example.com", QType::CNAME),
+                cname("www.example.com", "host.example.com"),
+                a("host.example.com", 300, 1),
+                rrsig("host.example.com", QType::A),
+            ],
+            vec![],
+        );
+        secure.header.authentic_data = true;
+        cache.store(&question("www.example.com", QType::A), &secure, now);
+        let answer = cache
+            .lookup(&question("www.example.com", QType::A), now)
+            .unwrap();
+        assert_eq!(answer.answers.len(), 4);
+        assert!(answer.secure);
+
+        // and the proofs with negative answers
+        let nsec = ResourceRecord::new(
+            "host.example.com".parse().unwrap(),
+            Class::IN,
+            300,
+            RData::NSEC(Nsec {
+                next: "www.example.com".parse().unwrap(),
+                types: vec![QType::A, QType::RRSIG, QType::NSEC],
+            }),
+        );
+        let nodata = reply(
+            0,
+            vec![],
+            vec![
+                soa("example.com"),
+                rrsig("example.com", QType::SOA),
+                nsec,
+                rrsig("host.example.com", QType::NSEC),
+            ],
+        );
+        cache.store(&question("host.example.com", QType::AAAA), &nodata, now);
+        let answer = cache
+            .lookup(&question("host.example.com", QType::AAAA), now)
+            .unwrap();
+        assert_eq!(answer.authorities.len(), 4);
+        assert!(!answer.secure);
+    }
+}
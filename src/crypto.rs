@@ -0,0 +1,611 @@
+// Signature verification for the DNSSEC algorithms the validator implements
+// (RFC 8624 3.1): RSA/SHA-256 (RFC 5702), ECDSA P-256 and P-384 (RFC 6605)
+// and Ed25519 (RFC 8080). Keys and signatures are taken in their DNSKEY and
+// RRSIG wire formats.
+
+use crate::{
+    bigint::{BigUint, Modulus, Residue},
+    digest::{sha256, sha384, sha512},
+};
+
+pub const RSASHA256: u8 = 8;
+pub const ECDSAP256SHA256: u8 = 13;
+pub const ECDSAP384SHA384: u8 = 14;
+pub const ED25519: u8 = 15;
+
+// DigestInfo of an EMSA-PKCS1-v1_5 encoded SHA-256 hash (RFC 8017 9.2)
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+// RSA moduli DNSSEC allows (RFC 5702 2)
+const MIN_RSA_BITS: usize = 512;
+const MAX_RSA_BITS: usize = 4096;
+// Longer exponents only make verifying slow, other validators refuse them
+// as well
+const MAX_RSA_EXPONENT_BITS: usize = 4096;
+
+pub fn is_supported(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        RSASHA256 | ECDSAP256SHA256 | ECDSAP384SHA384 | ED25519
+    )
+}
+
+// Whether `signature` is the signature of `data` by `public_key`. Malformed
+// keys and signatures, and unknown algorithms, just fail.
+pub fn verify(algorithm: u8, public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    match algorithm {
+        RSASHA256 => verify_rsa_sha256(public_key, data, signature),
+        ECDSAP256SHA256 => Curve::p256().verify(public_key, &sha256(data), signature),
+        ECDSAP384SHA384 => Curve::p384().verify(public_key, &sha384(data), signature),
+        ED25519 => Edwards::new().verify(public_key, data, signature),
+        _ => false,
+    }
+}
+
+// The key is the exponent length in one byte, or a zero and two bytes, then
+// the exponent and the modulus (RFC 3110 2)
+fn verify_rsa_sha256(public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    let (length, rest) = match public_key {
+        [0, high, low, rest @ ..] => (u16::from_be_bytes([*high, *low]) as usize, rest),
+        [length, rest @ ..] => (*length as usize, rest),
+        [] => return false,
+    };
+    if length == 0 || rest.len() <= length {
+        return false;
+    }
+    let exponent = BigUint::from_be_bytes(&rest[..length]);
+    let n = BigUint::from_be_bytes(&rest[length..]);
+    let size = rest.len() - length;
+
+    if !(MIN_RSA_BITS..=MAX_RSA_BITS).contains(&n.bits())
+        || exponent.bits() > MAX_RSA_EXPONENT_BITS
+        || signature.len() != size
+    {
+        return false;
+    }
+    let s = BigUint::from_be_bytes(signature);
+    let Some(modulus) = Modulus::new(&n) else {
+        return false;
+    };
+    if s >= n {
+        return false;
+    }
+
+    let m = modulus.value(&modulus.pow(&modulus.residue(&s), &exponent));
+    let Some(encoded) = m.to_be_bytes(size) else {
+        return false;
+    };
+
+    // 0x00 0x01 0xFF... 0x00 DigestInfo hash
+    let hash = sha256(data);
+    let padding = size.saturating_sub(3 + SHA256_DIGEST_INFO.len() + hash.len());
+    let mut expected = vec![0x00, 0x01];
+    expected.extend(std::iter::repeat_n(0xFF, padding));
+    expected.push(0x00);
+    expected.extend_from_slice(&SHA256_DIGEST_INFO);
+    expected.extend_from_slice(&hash);
+
+    padding >= 8 && encoded == expected
+}
+
+// A short Weierstrass curve y^2 = x^3 - 3x + b over a prime field
+struct Curve {
+    field: Modulus,
+    order: Modulus,
+    b: Residue,
+    gx: Residue,
+    gy: Residue,
+    // bytes in a coordinate
+    size: usize,
+}
+
+// Jacobian coordinates, (X / Z^2, Y / Z^3), with Z = 0 at infinity
+#[derive(Clone)]
+struct Point {
+    x: Residue,
+    y: Residue,
+    z: Residue,
+}
+
+impl Curve {
+    fn new(p: &str, n: &str, b: &str, gx: &str, gy: &str) -> Curve {
+        let field = Modulus::new(&BigUint::from_hex(p)).expect("odd prime");
+        let order = Modulus::new(&BigUint::from_hex(n)).expect("odd prime");
+        Curve {
+            b: field.residue(&BigUint::from_hex(b)),
+            gx: field.residue(&BigUint::from_hex(gx)),
+            gy: field.residue(&BigUint::from_hex(gy)),
+            size: p.len() / 2,
+            field,
+            order,
+        }
+    }
+
+    // SEC 2 secp256r1 and secp384r1
+    fn p256() -> Curve {
+        Curve::new(
+            "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF",
+            "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551",
+            "5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B",
+            "6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296",
+            "4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5",
+        )
+    }
+
+    fn p384() -> Curve {
+        Curve::new(
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE\
+             FFFFFFFF0000000000000000FFFFFFFF",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC7634D81F4372DDF\
+             581A0DB248B0A77AECEC196ACCC52973",
+            "B3312FA7E23EE7E4988E056BE3F82D19181D9C6EFE8141120314088F5013875A\
+             C656398D8A2ED19D2A85C8EDD3EC2AEF",
+            "AA87CA22BE8B05378EB1C71EF320AD746E1D3B628BA79B9859F741E082542A38\
+             5502F25DBF55296C3A545E3872760AB7",
+            "3617DE4A96262C6F5D9E98BF9292DC29F8F41DBD289A147CE9DA3113B5F0B8C0\
+             0A60B1CE1D7E819D7A431D7C90EA0E5F",
+        )
+    }
+
+    // The key is x followed by y and the signature r followed by s, each
+    // `size` bytes (RFC 6605 4)
+    fn verify(&self, public_key: &[u8], hash: &[u8], signature: &[u8]) -> bool {
+        if public_key.len() != 2 * self.size || signature.len() != 2 * self.size {
+            return false;
+        }
+        let Some(q) = self.affine(&public_key[..self.size], &public_key[self.size..]) else {
+            return false;
+        };
+
+        let n = self.order.modulus();
+        let r = BigUint::from_be_bytes(&signature[..self.size]);
+        let s = BigUint::from_be_bytes(&signature[self.size..]);
+        if r.is_zero() || s.is_zero() || r >= n || s >= n {
+            return false;
+        }
+
+        // the hash is as long as the order for both curves
+        let e = self.order.residue(&BigUint::from_be_bytes(hash));
+        let w = self.order.invert(&self.order.residue(&s));
+        let u1 = self.order.value(&self.order.mul(&e, &w));
+        let u2 = self
+            .order
+            .value(&self.order.mul(&self.order.residue(&r), &w));
+
+        let g = Point {
+            x: self.gx.clone(),
+            y: self.gy.clone(),
+            z: self.field.one(),
+        };
+        let point = self.add(&self.multiply(&g, &u1), &self.multiply(&q, &u2));
+        if self.field.is_zero(&point.z) {
+            return false;
+        }
+
+        let z_inverse = self.field.invert(&point.z);
+        let x = self
+            .field
+            .value(&self.field.mul(&point.x, &self.field.square(&z_inverse)));
+        x.rem(&n) == r
+    }
+
+    // The point (x, y), if it is on the curve
+    fn affine(&self, x: &[u8], y: &[u8]) -> Option<Point> {
+        let p = self.field.modulus();
+        let (x, y) = (BigUint::from_be_bytes(x), BigUint::from_be_bytes(y));
+        if x >= p || y >= p {
+            return None;
+        }
+        let (x, y) = (self.field.residue(&x), self.field.residue(&y));
+
+        let x3 = self.field.mul(&self.field.square(&x), &x);
+        let three_x = self.field.add(&self.field.add(&x, &x), &x);
+        let rhs = self.field.add(&self.field.sub(&x3, &three_x), &self.b);
+        if self.field.square(&y) != rhs {
+            return None;
+        }
+
+        Some(Point {
+            x,
+            y,
+            z: self.field.one(),
+        })
+    }
+
+    fn infinity(&self) -> Point {
+        Point {
+            x: self.field.one(),
+            y: self.field.one(),
+            z: self.field.zero(),
+        }
+    }
+
+    // dbl-2001-b, for a = -3
+    fn double(&self, point: &Point) -> Point {
+        let f = &self.field;
+        if f.is_zero(&point.z) || f.is_zero(&point.y) {
+            return self.infinity();
+        }
+
+        let delta = f.square(&point.z);
+        let gamma = f.square(&point.y);
+        let beta = f.mul(&point.x, &gamma);
+        let t = f.mul(&f.sub(&point.x, &delta), &f.add(&point.x, &delta));
+        let alpha = f.add(&f.add(&t, &t), &t);
+        let beta4 = f.add(&f.add(&beta, &beta), &f.add(&beta, &beta));
+        let beta8 = f.add(&beta4, &beta4);
+        let x = f.sub(&f.square(&alpha), &beta8);
+        let yz = f.add(&point.y, &point.z);
+        let z = f.sub(&f.sub(&f.square(&yz), &gamma), &delta);
+        let gamma2 = f.square(&gamma);
+        let gamma4 = f.add(&f.add(&gamma2, &gamma2), &f.add(&gamma2, &gamma2));
+        let gamma8 = f.add(&gamma4, &gamma4);
+        let y = f.sub(&f.mul(&alpha, &f.sub(&beta4, &x)), &gamma8);
+
+        Point { x, y, z }
+    }
+
+    // add-2007-bl, falling back to doubling for equal points
+    fn add(&self, p: &Point, q: &Point) -> Point {
+        let f = &self.field;
+        if f.is_zero(&p.z) {
+            return q.clone();
+        }
+        if f.is_zero(&q.z) {
+            return p.clone();
+        }
+
+        let z1z1 = f.square(&p.z);
+        let z2z2 = f.square(&q.z);
+        let u1 = f.mul(&p.x, &z2z2);
+        let u2 = f.mul(&q.x, &z1z1);
+        let s1 = f.mul(&f.mul(&p.y, &q.z), &z2z2);
+        let s2 = f.mul(&f.mul(&q.y, &p.z), &z1z1);
+        let h = f.sub(&u2, &u1);
+        let r = f.sub(&s2, &s1);
+        if f.is_zero(&h) {
+            return if f.is_zero(&r) {
+                self.double(p)
+            } else {
+                self.infinity()
+            };
+        }
+
+        let r = f.add(&r, &r);
+        let i = f.square(&f.add(&h, &h));
+        let j = f.mul(&h, &i);
+        let v = f.mul(&u1, &i);
+        let x = f.sub(&f.sub(&f.square(&r), &j), &f.add(&v, &v));
+        let s1j = f.mul(&s1, &j);
+        let y = f.sub(&f.mul(&r, &f.sub(&v, &x)), &f.add(&s1j, &s1j));
+        let zz = f.square(&f.add(&p.z, &q.z));
+        let z = f.mul(&f.sub(&f.sub(&zz, &z1z1), &z2z2), &h);
+
+        Point { x, y, z }
+    }
+
+    fn multiply(&self, point: &Point, scalar: &BigUint) -> Point {
+        let mut result = self.infinity();
+        for i in (0..scalar.bits()).rev() {
+            result = self.double(&result);
+            if scalar.bit(i) {
+                result = self.add(&result, point);
+            }
+        }
+        result
+    }
+}
+
+// The twisted Edwards curve -x^2 + y^2 = 1 + d x^2 y^2 of Ed25519 (RFC 8032 5.1)
+struct Edwards {
+    field: Modulus,
+    // group order L
+    order: BigUint,
+    d: Residue,
+    d2: Residue,
+    // (p + 3) / 8 and a square root of -1
+    sqrt_exponent: BigUint,
+    i: Residue,
+    base: EdwardsPoint,
+}
+
+// Extended coordinates, x = X / Z, y = Y / Z and x y = T / Z
+#[derive(Clone)]
+struct EdwardsPoint {
+    x: Residue,
+    y: Residue,
+    z: Residue,
+    t: Residue,
+}
+
+impl Edwards {
+    fn new() -> Edwards {
+        let p =
+            BigUint::from_hex("7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED");
+        let field = Modulus::new(&p).expect("odd prime");
+        let d = field.residue(&BigUint::from_hex(
+            "52036CEE2B6FFE738CC740797779E89800700A4D4141D8AB75EB4DCA135978A3",
+        ));
+        let x = field.residue(&BigUint::from_hex(
+            "216936D3CD6E53FEC0A4E231FDD6DC5C692CC7609525A7B2C9562D608F25D51A",
+        ));
+        let y = field.residue(&BigUint::from_hex(
+            "6666666666666666666666666666666666666666666666666666666666666658",
+        ));
+
+        Edwards {
+            order: BigUint::from_hex(
+                "1000000000000000000000000000000014DEF9DEA2F79CD65812631A5CF5D3ED",
+            ),
+            d2: field.add(&d, &d),
+            d,
+            sqrt_exponent: BigUint::from_hex(
+                "0FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE",
+            ),
+            i: field.residue(&BigUint::from_hex(
+                "2B8324804FC1DF0B2B4D00993DFBD7A72F431806AD2FE478C4EE1B274A0EA0B0",
+            )),
+            base: EdwardsPoint {
+                t: field.mul(&x, &y),
+                z: field.one(),
+                x,
+                y,
+            },
+            field,
+        }
+    }
+
+    // The signature is R followed by S, checked as [S]B = R + [k]A without
+    // the cofactor (RFC 8032 5.1.7)
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        if public_key.len() != 32 || signature.len() != 64 {
+            return false;
+        }
+        let Some(a) = self.decode(public_key) else {
+            return false;
+        };
+        let s = BigUint::from_le_bytes(&signature[32..]);
+        if s >= self.order {
+            return false;
+        }
+
+        let k = self.challenge(&signature[..32], public_key, message);
+        let minus_a = EdwardsPoint {
+            x: self.field.neg(&a.x),
+            t: self.field.neg(&a.t),
+            ..a
+        };
+        let point = self.add(&self.multiply(&self.base, &s), &self.multiply(&minus_a, &k));
+        self.encode(&point) == signature[..32]
+    }
+
+    // SHA-512(R || A || M) mod L
+    fn challenge(&self, r: &[u8], public_key: &[u8], message: &[u8]) -> BigUint {
+        let mut data = r.to_vec();
+        data.extend_from_slice(public_key);
+        data.extend_from_slice(message);
+        BigUint::from_le_bytes(&sha512(&data)).rem(&self.order)
+    }
+
+    // y in little-endian with the low bit of x in the top bit (RFC 8032 5.1.3)
+    fn decode(&self, bytes: &[u8]) -> Option<EdwardsPoint> {
+        let f = &self.field;
+        let mut y_bytes = bytes.to_vec();
+        let sign = y_bytes[31] >> 7 == 1;
+        y_bytes[31] &= 0x7F;
+        let y = BigUint::from_le_bytes(&y_bytes);
+        if y >= f.modulus() {
+            return None;
+        }
+        let y = f.residue(&y);
+
+        // x^2 = (y^2 - 1) / (d y^2 + 1)
+        let y2 = f.square(&y);
+        let u = f.sub(&y2, &f.one());
+        let v = f.add(&f.mul(&self.d, &y2), &f.one());
+        let x2 = f.mul(&u, &f.invert(&v));
+
+        // a square root is x2^((p + 3) / 8), times sqrt(-1) when that squares to -x2
+        let mut x = f.pow(&x2, &self.sqrt_exponent);
+        if f.square(&x) != x2 {
+            x = f.mul(&x, &self.i);
+            if f.square(&x) != x2 {
+                return None;
+            }
+        }
+        if f.is_zero(&x) && sign {
+            return None;
+        }
+        if f.value(&x).bit(0) != sign {
+            x = f.neg(&x);
+        }
+
+        Some(EdwardsPoint {
+            t: f.mul(&x, &y),
+            z: f.one(),
+            x,
+            y,
+        })
+    }
+
+    fn encode(&self, point: &EdwardsPoint) -> Vec<u8> {
+        let f = &self.field;
+        let z_inverse = f.invert(&point.z);
+        let x = f.value(&f.mul(&point.x, &z_inverse));
+        let y = f.value(&f.mul(&point.y, &z_inverse));
+        let mut bytes = y.to_le_bytes(32).expect("reduced");
+        bytes[31] |= (x.bit(0) as u8) << 7;
+        bytes
+    }
+
+    fn identity(&self) -> EdwardsPoint {
+        EdwardsPoint {
+            x: self.field.zero(),
+            y: self.field.one(),
+            z: self.field.one(),
+            t: self.field.zero(),
+        }
+    }
+
+    // add-2008-hwcd-3, which also doubles
+    fn add(&self, p: &EdwardsPoint, q: &EdwardsPoint) -> EdwardsPoint {
+        let f = &self.field;
+        let a = f.mul(&f.sub(&p.y, &p.x), &f.sub(&q.y, &q.x));
+        let b = f.mul(&f.add(&p.y, &p.x), &f.add(&q.y, &q.x));
+        let c = f.mul(&f.mul(&p.t, &self.d2), &q.t);
+        let zz = f.mul(&p.z, &q.z);
+        let d = f.add(&zz, &zz);
+        let (e, ff, g, h) = (f.sub(&b, &a), f.sub(&d, &c), f.add(&d, &c), f.add(&b, &a));
+
+        EdwardsPoint {
+            x: f.mul(&e, &ff),
+            y: f.mul(&g, &h),
+            z: f.mul(&ff, &g),
+            t: f.mul(&e, &h),
+        }
+    }
+
+    fn multiply(&self, point: &EdwardsPoint, scalar: &BigUint) -> EdwardsPoint {
+        let mut result = self.identity();
+        for i in (0..scalar.bits()).rev() {
+            result = self.add(&result, &result);
+            if scalar.bit(i) {
+                result = self.add(&result, point);
+            }
+        }
+        result
+    }
+}
+
+// Ed25519 key pair and signatures from a 32-byte seed, for signing test zones
+#[cfg(test)]
+pub fn ed25519_public_key(seed: &[u8; 32]) -> Vec<u8> {
+    let curve = Edwards::new();
+    let (scalar, _) = ed25519_expand(seed);
+    curve.encode(&curve.multiply(&curve.base, &scalar))
+}
+
+#[cfg(test)]
+pub fn ed25519_sign(seed: &[u8; 32], message: &[u8]) -> Vec<u8> {
+    let curve = Edwards::new();
+    let (scalar, prefix) = ed25519_expand(seed);
+    let public_key = curve.encode(&curve.multiply(&curve.base, &scalar));
+
+    let mut data = prefix.to_vec();
+    data.extend_from_slice(message);
+    let r = BigUint::from_le_bytes(&sha512(&data)).rem(&curve.order);
+    let mut signature = curve.encode(&curve.multiply(&curve.base, &r));
+
+    let k = curve.challenge(&signature, &public_key, message);
+    let s = r.add(&k.mul(&scalar)).rem(&curve.order);
+    signature.extend(s.to_le_bytes(32).expect("reduced"));
+    signature
+}
+
+#[cfg(test)]
+fn ed25519_expand(seed: &[u8; 32]) -> (BigUint, [u8; 32]) {
+    let hash = sha512(seed);
+    let mut scalar: [u8; 32] = hash[..32].try_into().unwrap();
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    (
+        BigUint::from_le_bytes(&scalar),
+        hash[32..].try_into().unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const MESSAGE: &[u8] = b"dnssec test message";
+
+    fn check(algorithm: u8, key: &str, signature: &str) {
+        let (key, signature) = (hex(key), hex(signature));
+        assert!(verify(algorithm, &key, MESSAGE, &signature));
+        assert!(!verify(algorithm, &key, b"dnssec test messagf", &signature));
+
+        let mut tampered = signature.clone();
+        tampered[10] ^= 1;
+        assert!(!verify(algorithm, &key, MESSAGE, &tampered));
+        assert!(!verify(algorithm, &key, MESSAGE, &signature[1..]));
+    }
+
+    #[test]
+    fn test_rsa_sha256() {
+        check(
+            RSASHA256,
+            "03010001b94f95711be130e8f6981b5d00532f1d0d76d27876bb39de0116ebc286d2a18a7ac4f459aa77aec14d1192179254e0d8629b5c13b796ffd9449a196b007f9cd729d7b13d8a0fbbe2a74d7ba9a4af36cbfa667054bc3b13bdb03f9f6a8f2a38bd1144f7d5fea797055507281c1f014ec9aa6f7266fd47c87a30eda1f407f48b15",
+            "269275e4b1f0238f11e364462ef6872065142275f8d0d5efc6334ffe0c30513528bca56527fac9ddff6d98a7f49a123101e64a1681495fae47ee30be5cb8d18694c346b5046fd6f3a5938e2a1aba1ef6bffb555039a59a15ab66058abfb2645df47dd707f9f40d001bab77123e44216f36f9d7459a658986146873f6b0d3c17a",
+        );
+
+        // an exponent of 4104 bits
+        let key = hex("03010001b94f95711be130e8f6981b5d00532f1d0d76d27876bb39de0116ebc286d2a18a7ac4f459aa77aec14d1192179254e0d8629b5c13b796ffd9449a196b007f9cd729d7b13d8a0fbbe2a74d7ba9a4af36cbfa667054bc3b13bdb03f9f6a8f2a38bd1144f7d5fea797055507281c1f014ec9aa6f7266fd47c87a30eda1f407f48b15");
+        let mut long = vec![0, 2, 1];
+        long.extend(vec![0xFF; 513]);
+        long.extend(&key[4..]);
+        let signature = hex("269275e4b1f0238f11e364462ef6872065142275f8d0d5efc6334ffe0c30513528bca56527fac9ddff6d98a7f49a123101e64a1681495fae47ee30be5cb8d18694c346b5046fd6f3a5938e2a1aba1ef6bffb555039a59a15ab66058abfb2645df47dd707f9f40d001bab77123e44216f36f9d7459a658986146873f6b0d3c17a");
+        assert!(!verify(RSASHA256, &long, MESSAGE, &signature));
+    }
+
+    #[test]
+    fn test_ecdsa() {
+        check(
+            ECDSAP256SHA256,
+            "bd5a69a7f6a2f50f38d7747209bfd2dd46d86fd6e25667d4594a5d9d6493a922c34846c2bb37d605f75f5133c6756ae3a6647e06e624804faf8e05bacac46344",
+            "57b40b8dade87702cbc61917f838ae376a8d82db6e509d504015bc9c24e9a2d23a9ec809bc4ba442b59f47600a74563f1272dc1f238b7bd2f7ce403f1d08ba2c",
+        );
+        check(
+            ECDSAP384SHA384,
+            "5064e7c9e9243a8c170cdc7cd8c9168ada435329892fa04660c758d88a0ccdf79f4df81ad510c97a8fe66c0ac8bec7079ca7e363c21a51631a95895c67155f7061efdf63365b500b9a95065574c33e73fdae7cdf97742a3d54e1e47a6516c3b0",
+            "ce9e9ccea32265fe4000472869eab1bb4f8c8900b62f9e42e180df6b82539bb81590c553bd9319c6f7a76ff1c99a6fbd7a956d025d4bf6c3844dc6efbce922a4c19fd0ebcdcb2c4e462a0af4b50571e26651c4e504a74a8d1333043dca12a60b",
+        );
+
+        // not on the curve
+        let mut key = hex("bd5a69a7f6a2f50f38d7747209bfd2dd46d86fd6e25667d4594a5d9d6493a922c34846c2bb37d605f75f5133c6756ae3a6647e06e624804faf8e05bacac46344");
+        key[63] ^= 1;
+        let signature = hex("57b40b8dade87702cbc61917f838ae376a8d82db6e509d504015bc9c24e9a2d23a9ec809bc4ba442b59f47600a74563f1272dc1f238b7bd2f7ce403f1d08ba2c");
+        assert!(!verify(ECDSAP256SHA256, &key, MESSAGE, &signature));
+    }
+
+    #[test]
+    fn test_ed25519() {
+        check(
+            ED25519,
+            "c31db7a54395174c7b60c6018fb232e00d1c59ee4cf3bc35fa0219269cd491b0",
+            "67c9d29349a9eec7f804aa1515c024461f970e00e2a8c2a9c0d28a3cb73da4855f6c91de67d2eb9eac34c90c823dc39c519c138521383239fe7d171908eddd00",
+        );
+
+        // RFC 8032 7.1, test 1
+        let seed: [u8; 32] =
+            hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+                .try_into()
+                .unwrap();
+        let public_key = ed25519_public_key(&seed);
+        let signature = ed25519_sign(&seed, b"");
+        assert_eq!(
+            public_key,
+            hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+        );
+        assert_eq!(signature, hex("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"));
+        assert!(verify(ED25519, &public_key, b"", &signature));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(is_supported(RSASHA256) && is_supported(ED25519));
+        assert!(!is_supported(5) && !is_supported(16));
+        assert!(!verify(5, &[3, 1, 0, 1], MESSAGE, &[0; 64]));
+    }
+}
@@ -0,0 +1,663 @@
+// DNSSEC record data: DNSKEY, CDNSKEY, DS, CDS, RRSIG, NSEC (RFC 4034, RFC 7344),
+// NSEC3 and NSEC3PARAM (RFC 5155)
+
+use std::fmt;
+
+use crate::{
+    digest::{sha1, Digest},
+    encoder::Encoder,
+    error::{read_u16, read_u32, take, DecodeError, EncodeError, ParseError},
+    field::QType,
+    name::Name,
+    presentation::{
+        from_base32hex, from_base64, from_hex, parse_name, parse_number, parse_timestamp,
+        to_base32hex, to_base64, to_hex, to_timestamp,
+    },
+};
+
+// DNSKEY flag of keys that sign the zone's RRsets (RFC 4034 2.1.1)
+pub const ZONE_KEY: u16 = 0x0100;
+// DNSKEY flag of key signing keys, the ones DS records point at (RFC 4034 2.1.1)
+pub const SECURE_ENTRY_POINT: u16 = 0x0001;
+// The only protocol DNSKEY records have (RFC 4034 2.1.2)
+pub const DNSKEY_PROTOCOL: u8 = 3;
+
+// NSEC3 hash algorithm SHA-1 and the Opt-Out flag (RFC 5155 11)
+pub const NSEC3_SHA1: u8 = 1;
+const NSEC3_OPT_OUT: u8 = 0x01;
+
+// Also the rdata of CDNSKEY
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dnskey {
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: Vec<u8>,
+}
+
+// Also the rdata of CDS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ds {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+// Times are seconds since the epoch in serial number arithmetic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rrsig {
+    pub type_covered: QType,
+    pub algorithm: u8,
+    pub labels: u8,
+    pub original_ttl: u32,
+    pub expiration: u32,
+    pub inception: u32,
+    pub key_tag: u16,
+    pub signer: Name,
+    pub signature: Vec<u8>,
+}
+
+// `types` is kept sorted by value without duplicates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsec {
+    pub next: Name,
+    pub types: Vec<QType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsec3 {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+    pub next_hashed: Vec<u8>,
+    pub types: Vec<QType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsec3Param {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+}
+
+impl Dnskey {
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & ZONE_KEY != 0 && self.protocol == DNSKEY_PROTOCOL
+    }
+
+    // Checksum of the rdata identifying the key in DS and RRSIG records
+    // (RFC 4034 appendix B)
+    pub fn key_tag(&self) -> u16 {
+        let mut encoder = Encoder::uncompressed();
+        self.encode(&mut encoder);
+
+        let mut sum: u32 = 0;
+        for (i, byte) in encoder.finish().iter().enumerate() {
+            sum += if i & 1 == 0 {
+                (*byte as u32) << 8
+            } else {
+                *byte as u32
+            };
+        }
+        sum += (sum >> 16) & 0xFFFF;
+        sum as u16
+    }
+
+    // The DS record for the key at `owner`, with a digest over the owner
+    // name and the rdata (RFC 4034 5.1.4). `None` for unknown digest types.
+    pub fn to_ds(&self, owner: &Name, digest_type: u8) -> Option<Ds> {
+        let digest = Digest::from_ds_type(digest_type)?;
+        let mut encoder = Encoder::uncompressed();
+        encoder.put_name(&owner.to_lowercase(), false);
+        self.encode(&mut encoder);
+
+        Some(Ds {
+            key_tag: self.key_tag(),
+            algorithm: self.algorithm,
+            digest_type,
+            digest: digest.hash(&encoder.finish()),
+        })
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_u16(self.flags);
+        encoder.put_u8(self.protocol);
+        encoder.put_u8(self.algorithm);
+        encoder.put_slice(&self.public_key);
+    }
+
+    // Reads up to the end of `buf`, which the caller cuts at the end of the
+    // rdata
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<Dnskey, DecodeError> {
+        let header = take(buf, start_pos, 4)?;
+        Ok(Dnskey {
+            flags: u16::from_be_bytes([header[0], header[1]]),
+            protocol: header[2],
+            algorithm: header[3],
+            public_key: buf[start_pos + 4..].to_vec(),
+        })
+    }
+
+    // `flags protocol algorithm base64...`
+    pub fn from_tokens(tokens: &[String]) -> Result<Dnskey, ParseError> {
+        if tokens.len() < 4 {
+            return Err(ParseError::FieldCount("DNSKEY".to_string()));
+        }
+        Ok(Dnskey {
+            flags: parse_number(&tokens[0])?,
+            protocol: parse_number(&tokens[1])?,
+            algorithm: parse_number(&tokens[2])?,
+            public_key: from_base64(&tokens[3..].concat())?,
+        })
+    }
+}
+
+impl Ds {
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_u16(self.key_tag);
+        encoder.put_u8(self.algorithm);
+        encoder.put_u8(self.digest_type);
+        encoder.put_slice(&self.digest);
+    }
+
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<Ds, DecodeError> {
+        let header = take(buf, start_pos, 4)?;
+        Ok(Ds {
+            key_tag: u16::from_be_bytes([header[0], header[1]]),
+            algorithm: header[2],
+            digest_type: header[3],
+            digest: buf[start_pos + 4..].to_vec(),
+        })
+    }
+
+    // `key_tag algorithm digest_type hex...`
+    pub fn from_tokens(tokens: &[String]) -> Result<Ds, ParseError> {
+        if tokens.len() < 4 {
+            return Err(ParseError::FieldCount("DS".to_string()));
+        }
+        Ok(Ds {
+            key_tag: parse_number(&tokens[0])?,
+            algorithm: parse_number(&tokens[1])?,
+            digest_type: parse_number(&tokens[2])?,
+            digest: from_hex(&tokens[3..].concat())?,
+        })
+    }
+}
+
+impl Rrsig {
+    // The signer's name is never compressed (RFC 4034 3.1.7)
+    pub fn encode(&self, encoder: &mut Encoder) {
+        self.encode_header(encoder);
+        encoder.put_slice(&self.signature);
+    }
+
+    // Everything but the signature, which is what gets signed ahead of the
+    // RRset (RFC 4034 3.1.8.1)
+    pub fn encode_header(&self, encoder: &mut Encoder) {
+        encoder.put_u16(self.type_covered.to_u16());
+        encoder.put_u8(self.algorithm);
+        encoder.put_u8(self.labels);
+        encoder.put_u32(self.original_ttl);
+        encoder.put_u32(self.expiration);
+        encoder.put_u32(self.inception);
+        encoder.put_u16(self.key_tag);
+        encoder.put_name(&self.signer, false);
+    }
+
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<Rrsig, DecodeError> {
+        let header = take(buf, start_pos, 18)?;
+        let (signer, next) = Name::from_bytes(buf, start_pos + 18)?;
+        Ok(Rrsig {
+            type_covered: QType::from_u16(u16::from_be_bytes([header[0], header[1]])),
+            algorithm: header[2],
+            labels: header[3],
+            original_ttl: read_u32(buf, start_pos + 4)?,
+            expiration: read_u32(buf, start_pos + 8)?,
+            inception: read_u32(buf, start_pos + 12)?,
+            key_tag: read_u16(buf, start_pos + 16)?,
+            signer,
+            signature: buf[next..].to_vec(),
+        })
+    }
+
+    // `type algorithm labels ttl expiration inception key_tag signer base64...`
+    pub fn from_tokens(tokens: &[String], origin: &Name) -> Result<Rrsig, ParseError> {
+        if tokens.len() < 9 {
+            return Err(ParseError::FieldCount("RRSIG".to_string()));
+        }
+        Ok(Rrsig {
+            type_covered: tokens[0].parse()?,
+            algorithm: parse_number(&tokens[1])?,
+            labels: parse_number(&tokens[2])?,
+            original_ttl: parse_number(&tokens[3])?,
+            expiration: parse_timestamp(&tokens[4])?,
+            inception: parse_timestamp(&tokens[5])?,
+            key_tag: parse_number(&tokens[6])?,
+            signer: parse_name(&tokens[7], origin)?,
+            signature: from_base64(&tokens[8..].concat())?,
+        })
+    }
+}
+
+impl Nsec {
+    // The next name is never compressed (RFC 4034 4.1.1)
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_name(&self.next, false);
+        encode_type_bitmap(encoder, &self.types);
+    }
+
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<Nsec, DecodeError> {
+        let (next, idx) = Name::from_bytes(buf, start_pos)?;
+        Ok(Nsec {
+            next,
+            types: type_bitmap_from_bytes(buf, idx, start_pos)?,
+        })
+    }
+
+    // `next type...`
+    pub fn from_tokens(tokens: &[String], origin: &Name) -> Result<Nsec, ParseError> {
+        let next = tokens
+            .first()
+            .ok_or_else(|| ParseError::FieldCount("NSEC".to_string()))?;
+        Ok(Nsec {
+            next: parse_name(next, origin)?,
+            types: types_from_tokens(&tokens[1..])?,
+        })
+    }
+}
+
+impl Nsec3 {
+    // Unsigned delegations may lie in the span this record covers
+    pub fn opt_out(&self) -> bool {
+        self.flags & NSEC3_OPT_OUT != 0
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        encoder.put_u8(self.hash_algorithm);
+        encoder.put_u8(self.flags);
+        encoder.put_u16(self.iterations);
+        encoder.put_character_string(&self.salt)?;
+        encoder.put_character_string(&self.next_hashed)?;
+        encode_type_bitmap(encoder, &self.types);
+        Ok(())
+    }
+
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<Nsec3, DecodeError> {
+        let (param, idx) = Nsec3Param::from_bytes(buf, start_pos)?;
+        let length = take(buf, idx, 1)?[0] as usize;
+        let next_hashed = take(buf, idx + 1, length)?.to_vec();
+        if next_hashed.is_empty() {
+            return Err(DecodeError::BadRdata(start_pos));
+        }
+        Ok(Nsec3 {
+            hash_algorithm: param.hash_algorithm,
+            flags: param.flags,
+            iterations: param.iterations,
+            salt: param.salt,
+            next_hashed,
+            types: type_bitmap_from_bytes(buf, idx + 1 + length, start_pos)?,
+        })
+    }
+
+    // `algorithm flags iterations salt next_hashed type...`
+    pub fn from_tokens(tokens: &[String]) -> Result<Nsec3, ParseError> {
+        if tokens.len() < 5 {
+            return Err(ParseError::FieldCount("NSEC3".to_string()));
+        }
+        let param = Nsec3Param::from_tokens(&tokens[..4])?;
+        let next_hashed = from_base32hex(&tokens[4])?;
+        if next_hashed.is_empty() || next_hashed.len() > 255 {
+            return Err(ParseError::InvalidValue(tokens[4].clone()));
+        }
+        Ok(Nsec3 {
+            hash_algorithm: param.hash_algorithm,
+            flags: param.flags,
+            iterations: param.iterations,
+            salt: param.salt,
+            next_hashed,
+            types: types_from_tokens(&tokens[5..])?,
+        })
+    }
+}
+
+impl Nsec3Param {
+    pub fn encode(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        encoder.put_u8(self.hash_algorithm);
+        encoder.put_u8(self.flags);
+        encoder.put_u16(self.iterations);
+        encoder.put_character_string(&self.salt)
+    }
+
+    // NSEC3 starts with the same fields
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<(Nsec3Param, usize), DecodeError> {
+        let header = take(buf, start_pos, 5)?;
+        let length = header[4] as usize;
+        let salt = take(buf, start_pos + 5, length)?.to_vec();
+        let param = Nsec3Param {
+            hash_algorithm: header[0],
+            flags: header[1],
+            iterations: u16::from_be_bytes([header[2], header[3]]),
+            salt,
+        };
+        Ok((param, start_pos + 5 + length))
+    }
+
+    // `algorithm flags iterations salt`, with `-` for an empty salt
+    pub fn from_tokens(tokens: &[String]) -> Result<Nsec3Param, ParseError> {
+        if tokens.len() != 4 {
+            return Err(ParseError::FieldCount("NSEC3PARAM".to_string()));
+        }
+        let salt = match tokens[3].as_str() {
+            "-" => Vec::new(),
+            hex => from_hex(hex)?,
+        };
+        if salt.len() > 255 {
+            return Err(ParseError::InvalidValue(tokens[3].clone()));
+        }
+        Ok(Nsec3Param {
+            hash_algorithm: parse_number(&tokens[0])?,
+            flags: parse_number(&tokens[1])?,
+            iterations: parse_number(&tokens[2])?,
+            salt,
+        })
+    }
+}
+
+// The hashed owner name of `name` in an NSEC3 zone: SHA-1 over the canonical
+// wire form and the salt, then `iterations` more times over the previous hash
+// and the salt (RFC 5155 5)
+pub fn nsec3_hash(name: &Name, salt: &[u8], iterations: u16) -> Vec<u8> {
+    let mut encoder = Encoder::uncompressed();
+    encoder.put_name(&name.to_lowercase(), false);
+    let mut data = encoder.finish();
+    data.extend_from_slice(salt);
+
+    let mut hash = sha1(&data).to_vec();
+    for _ in 0..iterations {
+        hash.extend_from_slice(salt);
+        hash = sha1(&hash).to_vec();
+    }
+    hash
+}
+
+// Window blocks of up to 256 types each, in increasing order, with trailing
+// zero octets left out (RFC 4034 4.1.2)
+fn encode_type_bitmap(encoder: &mut Encoder, types: &[QType]) {
+    let mut values: Vec<u16> = types.iter().map(|qtype| qtype.to_u16()).collect();
+    values.sort_unstable();
+    values.dedup();
+
+    for window in values.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let mut bitmap = [0u8; 32];
+        for value in window {
+            let bit = (value & 0xFF) as usize;
+            bitmap[bit / 8] |= 0x80 >> (bit % 8);
+        }
+        let length = (window[window.len() - 1] & 0xFF) as usize / 8 + 1;
+        encoder.put_u8((window[0] >> 8) as u8);
+        encoder.put_u8(length as u8);
+        encoder.put_slice(&bitmap[..length]);
+    }
+}
+
+// Reads the windows from `idx` to the end of `buf`
+fn type_bitmap_from_bytes(
+    buf: &[u8],
+    mut idx: usize,
+    start_pos: usize,
+) -> Result<Vec<QType>, DecodeError> {
+    let mut types: Vec<QType> = Vec::new();
+    let mut last_window: Option<u8> = None;
+
+    while idx < buf.len() {
+        let header = take(buf, idx, 2)?;
+        let (window, length) = (header[0], header[1] as usize);
+        if last_window.is_some_and(|last| window <= last) || !(1..=32).contains(&length) {
+            return Err(DecodeError::BadRdata(start_pos));
+        }
+        let bitmap = take(buf, idx + 2, length)?;
+        for (i, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    let value = ((window as u16) << 8) | (i * 8 + bit) as u16;
+                    types.push(QType::from_u16(value));
+                }
+            }
+        }
+        last_window = Some(window);
+        idx += 2 + length;
+    }
+
+    Ok(types)
+}
+
+fn types_from_tokens(tokens: &[String]) -> Result<Vec<QType>, ParseError> {
+    let mut types: Vec<QType> = tokens
+        .iter()
+        .map(|token| token.parse())
+        .collect::<Result<_, _>>()?;
+    types.sort_by_key(|qtype| qtype.to_u16());
+    types.dedup();
+    Ok(types)
+}
+
+fn fmt_types(f: &mut fmt::Formatter<'_>, types: &[QType]) -> fmt::Result {
+    for qtype in types {
+        write!(f, " {}", qtype)?;
+    }
+    Ok(())
+}
+
+fn fmt_salt(f: &mut fmt::Formatter<'_>, salt: &[u8]) -> fmt::Result {
+    if salt.is_empty() {
+        write!(f, "-")
+    } else {
+        write!(f, "{}", to_hex(salt))
+    }
+}
+
+impl fmt::Display for Dnskey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.flags,
+            self.protocol,
+            self.algorithm,
+            to_base64(&self.public_key)
+        )
+    }
+}
+
+impl fmt::Display for Ds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.key_tag,
+            self.algorithm,
+            self.digest_type,
+            to_hex(&self.digest)
+        )
+    }
+}
+
+impl fmt::Display for Rrsig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {:#} {}",
+            self.type_covered,
+            self.algorithm,
+            self.labels,
+            self.original_ttl,
+            to_timestamp(self.expiration),
+            to_timestamp(self.inception),
+            self.key_tag,
+            self.signer,
+            to_base64(&self.signature)
+        )
+    }
+}
+
+impl fmt::Display for Nsec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.next)?;
+        fmt_types(f, &self.types)
+    }
+}
+
+impl fmt::Display for Nsec3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
+        )?;
+        fmt_salt(f, &self.salt)?;
+        write!(f, " {}", to_base32hex(&self.next_hashed))?;
+        fmt_types(f, &self.types)
+    }
+}
+
+impl fmt::Display for Nsec3Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
+        )?;
+        fmt_salt(f, &self.salt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bytes(encode: impl Fn(&mut Encoder)) -> Vec<u8> {
+        let mut encoder = Encoder::uncompressed();
+        encode(&mut encoder);
+        encoder.finish()
+    }
+
+    fn tokens(s: &str) -> Vec<String> {
+        crate::presentation::tokenize(s).unwrap()
+    }
+
+    #[test]
+    fn test_nsec_type_bitmap() {
+        // RFC 4034 4.3
+        let nsec = Nsec::from_tokens(
+            &tokens("host.example.com. A MX RRSIG NSEC TYPE1234"),
+            &Name::root(),
+        )
+        .unwrap();
+        let mut bitmap = vec![0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03];
+        bitmap.extend_from_slice(&[0x04, 0x1b]);
+        bitmap.extend_from_slice(&[0x00; 26]);
+        bitmap.push(0x20);
+
+        let bytes = to_bytes(|encoder| nsec.encode(encoder));
+        let name_length = nsec.next.wire_len();
+        assert_eq!(bytes[name_length..], bitmap);
+        assert_eq!(Nsec::from_bytes(&bytes, 0).unwrap(), nsec);
+        assert_eq!(
+            nsec.to_string(),
+            "host.example.com. A MX RRSIG NSEC TYPE1234"
+        );
+    }
+
+    #[test]
+    fn test_nsec3_presentation() {
+        // RFC 5155 appendix A, with the types sorted by value
+        let nsec3 = Nsec3::from_tokens(&tokens(
+            "1 1 12 aabbccdd 2t7b4g4vsa5smi47k61mv5bv1a22bojr MX DNSKEY NS SOA NSEC3PARAM RRSIG",
+        ))
+        .unwrap();
+        assert_eq!(nsec3.next_hashed.len(), 20);
+        assert_eq!(
+            nsec3.to_string(),
+            "1 1 12 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR NS SOA MX RRSIG DNSKEY NSEC3PARAM"
+        );
+        let bytes = to_bytes(|encoder| nsec3.encode(encoder).unwrap());
+        assert_eq!(Nsec3::from_bytes(&bytes, 0).unwrap(), nsec3);
+
+        let param = Nsec3Param::from_tokens(&tokens("1 0 0 -")).unwrap();
+        assert!(param.salt.is_empty());
+        assert_eq!(param.to_string(), "1 0 0 -");
+        let bytes = to_bytes(|encoder| param.encode(encoder).unwrap());
+        assert_eq!(bytes, [1, 0, 0, 0, 0]);
+        assert_eq!(Nsec3Param::from_bytes(&bytes, 0).unwrap(), (param, 5));
+    }
+
+    #[test]
+    fn test_invalid_type_bitmap() {
+        // windows out of order
+        let bytes = [0, 1, 0x02, 0x01, 0x40, 0x00, 0x01, 0x40];
+        assert_eq!(Nsec::from_bytes(&bytes, 0), Err(DecodeError::BadRdata(0)));
+        // empty and oversized windows
+        assert_eq!(
+            Nsec::from_bytes(&[0, 0x00, 0x00], 0),
+            Err(DecodeError::BadRdata(0))
+        );
+        let mut bytes = vec![0, 0x00, 33];
+        bytes.extend_from_slice(&[0xFF; 33]);
+        assert_eq!(Nsec::from_bytes(&bytes, 0), Err(DecodeError::BadRdata(0)));
+        // NSEC3 with an empty next hashed owner name
+        assert_eq!(
+            Nsec3::from_bytes(&[1, 0, 0, 0, 0, 0], 0),
+            Err(DecodeError::BadRdata(0))
+        );
+    }
+
+    #[test]
+    fn test_dnskey_key_tag_and_ds() {
+        // RFC 4034 5.4
+        let dnskey = Dnskey::from_tokens(&tokens(
+            "256 3 5 AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZ\
+             DRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc\
+             nOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+        ))
+        .unwrap();
+        assert!(dnskey.is_zone_key());
+        assert_eq!(dnskey.key_tag(), 60485);
+
+        let owner = "DSKEY.example.com".parse().unwrap();
+        let ds = dnskey.to_ds(&owner, 1).unwrap();
+        assert_eq!(
+            ds.to_string(),
+            "60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118"
+        );
+        assert_eq!(dnskey.to_ds(&owner, 3), None);
+    }
+
+    #[test]
+    fn test_nsec3_hash() {
+        // RFC 5155 appendix A
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+        let hash = |name: &str| to_base32hex(&nsec3_hash(&name.parse().unwrap(), &salt, 12));
+        assert_eq!(hash("example"), "0P9MHAVEQVM6T7VBL5LOP2U3T2RP3TOM");
+        assert_eq!(hash("a.EXAMPLE"), "35MTHGPGCU1QG68FAB165KLNSNK3DPVL");
+    }
+
+    #[test]
+    fn test_rrsig_presentation() {
+        // RFC 4034 3.3
+        let s = "A 5 3 86400 20030322173103 20030220173103 2642 example.com. \
+                 oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6o\
+                 B9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkG\
+                 J5D6fwFm8nN+6pBzeDQfsS3Ap3o=";
+        let rrsig = Rrsig::from_tokens(&tokens(s), &Name::root()).unwrap();
+        assert_eq!(rrsig.type_covered, QType::A);
+        assert_eq!(rrsig.expiration, 1048354263);
+        assert_eq!(rrsig.signature.len(), 128);
+        assert_eq!(rrsig.to_string(), s);
+
+        let bytes = to_bytes(|encoder| rrsig.encode(encoder));
+        assert_eq!(Rrsig::from_bytes(&bytes, 0).unwrap(), rrsig);
+    }
+}
@@ -0,0 +1,197 @@
+// EDNS(0) (RFC 6891). The OPT pseudo-record is kept out of the additional
+// section and carried as `Packet::edns` instead.
+
+use crate::{
+    error::{read_u16, take, DecodeError},
+    field::{Class, QType},
+    name::Name,
+    rdata::RData,
+    resource_records::ResourceRecord,
+};
+
+// Extended RCODE for an EDNS version the responder does not implement
+pub const BADVERS: u16 = 16;
+
+// Advertised sizes below the classic limit are treated as the limit (6.2.5)
+pub const MIN_UDP_PAYLOAD: u16 = 512;
+
+// Extended DNS Error option code (RFC 8914)
+pub const EXTENDED_ERROR: u16 = 15;
+
+// DO bit in the flags half of the OPT TTL (RFC 3225)
+const DNSSEC_OK: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    pub udp_payload_size: u16,
+    // upper 8 bits of the 12-bit RCODE
+    pub extended_rcode: u8,
+    pub version: u8,
+    pub dnssec_ok: bool,
+    // the flag bits other than DO, zero unless some extension defines them
+    pub z: u16,
+    pub options: Vec<EdnsOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl EdnsOption {
+    // INFO-CODE followed by the UTF-8 EXTRA-TEXT
+    pub fn extended_error(info_code: u16, text: &str) -> Self {
+        let mut data = info_code.to_be_bytes().to_vec();
+        data.extend_from_slice(text.as_bytes());
+        Self {
+            code: EXTENDED_ERROR,
+            data,
+        }
+    }
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Self {
+        Self {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: Vec::new(),
+        }
+    }
+
+    // The largest UDP message the sender is able to receive
+    pub fn max_udp_size(&self) -> usize {
+        self.udp_payload_size.max(MIN_UDP_PAYLOAD) as usize
+    }
+
+    pub fn to_record(&self) -> ResourceRecord {
+        let mut flags = self.z & !DNSSEC_OK;
+        if self.dnssec_ok {
+            flags |= DNSSEC_OK;
+        }
+        let ttl = (self.extended_rcode as u32) << 24 | (self.version as u32) << 16 | flags as u32;
+
+        let mut data: Vec<u8> = Vec::new();
+        for option in &self.options {
+            data.extend_from_slice(&option.code.to_be_bytes());
+            data.extend_from_slice(&(option.data.len() as u16).to_be_bytes());
+            data.extend_from_slice(&option.data);
+        }
+
+        ResourceRecord::new(
+            Name::root(),
+            Class::from_u16(self.udp_payload_size),
+            ttl,
+            RData::Opaque(QType::OPT, data),
+        )
+    }
+
+    // `offset` is where the record starts in the message, for errors
+    pub fn from_record(record: &ResourceRecord, offset: usize) -> Result<Edns, DecodeError> {
+        let data = match &record.rdata {
+            RData::Opaque(qtype, data) if *qtype == QType::OPT => data,
+            _ => return Err(DecodeError::BadOpt(offset)),
+        };
+        if !record.name.is_root() {
+            return Err(DecodeError::BadOpt(offset));
+        }
+
+        let mut options: Vec<EdnsOption> = Vec::new();
+        let mut idx = 0;
+        while idx < data.len() {
+            let code = read_u16(data, idx).map_err(|_| DecodeError::BadOpt(offset))?;
+            let length = read_u16(data, idx + 2).map_err(|_| DecodeError::BadOpt(offset))?;
+            let value =
+                take(data, idx + 4, length as usize).map_err(|_| DecodeError::BadOpt(offset))?;
+            options.push(EdnsOption {
+                code,
+                data: value.to_vec(),
+            });
+            idx += 4 + value.len();
+        }
+
+        let flags = record.ttl as u16;
+
+        Ok(Edns {
+            udp_payload_size: record.class.to_u16(),
+            extended_rcode: (record.ttl >> 24) as u8,
+            version: (record.ttl >> 16) as u8,
+            dnssec_ok: flags & DNSSEC_OK != 0,
+            z: flags & !DNSSEC_OK,
+            options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edns_record_round_trip() {
+        let edns = Edns {
+            udp_payload_size: 4096,
+            extended_rcode: 1,
+            version: 0,
+            dnssec_ok: true,
+            z: 0,
+            options: vec![
+                EdnsOption {
+                    code: 10,
+                    data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                },
+                EdnsOption {
+                    code: 12,
+                    data: vec![],
+                },
+            ],
+        };
+
+        let record = edns.to_record();
+        assert_eq!(record.class.to_u16(), 4096);
+        assert_eq!(record.ttl, 0x0100_8000);
+        assert_eq!(
+            record.to_bytes().unwrap(),
+            vec![
+                0, // root name
+                0, 41, // qtype OPT (41)
+                16, 0, // udp payload size 4096
+                1, 0, 128, 0, // extended rcode, version, DO
+                0, 16, // rdlength
+                0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8, // COOKIE
+                0, 12, 0, 0, // empty padding
+            ]
+        );
+
+        let (decoded, _) = ResourceRecord::from_bytes(&record.to_bytes().unwrap(), 0).unwrap();
+        assert_eq!(Edns::from_record(&decoded, 0), Ok(edns));
+    }
+
+    #[test]
+    fn test_malformed_edns_record() {
+        let mut record = Edns::new(1232).to_record();
+        record.rdata = RData::Opaque(QType::OPT, vec![0, 10, 0, 8, 1]);
+        assert_eq!(Edns::from_record(&record, 40), Err(DecodeError::BadOpt(40)));
+
+        let mut record = Edns::new(1232).to_record();
+        record.name = "example.com".parse().unwrap();
+        assert_eq!(Edns::from_record(&record, 40), Err(DecodeError::BadOpt(40)));
+    }
+
+    #[test]
+    fn test_extended_error_option() {
+        let option = EdnsOption::extended_error(6, "bad");
+        assert_eq!(option.code, EXTENDED_ERROR);
+        assert_eq!(option.data, vec![0, 6, b'b', b'a', b'd']);
+    }
+
+    #[test]
+    fn test_edns_max_udp_size() {
+        assert_eq!(Edns::new(100).max_udp_size(), 512);
+        assert_eq!(Edns::new(4096).max_udp_size(), 4096);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use crate::{error::EncodeError, name::Name};
+
+// Pointers only have 14 bits for the offset
+const MAX_POINTER_OFFSET: usize = 0x3FFF;
+
+// Message writer that remembers where every written name suffix starts so
+// later occurrences can be replaced by a compression pointer (RFC 1035 4.1.4).
+#[derive(Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+    suffixes: HashMap<Name, u16>,
+    uncompressed: bool,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Writer for standalone records, where a pointer would have nothing to
+    // point into
+    pub fn uncompressed() -> Self {
+        Self {
+            uncompressed: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn put_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_slice(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    // <character-string> (RFC 1035 3.3): a length octet followed by at most
+    // 255 bytes
+    pub fn put_character_string(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let length = u8::try_from(bytes.len())
+            .map_err(|_| EncodeError::CharacterStringTooLong(bytes.len()))?;
+        self.put_u8(length);
+        self.put_slice(bytes);
+        Ok(())
+    }
+
+    // Overwrites a previously written u16, used to patch `rdlength`
+    pub fn set_u16(&mut self, pos: usize, value: u16) {
+        self.bytes[pos..pos + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    // Writes `name`, pointing at an earlier copy of its longest known suffix
+    // when `compress` is set. Uncompressed names are never used as targets.
+    pub fn put_name(&mut self, name: &Name, compress: bool) {
+        let compress = compress && !self.uncompressed;
+
+        for (skip, label) in name.labels().iter().enumerate() {
+            let suffix = name.suffix(skip);
+
+            if compress {
+                if let Some(&offset) = self.suffixes.get(&suffix) {
+                    self.put_u16(0xC000 | offset);
+                    return;
+                }
+
+                if self.bytes.len() <= MAX_POINTER_OFFSET {
+                    self.suffixes.insert(suffix, self.bytes.len() as u16);
+                }
+            }
+
+            self.bytes.push(label.len() as u8);
+            self.bytes.extend_from_slice(label);
+        }
+
+        // Null byte to terminate the domain name
+        self.bytes.push(0);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_name_compresses_suffixes() {
+        let mut encoder = Encoder::new();
+        encoder.put_name(&"en.example.com".parse().unwrap(), true);
+        encoder.put_name(&"es.EXAMPLE.com".parse().unwrap(), true);
+        encoder.put_name(&"en.example.com".parse().unwrap(), true);
+
+        let expected_bytes = vec![
+            2, 101, 110, // "en" label
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            2, 101, 115, // "es" label
+            192, 3, // pointer to "example.com"
+            192, 0, // pointer to "en.example.com"
+        ];
+        assert_eq!(encoder.finish(), expected_bytes);
+    }
+
+    #[test]
+    fn test_uncompressed_names_are_not_targets() {
+        let mut encoder = Encoder::new();
+        encoder.put_name(&"example.com".parse().unwrap(), false);
+        encoder.put_name(&"example.com".parse().unwrap(), true);
+        encoder.put_name(&"example.com".parse().unwrap(), false);
+
+        let name = "example.com".parse::<Name>().unwrap().to_bytes();
+        let mut expected_bytes = name.clone();
+        expected_bytes.extend(&name);
+        expected_bytes.extend(&name);
+        assert_eq!(encoder.finish(), expected_bytes);
+    }
+
+    #[test]
+    fn test_long_character_strings_are_rejected() {
+        let mut encoder = Encoder::new();
+        encoder.put_character_string(&[b'a'; 255]).unwrap();
+        assert_eq!(encoder.len(), 256);
+        assert_eq!(
+            encoder.put_character_string(&[b'a'; 256]),
+            Err(EncodeError::CharacterStringTooLong(256))
+        );
+        assert_eq!(encoder.len(), 256);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{io, net::SocketAddr, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{field::QType, name::Name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    #[error("message truncated: needed {needed} bytes at offset {offset}")]
+    Truncated { offset: usize, needed: usize },
+    #[error("invalid label length {length} at offset {offset}")]
+    BadLabelLength { offset: usize, length: u8 },
+    #[error("name starting at offset {0} is longer than 255 bytes")]
+    NameTooLong(usize),
+    #[error("compression pointer loop at offset {0}")]
+    PointerLoop(usize),
+    #[error("compression pointer at offset {0} does not point backwards")]
+    ForwardPointer(usize),
+    #[error("malformed record data at offset {0}")]
+    BadRdata(usize),
+    #[error("invalid or duplicate OPT record at offset {0}")]
+    BadOpt(usize),
+    #[error("{0} trailing bytes after the last record")]
+    TrailingData(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum EncodeError {
+    #[error("character-string of {0} bytes, longer than 255")]
+    CharacterStringTooLong(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("empty label in {0:?}")]
+    EmptyLabel(String),
+    #[error("label longer than 63 bytes in {0:?}")]
+    LabelTooLong(String),
+    #[error("name longer than 255 bytes: {0:?}")]
+    NameTooLong(String),
+    #[error("invalid escape sequence in {0:?}")]
+    BadEscape(String),
+    #[error("unknown record type {0:?}")]
+    UnknownType(String),
+    #[error("unknown class {0:?}")]
+    UnknownClass(String),
+    #[error("invalid value {0:?}")]
+    InvalidValue(String),
+    #[error("unterminated quoted string in {0:?}")]
+    Unterminated(String),
+    #[error("wrong number of fields for {0} record data")]
+    FieldCount(String),
+}
+
+#[derive(Debug, Error)]
+pub enum UpstreamError {
+    #[error("no reply from {0} in time")]
+    Timeout(SocketAddr),
+    #[error("no upstream configured")]
+    NoUpstream,
+    #[error("reply from {0} does not match the query")]
+    Mismatch(SocketAddr),
+    #[error("malformed reply: {0}")]
+    Malformed(#[from] DecodeError),
+    #[error("query cannot be encoded: {0}")]
+    Unencodable(#[from] EncodeError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum ZoneError {
+    #[error("reading {}: {source}", .file.display())]
+    Io { file: PathBuf, source: io::Error },
+    #[error("{}: line {line}: {source}", .file.display())]
+    Syntax {
+        file: PathBuf,
+        line: usize,
+        source: ParseError,
+    },
+    #[error("{}: line {line}: no TTL and no $TTL before it", .file.display())]
+    NoTtl { file: PathBuf, line: usize },
+    #[error("{}: line {line}: {name} is outside the zone", .file.display())]
+    OutOfZone {
+        file: PathBuf,
+        line: usize,
+        name: Name,
+    },
+    #[error("$INCLUDE nested more than {0} deep")]
+    IncludeDepth(usize),
+    #[error("no SOA record at the apex of {0}")]
+    NoSoa(Name),
+}
+
+// Why an answer failed validation, reported to clients as an Extended DNS
+// Error (RFC 8914)
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DnssecError {
+    #[error("no DNSKEY of {0} matches its DS records")]
+    DnskeyMissing(Name),
+    #[error("no valid RRSIG for {0} {1}")]
+    RrsigsMissing(Name, QType),
+    #[error("signature expired")]
+    SignatureExpired,
+    #[error("signature not yet valid")]
+    SignatureNotYetValid,
+    #[error("signature does not verify")]
+    BadSignature,
+    #[error("no proof that {0} does not exist")]
+    NsecMissing(Name),
+    #[error("looking up {0} {1} failed")]
+    Lookup(Name, QType),
+}
+
+impl DnssecError {
+    // INFO-CODE of the Extended DNS Error (RFC 8914 4)
+    pub fn info_code(&self) -> u16 {
+        match self {
+            DnssecError::BadSignature => 6,
+            DnssecError::SignatureExpired => 7,
+            DnssecError::SignatureNotYetValid => 8,
+            DnssecError::DnskeyMissing(_) => 9,
+            DnssecError::RrsigsMissing(_, _) => 10,
+            DnssecError::NsecMissing(_) => 12,
+            DnssecError::Lookup(_, _) => 23,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("gave up after {0} referrals")]
+    TooManyReferrals(usize),
+    #[error("gave up after {0} queries")]
+    TooManyQueries(usize),
+    #[error("nameserver names nested more than {0} deep")]
+    TooDeep(usize),
+    #[error("CNAME chain longer than {0}")]
+    CnameChain(usize),
+    #[error("no address for any nameserver of {0}")]
+    NoNameserver(Name),
+    #[error("{0} answered with rcode {1}")]
+    Rcode(SocketAddr, u16),
+    #[error(transparent)]
+    Upstream(#[from] UpstreamError),
+    #[error("bogus answer: {0}")]
+    Bogus(#[from] DnssecError),
+}
+
+// Returns `data[offset..offset + len]`, or `Truncated` if the buffer is too short
+pub fn take(data: &[u8], offset: usize, len: usize) -> Result<&[u8], DecodeError> {
+    data.get(offset..offset + len)
+        .ok_or(DecodeError::Truncated {
+            offset,
+            needed: len,
+        })
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16, DecodeError> {
+    let bytes = take(data, offset, 2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    let bytes = take(data, offset, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -1,75 +1,196 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum QType {
-    A = 1,
-    NS = 2,
-    MD = 3,
-    MF = 4,
-    CNAME = 5,
-    SOA = 6,
-    MB = 7,
-    MG = 8,
-    MR = 9,
-    NULL = 10,
-    WKS = 11,
-    PTR = 12,
-    HINFO = 13,
-    MINFO = 14,
-    MX = 15,
-    TXT = 16,
-}
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
-impl QType {
-    pub fn to_u16(self) -> u16 {
-        self as u16
-    }
+use crate::error::ParseError;
 
-    pub fn from_u16(value: u16) -> QType {
-        match value {
-            1 => QType::A,
-            2 => QType::NS,
-            3 => QType::MD,
-            4 => QType::MF,
-            5 => QType::CNAME,
-            6 => QType::SOA,
-            7 => QType::MB,
-            8 => QType::MG,
-            9 => QType::MR,
-            10 => QType::NULL,
-            11 => QType::WKS,
-            12 => QType::PTR,
-            13 => QType::HINFO,
-            14 => QType::MINFO,
-            15 => QType::MX,
-            16 => QType::TXT,
-            _ => panic!("Unknown QType"),
+// Declares a registry of 16-bit DNS parameters as an enum with one variant per
+// assigned value plus `Unknown(u16)` for everything else (RFC 3597), together
+// with the wire and presentation conversions. Unknown values are shown in the
+// generic `<prefix><value>` syntax, e.g. `TYPE1234` or `CLASS5`.
+macro_rules! registry {
+    (
+        $name:ident, $prefix:literal, $error:ident,
+        { $($variant:ident = $value:literal => $mnemonic:literal,)* }
+    ) => {
+        #[derive(Debug, Clone, Copy)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(u16),
         }
-    }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Class {
-    IN = 1,
-    CS = 2,
-    CH = 3,
-    HS = 4,
-}
+        impl $name {
+            pub fn to_u16(self) -> u16 {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
 
-impl Class {
-    pub fn to_u16(self) -> u16 {
-        self as u16
-    }
+            pub fn from_u16(value: u16) -> $name {
+                match value {
+                    $($value => $name::$variant,)*
+                    _ => $name::Unknown(value),
+                }
+            }
+        }
 
-    pub fn from_u16(value: u16) -> Class {
-        match value {
-            1 => Class::IN,
-            2 => Class::CS,
-            3 => Class::CH,
-            4 => Class::HS,
-            _ => panic!("Unknown Class"),
+        // `Unknown(1)` and the named variant for 1 are the same value
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.to_u16() == other.to_u16()
+            }
         }
-    }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.to_u16().hash(state);
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match $name::from_u16(self.to_u16()) {
+                    $($name::$variant => write!(f, $mnemonic),)*
+                    $name::Unknown(value) => write!(f, concat!($prefix, "{}"), value),
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let upper = s.to_ascii_uppercase();
+                match upper.as_str() {
+                    $($mnemonic => Ok($name::$variant),)*
+                    _ => upper
+                        .strip_prefix($prefix)
+                        .filter(|digits| {
+                            !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+                        })
+                        .and_then(|digits| digits.parse::<u16>().ok())
+                        .map($name::from_u16)
+                        .ok_or_else(|| ParseError::$error(s.to_string())),
+                }
+            }
+        }
+    };
 }
 
+// IANA "Resource Record (RR) TYPEs" registry, QTYPEs included
+registry!(QType, "TYPE", UnknownType, {
+    A = 1 => "A",
+    NS = 2 => "NS",
+    MD = 3 => "MD",
+    MF = 4 => "MF",
+    CNAME = 5 => "CNAME",
+    SOA = 6 => "SOA",
+    MB = 7 => "MB",
+    MG = 8 => "MG",
+    MR = 9 => "MR",
+    NULL = 10 => "NULL",
+    WKS = 11 => "WKS",
+    PTR = 12 => "PTR",
+    HINFO = 13 => "HINFO",
+    MINFO = 14 => "MINFO",
+    MX = 15 => "MX",
+    TXT = 16 => "TXT",
+    RP = 17 => "RP",
+    AFSDB = 18 => "AFSDB",
+    X25 = 19 => "X25",
+    ISDN = 20 => "ISDN",
+    RT = 21 => "RT",
+    NSAP = 22 => "NSAP",
+    NSAPPTR = 23 => "NSAP-PTR",
+    SIG = 24 => "SIG",
+    KEY = 25 => "KEY",
+    PX = 26 => "PX",
+    GPOS = 27 => "GPOS",
+    AAAA = 28 => "AAAA",
+    LOC = 29 => "LOC",
+    NXT = 30 => "NXT",
+    EID = 31 => "EID",
+    NIMLOC = 32 => "NIMLOC",
+    SRV = 33 => "SRV",
+    ATMA = 34 => "ATMA",
+    NAPTR = 35 => "NAPTR",
+    KX = 36 => "KX",
+    CERT = 37 => "CERT",
+    A6 = 38 => "A6",
+    DNAME = 39 => "DNAME",
+    SINK = 40 => "SINK",
+    OPT = 41 => "OPT",
+    APL = 42 => "APL",
+    DS = 43 => "DS",
+    SSHFP = 44 => "SSHFP",
+    IPSECKEY = 45 => "IPSECKEY",
+    RRSIG = 46 => "RRSIG",
+    NSEC = 47 => "NSEC",
+    DNSKEY = 48 => "DNSKEY",
+    DHCID = 49 => "DHCID",
+    NSEC3 = 50 => "NSEC3",
+    NSEC3PARAM = 51 => "NSEC3PARAM",
+    TLSA = 52 => "TLSA",
+    SMIMEA = 53 => "SMIMEA",
+    HIP = 55 => "HIP",
+    NINFO = 56 => "NINFO",
+    RKEY = 57 => "RKEY",
+    TALINK = 58 => "TALINK",
+    CDS = 59 => "CDS",
+    CDNSKEY = 60 => "CDNSKEY",
+    OPENPGPKEY = 61 => "OPENPGPKEY",
+    CSYNC = 62 => "CSYNC",
+    ZONEMD = 63 => "ZONEMD",
+    SVCB = 64 => "SVCB",
+    HTTPS = 65 => "HTTPS",
+    DSYNC = 66 => "DSYNC",
+    SPF = 99 => "SPF",
+    UINFO = 100 => "UINFO",
+    UID = 101 => "UID",
+    GID = 102 => "GID",
+    UNSPEC = 103 => "UNSPEC",
+    NID = 104 => "NID",
+    L32 = 105 => "L32",
+    L64 = 106 => "L64",
+    LP = 107 => "LP",
+    EUI48 = 108 => "EUI48",
+    EUI64 = 109 => "EUI64",
+    NXNAME = 128 => "NXNAME",
+    TKEY = 249 => "TKEY",
+    TSIG = 250 => "TSIG",
+    IXFR = 251 => "IXFR",
+    AXFR = 252 => "AXFR",
+    MAILB = 253 => "MAILB",
+    MAILA = 254 => "MAILA",
+    ANY = 255 => "ANY",
+    URI = 256 => "URI",
+    CAA = 257 => "CAA",
+    AVC = 258 => "AVC",
+    DOA = 259 => "DOA",
+    AMTRELAY = 260 => "AMTRELAY",
+    RESINFO = 261 => "RESINFO",
+    WALLET = 262 => "WALLET",
+    CLA = 263 => "CLA",
+    IPN = 264 => "IPN",
+    TA = 32768 => "TA",
+    DLV = 32769 => "DLV",
+});
+
+// IANA "DNS CLASSes" registry, QCLASSes included
+registry!(Class, "CLASS", UnknownClass, {
+    IN = 1 => "IN",
+    CS = 2 => "CS",
+    CH = 3 => "CH",
+    HS = 4 => "HS",
+    NONE = 254 => "NONE",
+    ANY = 255 => "ANY",
+});
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,6 +200,8 @@ mod tests {
         assert_eq!(QType::A.to_u16(), 1u16);
         assert_eq!(QType::NS.to_u16(), 2u16);
         assert_eq!(QType::CNAME.to_u16(), 5u16);
+        assert_eq!(QType::HTTPS.to_u16(), 65u16);
+        assert_eq!(QType::Unknown(9999).to_u16(), 9999u16);
     }
 
     #[test]
@@ -86,17 +209,62 @@ mod tests {
         assert_eq!(QType::from_u16(1), QType::A);
         assert_eq!(QType::from_u16(2), QType::NS);
         assert_eq!(QType::from_u16(5), QType::CNAME);
+        assert_eq!(QType::from_u16(28), QType::AAAA);
+        assert_eq!(QType::from_u16(255), QType::ANY);
+        assert!(matches!(QType::from_u16(9999), QType::Unknown(9999)));
     }
 
     #[test]
     fn test_class_to_u16() {
         assert_eq!(Class::IN.to_u16(), 1u16);
         assert_eq!(Class::CS.to_u16(), 2u16);
+        assert_eq!(Class::NONE.to_u16(), 254u16);
     }
 
     #[test]
     fn test_class_from_u16() {
         assert_eq!(Class::from_u16(1), Class::IN);
         assert_eq!(Class::from_u16(2), Class::CS);
+        assert_eq!(Class::from_u16(255), Class::ANY);
+        assert!(matches!(Class::from_u16(42), Class::Unknown(42)));
+    }
+
+    #[test]
+    fn test_unknown_equals_known_value() {
+        assert_eq!(QType::Unknown(1), QType::A);
+        assert_eq!(Class::Unknown(1), Class::IN);
+        assert_eq!(QType::Unknown(1).to_string(), "A");
+    }
+
+    #[test]
+    fn test_query_type_presentation_round_trip() {
+        for value in [1, 23, 28, 65, 252, 255, 257, 1234, 65535] {
+            let qtype = QType::from_u16(value);
+            assert_eq!(qtype.to_string().parse::<QType>(), Ok(qtype));
+        }
+
+        assert_eq!(QType::NSAPPTR.to_string(), "NSAP-PTR");
+        assert_eq!(QType::from_u16(1234).to_string(), "TYPE1234");
+        assert_eq!("aaaa".parse::<QType>(), Ok(QType::AAAA));
+        assert_eq!("TYPE28".parse::<QType>(), Ok(QType::AAAA));
+        assert!("TYPE".parse::<QType>().is_err());
+        assert!("TYPE+1".parse::<QType>().is_err());
+        assert!("TYPE65536".parse::<QType>().is_err());
+        assert!("BOGUS".parse::<QType>().is_err());
+    }
+
+    #[test]
+    fn test_class_presentation_round_trip() {
+        for value in [1, 3, 5, 254, 255] {
+            let class = Class::from_u16(value);
+            assert_eq!(class.to_string().parse::<Class>(), Ok(class));
+        }
+
+        assert_eq!(Class::from_u16(5).to_string(), "CLASS5");
+        assert_eq!("CLASS1".parse::<Class>(), Ok(Class::IN));
+        assert_eq!(
+            "XX".parse::<Class>(),
+            Err(ParseError::UnknownClass("XX".to_string()))
+        );
     }
 }
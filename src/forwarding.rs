@@ -0,0 +1,153 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{
+    error::ParseError,
+    name::Name,
+    upstream::{Strategy, Upstream, Upstreams},
+};
+
+const DNS_PORT: u16 = 53;
+
+#[derive(Debug)]
+pub enum Action {
+    Forward(Upstreams),
+    // answered locally without asking anyone
+    NxDomain,
+    Refused,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub suffix: Name,
+    pub action: Action,
+}
+
+impl Rule {
+    // `<suffix>=nxdomain`, `<suffix>=refused` or `<suffix>=<address>[,...]`
+    // where an address without a port uses 53
+    pub fn parse(spec: &str, strategy: Strategy) -> Result<Rule, ParseError> {
+        let (suffix, target) = spec
+            .split_once('=')
+            .ok_or_else(|| ParseError::InvalidValue(spec.to_string()))?;
+
+        let action = match target.to_ascii_lowercase().as_str() {
+            "nxdomain" => Action::NxDomain,
+            "refused" => Action::Refused,
+            _ => {
+                let upstreams = target
+                    .split(',')
+                    .map(|address| parse_address(address).map(Upstream::new))
+                    .collect::<Result<_, _>>()?;
+                Action::Forward(Upstreams::new(upstreams, strategy))
+            }
+        };
+
+        Ok(Rule {
+            suffix: suffix.parse()?,
+            action,
+        })
+    }
+}
+
+fn parse_address(s: &str) -> Result<SocketAddr, ParseError> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+        .map_err(|_| ParseError::InvalidValue(s.to_string()))
+}
+
+// Forwarding rules by domain suffix. A question follows the rule with the
+// longest suffix of its name, and a rule for the root catches everything.
+#[derive(Debug)]
+pub struct Forwarding {
+    rules: Vec<Rule>,
+}
+
+impl Forwarding {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    // Of several rules for the same suffix the last one wins
+    pub fn find(&self, name: &Name) -> Option<&Action> {
+        self.rules
+            .iter()
+            .filter(|rule| name.is_subdomain_of(&rule.suffix))
+            .max_by_key(|rule| rule.suffix.labels().len())
+            .map(|rule| &rule.action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(action: Option<&Action>) -> Vec<String> {
+        match action {
+            Some(Action::Forward(upstreams)) => upstreams
+                .addresses()
+                .iter()
+                .map(SocketAddr::to_string)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_rule_parse() {
+        let rule = Rule::parse("corp.internal=10.0.0.53,[::1]:5353", Strategy::Failover).unwrap();
+        assert_eq!(rule.suffix.to_string(), "corp.internal");
+        assert_eq!(
+            addresses(Some(&rule.action)),
+            vec!["10.0.0.53:53", "[::1]:5353"]
+        );
+
+        assert!(matches!(
+            Rule::parse("ads.example=NXDOMAIN", Strategy::Failover)
+                .unwrap()
+                .action,
+            Action::NxDomain
+        ));
+        assert!(matches!(
+            Rule::parse(".=refused", Strategy::Failover).unwrap().action,
+            Action::Refused
+        ));
+
+        assert!(Rule::parse("corp.internal", Strategy::Failover).is_err());
+        assert!(Rule::parse("corp.internal=10.0.0", Strategy::Failover).is_err());
+        assert!(Rule::parse("corp..internal=refused", Strategy::Failover).is_err());
+    }
+
+    #[test]
+    fn test_forwarding_longest_match() {
+        let rules = [
+            ".=192.0.2.1",
+            "corp.internal=10.0.0.53",
+            "lab.corp.internal=nxdomain",
+            "blocked.corp.internal=refused",
+        ];
+        let forwarding = Forwarding::new(
+            rules
+                .iter()
+                .map(|spec| Rule::parse(spec, Strategy::Failover).unwrap())
+                .collect(),
+        );
+        let find = |name: &str| forwarding.find(&name.parse().unwrap());
+
+        assert_eq!(addresses(find("example.com")), vec!["192.0.2.1:53"]);
+        assert_eq!(addresses(find("CORP.internal")), vec!["10.0.0.53:53"]);
+        assert_eq!(addresses(find("www.corp.internal")), vec!["10.0.0.53:53"]);
+        assert!(matches!(
+            find("x.lab.corp.internal"),
+            Some(Action::NxDomain)
+        ));
+        assert!(matches!(
+            find("blocked.corp.internal"),
+            Some(Action::Refused)
+        ));
+        // a suffix only matches whole labels
+        assert_eq!(addresses(find("xcorp.internal")), vec!["192.0.2.1:53"]);
+
+        let forwarding = Forwarding::new(vec![]);
+        assert!(forwarding.find(&"example.com".parse().unwrap()).is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::DecodeError;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
     pub id: u16,
@@ -7,7 +9,9 @@ pub struct Header {
     pub truncated_msg: bool,
     pub recursion_desired: bool,
     pub recursion_available: bool,
-    pub reserved: u8,
+    pub reserved: bool,
+    pub authentic_data: bool,
+    pub checking_disabled: bool,
     pub response_code: u8,
     pub question_count: u16,
     pub answer_count: u16,
@@ -51,11 +55,21 @@ impl Header {
         self
     }
 
-    pub fn reserved(&mut self, reserved: u8) -> &mut Self {
+    pub fn reserved(&mut self, reserved: bool) -> &mut Self {
         self.reserved = reserved;
         self
     }
 
+    pub fn authentic_data(&mut self, authentic_data: bool) -> &mut Self {
+        self.authentic_data = authentic_data;
+        self
+    }
+
+    pub fn checking_disabled(&mut self, checking_disabled: bool) -> &mut Self {
+        self.checking_disabled = checking_disabled;
+        self
+    }
+
     pub fn response_code(&mut self, response_code: u8) -> &mut Self {
         self.response_code = response_code;
         self
@@ -91,6 +105,8 @@ impl Header {
             recursion_desired: self.recursion_desired,
             recursion_available: self.recursion_available,
             reserved: self.reserved,
+            authentic_data: self.authentic_data,
+            checking_disabled: self.checking_disabled,
             response_code: self.response_code,
             question_count: self.question_count,
             answer_count: self.answer_count,
@@ -120,7 +136,9 @@ impl Header {
             | (self.recursion_desired as u8);
 
         bytes[3] = ((self.recursion_available as u8) << 7)
-            | ((self.reserved & 0x07) << 4)
+            | ((self.reserved as u8) << 6)
+            | ((self.authentic_data as u8) << 5)
+            | ((self.checking_disabled as u8) << 4)
             | (self.response_code & 0x0F);
 
         // Serialize `question_count` (16 bits)
@@ -142,7 +160,14 @@ impl Header {
         bytes
     }
 
-    pub fn from_bytes(data: &[u8]) -> Header {
+    pub fn from_bytes(data: &[u8]) -> Result<Header, DecodeError> {
+        if data.len() < 12 {
+            return Err(DecodeError::Truncated {
+                offset: 0,
+                needed: 12,
+            });
+        }
+
         // Parse the ID
         let id = u16::from_be_bytes([data[0], data[1]]);
 
@@ -155,7 +180,9 @@ impl Header {
         let truncated_msg = (flags & 0x0200) != 0;
         let recursion_desired = (flags & 0x0100) != 0;
         let recursion_available = (flags & 0x0080) != 0;
-        let reserved = ((flags & 0x0070) >> 4) as u8;
+        let reserved = (flags & 0x0040) != 0;
+        let authentic_data = (flags & 0x0020) != 0;
+        let checking_disabled = (flags & 0x0010) != 0;
         let response_code = (flags & 0x000F) as u8;
 
         // Parse the counts
@@ -164,7 +191,7 @@ impl Header {
         let authority_count = u16::from_be_bytes([data[8], data[9]]);
         let additional_count = u16::from_be_bytes([data[10], data[11]]);
 
-        Self {
+        Ok(Self {
             id,
             query_response,
             opcode,
@@ -173,12 +200,14 @@ impl Header {
             recursion_desired,
             recursion_available,
             reserved,
+            authentic_data,
+            checking_disabled,
             response_code,
             question_count,
             answer_count,
             authority_count,
             additional_count,
-        }
+        })
     }
 }
 
@@ -196,7 +225,9 @@ mod tests {
             truncated_msg: false,
             recursion_desired: true,
             recursion_available: true,
-            reserved: 0,
+            reserved: false,
+            authentic_data: false,
+            checking_disabled: false,
             response_code: 0,
             question_count: 1,
             answer_count: 2,
@@ -235,7 +266,9 @@ mod tests {
             truncated_msg: false,
             recursion_desired: true,
             recursion_available: true,
-            reserved: 0,
+            reserved: false,
+            authentic_data: false,
+            checking_disabled: false,
             response_code: 0,
             question_count: 1,
             answer_count: 2,
@@ -243,6 +276,33 @@ mod tests {
             additional_count: 4,
         };
 
-        assert_eq!(Header::from_bytes(&bytes), header);
+        assert_eq!(Header::from_bytes(&bytes), Ok(header));
+    }
+
+    #[test]
+    fn test_header_dnssec_bits() {
+        let header = Header::default()
+            .authentic_data(true)
+            .checking_disabled(true)
+            .build();
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[3], 0x30);
+        assert_eq!(Header::from_bytes(&bytes), Ok(header));
+
+        let mut bytes = [0; 12];
+        bytes[3] = 0x40;
+        let header = Header::from_bytes(&bytes).unwrap();
+        assert!(header.reserved && !header.authentic_data && !header.checking_disabled);
+    }
+
+    #[test]
+    fn test_header_from_short_bytes() {
+        assert_eq!(
+            Header::from_bytes(&[0x04, 0xD2, 0x91]),
+            Err(DecodeError::Truncated {
+                offset: 0,
+                needed: 12
+            })
+        );
     }
 }
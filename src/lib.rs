@@ -0,0 +1,26 @@
+pub mod bigint;
+pub mod cache;
+pub mod crypto;
+pub mod digest;
+pub mod dnssec;
+pub mod edns;
+pub mod encoder;
+pub mod error;
+pub mod field;
+pub mod forwarding;
+pub mod header;
+pub mod name;
+pub mod packet;
+pub mod pool;
+pub mod presentation;
+pub mod question;
+pub mod rdata;
+pub mod recursor;
+pub mod resource_records;
+pub mod svcb;
+pub mod tcp;
+#[cfg(test)]
+mod testing;
+pub mod upstream;
+pub mod validator;
+pub mod zone;
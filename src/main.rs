@@ -1,171 +1,913 @@
-use std::net::UdpSocket;
+use std::{
+    io,
+    net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use clap::{arg, Command};
-use field::{Class, QType};
-use header::Header;
-use packet::Packet;
-use question::Question;
-use resource_records::ResourceRecord;
+use clap::{arg, value_parser, ArgAction, Command};
+use dns_starter_rust::{
+    cache::{Answer, Cache},
+    dnssec::Ds,
+    edns::{Edns, EdnsOption, BADVERS},
+    error::ResolveError,
+    field::QType,
+    forwarding::{Action, Forwarding, Rule},
+    header::Header,
+    name::Name,
+    packet::Packet,
+    pool::ThreadPool,
+    presentation::tokenize,
+    question::Question,
+    recursor::{Minimisation, Recursor},
+    resource_records::ResourceRecord,
+    tcp,
+    upstream::{Strategy, Upstream, Upstreams, UDP_PAYLOAD_SIZE},
+    validator::{Security, Validator},
+    zone::{Zone, Zones},
+};
 
-mod field;
-mod header;
-mod packet;
-mod question;
-mod resource_records;
+// RFC 1035 4.1.1 response codes
+const FORMERR: u8 = 1;
+const SERVFAIL: u16 = 2;
+const NXDOMAIN: u16 = 3;
+const NOTIMP: u16 = 4;
+const REFUSED: u16 = 5;
 
-fn main() {
-    let udp_socket = UdpSocket::bind("127.0.0.1:2053").expect("Failed to bind to address");
-    let mut buf = [0; 512];
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Largest possible UDP datagram, queries may arrive before any negotiation
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+const ADDRESS: &str = "127.0.0.1:2053";
+
+const TCP_LIMITS: TcpLimits = TcpLimits {
+    connections: 64,
+    pipeline: 16,
+    idle_timeout: Duration::from_secs(10),
+};
+
+// Refreshes waiting for a worker, past that new ones are dropped
+const PREFETCH_QUEUE_SIZE: usize = 64;
 
+fn main() {
     let matches = Command::new("dns-rs")
         .version("1.0")
         .about("A simple Domain Name System server")
-        .arg(arg!(--resolver <VALUE>).required(true))
+        .arg(
+            arg!(--mode <MODE> "Forward to --resolver, resolve iteratively from the root servers, or answer for --zone only")
+                .value_parser(["forward", "recursive", "authoritative"])
+                .default_value("forward"),
+        )
+        .arg(
+            arg!(--"qname-minimisation" <MODE> "How much of query names to reveal in recursive mode: off, strict or relaxed")
+                .value_parser(|s: &str| s.parse::<Minimisation>())
+                .default_value("relaxed"),
+        )
+        .arg(
+            arg!(--zone <ZONE> "Zone to serve in authoritative mode: <origin>=<master file>")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--resolver <VALUE> "Upstream address, repeated or comma separated for several. In recursive mode, asked when iterative resolution fails")
+                .action(ArgAction::Append)
+                .value_delimiter(','),
+        )
+        .arg(
+            arg!(--forward <RULE> "Forwarding rule for a domain: <suffix>=<address>[,...], <suffix>=nxdomain or <suffix>=refused")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--strategy <NAME> "Order to try the upstreams in")
+                .value_parser(|s: &str| s.parse::<Strategy>())
+                .default_value("failover"),
+        )
+        .arg(
+            arg!(--workers <N> "Threads resolving queries")
+                .value_parser(value_parser!(usize))
+                .default_value("16"),
+        )
+        .arg(
+            arg!(--"queue-size" <N> "Queries waiting for a worker before new ones are refused")
+                .value_parser(value_parser!(usize))
+                .default_value("256"),
+        )
+        .arg(
+            arg!(--"cache-size" <N> "RRsets kept in the cache, 0 to turn it off")
+                .value_parser(value_parser!(usize))
+                .default_value("10000"),
+        )
+        .arg(
+            arg!(--"min-ttl" <SECONDS> "Shortest time an RRset is cached for")
+                .value_parser(value_parser!(u32))
+                .default_value("0"),
+        )
+        .arg(
+            arg!(--"max-ttl" <SECONDS> "Longest time an RRset is cached for")
+                .value_parser(value_parser!(u32))
+                .default_value("86400"),
+        )
+        .arg(
+            arg!(--"serve-stale" <SECONDS> "How long past expiry cached answers are served while upstream fails")
+                .value_parser(value_parser!(u64))
+                .default_value("86400"),
+        )
+        .arg(
+            arg!(--dnssec <MODE> "Validate answers with DNSSEC, or pass them on unchecked")
+                .value_parser(["validate", "off"])
+                .default_value("off"),
+        )
+        .arg(
+            arg!(--"trust-anchor" <DS> "DS record data for a root key, repeated for several, in place of the built-in ones")
+                .action(ArgAction::Append),
+        )
         .get_matches();
 
-    let resolver = matches.get_one::<String>("resolver").expect("required");
+    let mode = matches
+        .get_one::<String>("mode")
+        .expect("defaulted")
+        .as_str();
+    let recursive = mode == "recursive";
+    let minimisation = *matches
+        .get_one::<Minimisation>("qname-minimisation")
+        .expect("defaulted");
+    let strategy = *matches.get_one::<Strategy>("strategy").expect("defaulted");
+    let workers = *matches.get_one::<usize>("workers").expect("defaulted");
+    let queue_size = *matches.get_one::<usize>("queue-size").expect("defaulted");
+    let cache_size = *matches.get_one::<usize>("cache-size").expect("defaulted");
+    let min_ttl = *matches.get_one::<u32>("min-ttl").expect("defaulted");
+    let max_ttl = *matches.get_one::<u32>("max-ttl").expect("defaulted");
+    let serve_stale = *matches.get_one::<u64>("serve-stale").expect("defaulted");
+    let validate = matches.get_one::<String>("dnssec").expect("defaulted") == "validate";
+    if min_ttl > max_ttl {
+        eprintln!("--min-ttl must not be above --max-ttl");
+        std::process::exit(2);
+    }
+    match (mode, matches.contains_id("resolver")) {
+        ("forward", false) => {
+            eprintln!("--resolver is required in --mode forward");
+            std::process::exit(2);
+        }
+        ("authoritative", true) => {
+            eprintln!("--resolver does not apply to --mode authoritative");
+            std::process::exit(2);
+        }
+        _ => {}
+    }
+    if (mode == "authoritative") != matches.contains_id("zone") {
+        eprintln!("--zone is required in, and only applies to, --mode authoritative");
+        std::process::exit(2);
+    }
+    if mode == "authoritative" && matches.contains_id("forward") {
+        eprintln!("--forward does not apply to --mode authoritative");
+        std::process::exit(2);
+    }
+    if matches.contains_id("trust-anchor") && !validate {
+        eprintln!("--trust-anchor only applies to --dnssec validate");
+        std::process::exit(2);
+    }
+
+    let udp_socket = Arc::new(UdpSocket::bind(ADDRESS).expect("Failed to bind to address"));
+    let tcp_listener = TcpListener::bind(ADDRESS).expect("Failed to bind to address");
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
 
-    let mut header = Header::default();
+    let mut rules = Vec::new();
+    let mut fallback = None;
+    if let Some(resolvers) = matches.get_many::<String>("resolver") {
+        // with other servers to fall back on, moving on beats retrying one
+        let several = resolvers.len() > 1;
+        let upstreams = resolvers
+            .map(
+                |resolver| match resolver.to_socket_addrs().map(|mut addrs| addrs.next()) {
+                    Ok(Some(address)) if several => Upstream {
+                        attempts: 1,
+                        ..Upstream::new(address)
+                    },
+                    Ok(Some(address)) => Upstream::new(address),
+                    _ => {
+                        eprintln!("Invalid resolver address: {}", resolver);
+                        std::process::exit(2);
+                    }
+                },
+            )
+            .collect();
 
-    loop {
-        match udp_socket.recv_from(&mut buf) {
-            Ok((size, source)) => {
-                println!("Received {} bytes from {}", size, source);
-
-                println!("init {:?}", buf);
-                let mut packet = Packet::from_bytes(buf);
-                println!("-->Header {:#?}", packet.header);
-
-                // header = header
-                //     .id(packet.header.id)
-                //     .query_response(true)
-                //     .opcode(packet.header.opcode)
-                //     .recursion_desired(packet.header.recursion_desired)
-                //     .response_code(if packet.header.opcode == 0 { 0 } else { 4 })
-                //     .question_count(packet.header.question_count)
-                //     .build();
-                // println!("-->Header {:#?}", header);
-
-                let forward_packets = packet.split();
-
-                let mut dns = Dns::new(header.build());
-
-                let resource_records = Dns::forward(&udp_socket, resolver, forward_packets);
-
-                // TODO: check header counts
-
-                let response = packet.merge();
-
-                // dns.questions = packet.questions.clone();
-                //
-                // for i in 0..packet.header.question_count {
-                //     let question = packet.questions.get(i as usize).unwrap();
-                //     dns.add_resource_record(
-                //         question.name.clone(),
-                //         question.qtype,
-                //         question.class,
-                //         60,
-                //         4,
-                //         vec![8, 8, 8, 8],
-                //     );
-                //     dns.header.inc_ancount();
-                // }
-                //
-                // let response = dns.response();
-
-                udp_socket
-                    .send_to(&response, source)
-                    .expect("Failed to send response");
+        let upstreams = Upstreams::new(upstreams, strategy);
+        if recursive {
+            fallback = Some(upstreams);
+        } else {
+            // `--resolver` is the rule for the root, so for every name
+            // without a more specific rule
+            rules.push(Rule {
+                suffix: Name::root(),
+                action: Action::Forward(upstreams),
+            });
+        }
+    }
+    for spec in matches.get_many::<String>("forward").into_iter().flatten() {
+        match Rule::parse(spec, strategy) {
+            Ok(rule) => rules.push(rule),
+            Err(e) => {
+                eprintln!("Invalid forwarding rule {}: {}", spec, e);
+                std::process::exit(2);
             }
+        }
+    }
+
+    let mut zones = Vec::new();
+    for spec in matches.get_many::<String>("zone").into_iter().flatten() {
+        let Some((origin, file)) = spec.split_once('=') else {
+            eprintln!("Invalid zone {}: expected <origin>=<master file>", spec);
+            std::process::exit(2);
+        };
+        let loaded = origin
+            .parse::<Name>()
+            .map_err(|e| e.to_string())
+            .and_then(|origin| Zone::load(&origin, Path::new(file)).map_err(|e| e.to_string()));
+        match loaded {
+            Ok(zone) => zones.push(zone),
+            Err(e) => {
+                eprintln!("Invalid zone {}: {}", spec, e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut anchors = Vec::new();
+    for spec in matches
+        .get_many::<String>("trust-anchor")
+        .into_iter()
+        .flatten()
+    {
+        match tokenize(spec).and_then(|tokens| Ds::from_tokens(&tokens)) {
+            Ok(ds) => anchors.push(ds),
+            Err(e) => {
+                eprintln!("Invalid trust anchor {}: {}", spec, e);
+                std::process::exit(2);
+            }
+        }
+    }
+    let validator = validate.then(|| {
+        if anchors.is_empty() {
+            Validator::with_root_anchors()
+        } else {
+            Validator::new(anchors)
+        }
+    });
+
+    let cache = Cache::new(cache_size, min_ttl, max_ttl)
+        .with_stale_window(Duration::from_secs(serve_stale));
+    let (prefetch_sender, prefetch_receiver) = mpsc::sync_channel(PREFETCH_QUEUE_SIZE);
+    let recursor = recursive.then(|| Recursor {
+        minimisation,
+        dnssec: validate,
+        fallback,
+        ..Recursor::from_root_hints()
+    });
+    let dns = Arc::new(Dns::new(
+        Forwarding::new(rules),
+        Zones::new(zones),
+        recursor,
+        validator,
+        cache,
+        prefetch_sender,
+    ));
+    let pool = Arc::new(ThreadPool::new(workers, queue_size));
+
+    let prefetch_dns = Arc::clone(&dns);
+    let prefetch_pool = Arc::clone(&pool);
+    thread::spawn(move || prefetch(prefetch_receiver, prefetch_dns, prefetch_pool));
+
+    let tcp_dns = Arc::clone(&dns);
+    let tcp_pool = Arc::clone(&pool);
+    thread::spawn(move || serve_tcp(tcp_listener, tcp_dns, tcp_pool, TCP_LIMITS));
+
+    loop {
+        let (size, source) = match udp_socket.recv_from(&mut buf) {
+            Ok(received) => received,
             Err(e) => {
                 eprintln!("Error receiving data: {}", e);
-                break;
+                continue;
             }
+        };
+
+        let query = buf[..size].to_vec();
+        let job_dns = Arc::clone(&dns);
+        let job_socket = Arc::clone(&udp_socket);
+        let queued = pool.try_execute(move || {
+            if let Some(response) = job_dns.answer(&query, Transport::Udp) {
+                if let Err(e) = job_socket.send_to(&response, source) {
+                    eprintln!("Error sending response to {}: {}", source, e);
+                }
+            }
+        });
+
+        // every worker is stuck on a slow upstream, answer right away rather
+        // than let the queries pile up
+        if !queued {
+            let response = Header::from_bytes(&buf[..size])
+                .ok()
+                .and_then(|header| Packet::response(&header, SERVFAIL as u8).to_bytes().ok());
+            if let Some(response) = response {
+                if let Err(e) = udp_socket.send_to(&response, source) {
+                    eprintln!("Error sending response to {}: {}", source, e);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TcpLimits {
+    // connections open or with queries still being answered
+    connections: usize,
+    // queries of one connection waiting for a worker at the same time. Past
+    // that the connection is not read until one of them is answered.
+    pipeline: usize,
+    // connections are closed after this long without a query (RFC 7766 6.2.3)
+    idle_timeout: Duration,
+}
+
+// One of the connections counted against `TcpLimits::connections`, given
+// back once the connection and every answer still in the works for it are
+// done
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serve_tcp(listener: TcpListener, dns: Arc<Dns>, pool: Arc<ThreadPool>, limits: TcpLimits) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Error accepting TCP connection: {}", e);
+                continue;
+            }
+        };
+
+        // over the limit the connection is closed right away, and the slot
+        // given back with it
+        let taken = connections.fetch_add(1, Ordering::SeqCst);
+        let slot = Arc::new(Slot(Arc::clone(&connections)));
+        if taken >= limits.connections {
+            continue;
+        }
+
+        let dns = Arc::clone(&dns);
+        let pool = Arc::clone(&pool);
+        thread::spawn(move || {
+            if let Err(e) = serve_connection(stream, &dns, &pool, &slot, limits) {
+                eprintln!("TCP connection error: {}", e);
+            }
+        });
+    }
+}
+
+// Reads pipelined queries and writes every answer as soon as it is ready, so
+// answers may come back in a different order (RFC 7766 6.2.1.1)
+fn serve_connection(
+    stream: TcpStream,
+    dns: &Arc<Dns>,
+    pool: &ThreadPool,
+    slot: &Arc<Slot>,
+    limits: TcpLimits,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(limits.idle_timeout))?;
+    stream.set_write_timeout(Some(TCP_TIMEOUT))?;
+    let mut reader = stream.try_clone()?;
+    let writer = Arc::new(Mutex::new(stream));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+
+    loop {
+        let query = match tcp::read_message(&mut reader) {
+            Ok(query) => query,
+            // closed by the client, or idle for too long
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let dns = Arc::clone(dns);
+        let writer = Arc::clone(&writer);
+        let handle = move || {
+            let Some(response) = dns.answer(&query, Transport::Tcp) else {
+                return Ok(());
+            };
+            let mut stream = writer
+                .lock()
+                .map_err(|_| io::Error::other("writer poisoned"))?;
+            tcp::write_message(&mut *stream, &response)
+        };
+
+        if in_flight.fetch_add(1, Ordering::SeqCst) >= limits.pipeline {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            handle()?;
+            continue;
         }
+
+        let in_flight = Arc::clone(&in_flight);
+        let slot = Arc::clone(slot);
+        pool.execute(move || {
+            if let Err(e) = handle() {
+                eprintln!("Error answering TCP query: {}", e);
+            }
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            drop(slot);
+        });
     }
 }
 
-#[derive(Debug, Clone)]
+// Refreshes cache entries ahead of expiry on whichever worker is free. When
+// none is, the entry simply expires.
+fn prefetch(receiver: Receiver<Packet>, dns: Arc<Dns>, pool: Arc<ThreadPool>) {
+    for packet in receiver {
+        let dns = Arc::clone(&dns);
+        pool.try_execute(move || dns.refresh(&packet));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+}
+
+// Where the answers for a name come from
+#[derive(Debug)]
+enum Source<'a> {
+    Forward(&'a Upstreams),
+    Recurse(&'a Recursor),
+}
+
+#[derive(Debug)]
 struct Dns {
-    header: Header,
-    questions: Vec<Question>,
-    resource_records: Vec<ResourceRecord>,
+    forwarding: Forwarding,
+    // answered from their master files, in authoritative mode
+    zones: Zones,
+    // resolves the names no forwarding rule covers
+    recursor: Option<Recursor>,
+    // checks answers unless clients set CD, when DNSSEC is on
+    validator: Option<Validator>,
+    cache: Cache,
+    // queries whose cached answers are about to expire
+    prefetch: SyncSender<Packet>,
 }
 
 impl Dns {
-    fn new(header: Header) -> Self {
+    fn new(
+        forwarding: Forwarding,
+        zones: Zones,
+        recursor: Option<Recursor>,
+        validator: Option<Validator>,
+        cache: Cache,
+        prefetch: SyncSender<Packet>,
+    ) -> Self {
         Self {
-            header,
-            questions: Vec::new(),
-            resource_records: Vec::new(),
+            forwarding,
+            zones,
+            recursor,
+            validator,
+            cache,
+            prefetch,
         }
     }
 
-    fn add_question(&mut self, name: String, qtype: QType, class: Class) {
-        self.questions.push(Question::new(name, qtype, class));
+    // Wire form of the reply to the message in `bytes`, if it can have one
+    fn answer(&self, bytes: &[u8], transport: Transport) -> Option<Vec<u8>> {
+        let response = match Packet::from_bytes(bytes) {
+            Ok(mut packet) => {
+                let mut response = self.resolve(&mut packet);
+                let max_size = match transport {
+                    Transport::Udp => packet.max_udp_size(),
+                    Transport::Tcp => u16::MAX as usize,
+                };
+                if let Err(e) = response.truncate(max_size) {
+                    eprintln!("Unencodable answer: {}", e);
+                    response = reply(&packet, SERVFAIL);
+                }
+                response
+            }
+            Err(e) => {
+                eprintln!("Malformed query: {}", e);
+                // not even a header to answer to
+                Packet::response(&Header::from_bytes(bytes).ok()?, FORMERR)
+            }
+        };
+
+        response.to_bytes().ok()
+    }
+
+    fn resolve(&self, packet: &mut Packet) -> Packet {
+        // only version 0 exists (RFC 6891 6.1.3)
+        if packet.edns.as_ref().is_some_and(|edns| edns.version > 0) {
+            return reply(packet, BADVERS);
+        }
+
+        let mut forward_packets = packet.split();
+        for forward_packet in &mut forward_packets {
+            forward_packet.edns = own_edns(packet);
+            // the client's own AA and AD bits say nothing about the answer
+            forward_packet.header.authoritative_answer = false;
+            forward_packet.header.authentic_data = false;
+        }
+
+        let answered_packets = match self.forward(forward_packets) {
+            Ok(packets) => packets,
+            Err(e) => {
+                eprintln!("Forwarding failed: {}", e);
+                let mut response = reply(packet, SERVFAIL);
+                // why, for clients that can take it (RFC 8914)
+                if let (ResolveError::Bogus(e), Some(edns)) = (&e, &mut response.edns) {
+                    edns.options
+                        .push(EdnsOption::extended_error(e.info_code(), &e.to_string()));
+                }
+                return response;
+            }
+        };
+
+        // the first error among the questions stands for all of them
+        let response_code = if packet.header.opcode == 0 {
+            answered_packets
+                .iter()
+                .map(Packet::rcode)
+                .find(|&rcode| rcode != 0)
+                .unwrap_or(0)
+        } else {
+            NOTIMP
+        };
+        let mut response = reply(packet, response_code);
+        response.header.truncated_msg = answered_packets
+            .iter()
+            .any(|packet| packet.header.truncated_msg);
+        response.header.authoritative_answer = !answered_packets.is_empty()
+            && answered_packets
+                .iter()
+                .all(|packet| packet.header.authoritative_answer);
+        // only to clients that show they understand it (RFC 6840 5.7)
+        response.header.authentic_data = self.validator.is_some()
+            && (dnssec_ok(packet) || packet.header.authentic_data)
+            && answered_packets
+                .iter()
+                .all(|packet| packet.header.authentic_data);
+
+        let mut answered_packets = answered_packets;
+        if !dnssec_ok(packet) {
+            answered_packets.iter_mut().for_each(strip_dnssec);
+        }
+
+        // section counts are recomputed by `to_bytes`
+        Packet {
+            header: response.header,
+            edns: response.edns,
+            ..Packet::merge(answered_packets)
+        }
+    }
+
+    fn forward(&self, packets: Vec<Packet>) -> Result<Vec<Packet>, ResolveError> {
+        packets
+            .into_iter()
+            .map(|packet| self.forward_one(packet))
+            .collect()
     }
 
-    fn add_resource_record(
-        &mut self,
-        name: String,
-        qtype: QType,
-        class: Class,
-        ttl: u32,
-        rdlength: u16,
-        rdata: Vec<u8>,
-    ) {
-        self.resource_records.push(ResourceRecord::new(
-            name, qtype, class, ttl, rdlength, rdata,
-        ));
+    fn forward_one(&self, mut packet: Packet) -> Result<Packet, ResolveError> {
+        if let Some(zone) = self.zones.find(&packet.questions[0]) {
+            let reply = zone.answer(&packet.questions[0]);
+            packet.set_rcode(reply.rcode());
+            packet.header.authoritative_answer = reply.header.authoritative_answer;
+            packet.answers = reply.answers;
+            packet.authorities = reply.authorities;
+            packet.additionals = reply.additionals;
+            return Ok(packet);
+        }
+
+        let source = match self.source(&packet.questions[0].name) {
+            Ok(source) => source,
+            Err(rcode) => {
+                packet.set_rcode(rcode);
+                return Ok(packet);
+            }
+        };
+
+        if let Some(answer) = self.cache.lookup(&packet.questions[0], Instant::now()) {
+            if answer.prefetch {
+                // a full queue only means the entry expires as usual
+                let _ = self.prefetch.try_send(packet.clone());
+            }
+            return Ok(cached(packet, answer));
+        }
+
+        let reply = match self.query(&source, &packet) {
+            Ok(reply) if reply.rcode() != SERVFAIL => reply,
+            // bogus answers are refused outright, not papered over
+            Err(e @ ResolveError::Bogus(_)) => return Err(e),
+            // an expired answer beats none at all (RFC 8767)
+            result => match self
+                .cache
+                .lookup_stale(&packet.questions[0], Instant::now())
+            {
+                Some(answer) => return Ok(cached(packet, answer)),
+                None => result?,
+            },
+        };
+
+        packet.set_rcode(reply.rcode());
+        // an upstream answer that stayed truncated is passed on as such
+        packet.header.truncated_msg = reply.header.truncated_msg;
+        packet.header.authentic_data = reply.header.authentic_data;
+        packet.answers.extend(reply.answers);
+        packet.authorities.extend(reply.authorities);
+        packet.additionals.extend(reply.additionals);
+
+        Ok(packet)
     }
 
-    fn forward(
-        udp_socket: &UdpSocket,
-        addr: &String,
-        packets: Vec<Vec<u8>>,
-    ) -> Vec<ResourceRecord> {
-        let mut responses: Vec<ResourceRecord> = Vec::new();
+    // Where answers for `name` come from, or the rcode to answer with right
+    // away
+    fn source(&self, name: &Name) -> Result<Source<'_>, u16> {
+        match (self.forwarding.find(name), &self.recursor) {
+            (Some(Action::Forward(upstreams)), _) => Ok(Source::Forward(upstreams)),
+            (Some(Action::NxDomain), _) => Err(NXDOMAIN),
+            (None, Some(recursor)) => Ok(Source::Recurse(recursor)),
+            (Some(Action::Refused) | None, _) => Err(REFUSED),
+        }
+    }
 
-        for packet in packets {
-            println!("--> Packet {:?}", packet);
-            udp_socket
-                .send_to(&packet, addr)
-                .expect("Error receiving data: {}");
+    // Asks upstream, or the authoritative servers, validates the reply unless
+    // the client set CD, and caches what they say
+    fn query(&self, source: &Source, packet: &Packet) -> Result<Packet, ResolveError> {
+        let question = &packet.questions[0];
+        let mut reply = match source {
+            // the records come back unchecked, to be validated here
+            Source::Forward(upstreams) if self.validator.is_some() => {
+                upstreams.query(&unchecked_query(packet.clone()))?
+            }
+            Source::Forward(upstreams) => upstreams.query(packet)?,
+            Source::Recurse(recursor) => recursor.resolve(question)?,
+        };
 
-            let mut response_buf = [0u8; 512];
-            udp_socket
-                .recv_from(&mut response_buf)
-                .expect("Failed to receive response from upstream");
+        reply.header.authentic_data = false;
+        let checking_disabled = packet.header.checking_disabled;
+        if let (Some(validator), false) = (&self.validator, checking_disabled) {
+            let lookup = |question: &Question| self.lookup(source, question);
+            let security = validator.validate(question, &reply, &lookup, unix_time())?;
+            reply.header.authentic_data = security == Security::Secure;
+        }
 
-            let rr = ResourceRecord::from_bytes(&response_buf[..]);
+        // unchecked answers are not handed to clients that want them checked
+        if self.validator.is_none() || !checking_disabled {
+            self.cache.store(question, &reply, Instant::now());
+        }
+        Ok(reply)
+    }
+
+    // The records the validator asks for along the chain of trust
+    fn lookup(&self, source: &Source, question: &Question) -> Result<Packet, ResolveError> {
+        match source {
+            Source::Forward(upstreams) => {
+                let mut query = Packet::new(Header::default().recursion_desired(true).build());
+                query.questions.push(question.clone());
+                Ok(upstreams.query(&unchecked_query(query))?)
+            }
+            Source::Recurse(recursor) => recursor.resolve(question),
+        }
+    }
 
-            responses.push(rr);
+    fn refresh(&self, packet: &Packet) {
+        if let Ok(source) = self.source(&packet.questions[0].name) {
+            if let Err(e) = self.query(&source, packet) {
+                eprintln!("Prefetch failed: {}", e);
+            }
         }
+    }
+}
 
-        responses
+fn cached(mut packet: Packet, answer: Answer) -> Packet {
+    if answer.nxdomain {
+        packet.set_rcode(NXDOMAIN);
     }
+    packet.header.authentic_data = answer.secure;
+    packet.answers = answer.answers;
+    packet.authorities = answer.authorities;
+    packet
 }
 
-fn labels_from_bytes(data: &[u8], start_pos: usize) -> (String, usize) {
-    let mut cursor = start_pos;
-    let mut labels: Vec<String> = Vec::new();
+// `query` asking for the RRSIGs and proofs as well, without upstream
+// validating them
+fn unchecked_query(mut query: Packet) -> Packet {
+    query.header.checking_disabled = true;
+    query.edns = Some(Edns {
+        dnssec_ok: true,
+        ..Edns::new(UDP_PAYLOAD_SIZE)
+    });
+    query
+}
 
-    while data[cursor] != 0 {
-        if data[cursor] & 0b11000000 == 0b11000000 {
-            let offset =
-                (((data[cursor] & 0b00111111) as u16) << 8 | data[cursor + 1] as u16) as usize;
-            let (label, _) = labels_from_bytes(&data, offset);
-            labels.push(label);
-            // increase the index by tw0 since the offset used 2 bytes
-            cursor += 2;
-        } else {
-            let length = data[cursor] as usize;
-            cursor += 1;
-            let label = std::str::from_utf8(&data[cursor..cursor + length]).unwrap();
-            labels.push(label.to_string());
-            cursor += length;
+fn dnssec_ok(query: &Packet) -> bool {
+    query.edns.as_ref().is_some_and(|edns| edns.dnssec_ok)
+}
+
+// Drops the DNSSEC records a client without the DO bit did not ask for by
+// type (RFC 4035 3.2.1)
+fn strip_dnssec(packet: &mut Packet) {
+    let qtype = packet.questions.first().map(|question| question.qtype);
+    let wanted = |record: &ResourceRecord| {
+        !matches!(record.qtype(), QType::RRSIG | QType::NSEC | QType::NSEC3)
+            || Some(record.qtype()) == qtype
+    };
+    packet.answers.retain(wanted);
+    packet.authorities.retain(wanted);
+    packet.additionals.retain(wanted);
+}
+
+// Seconds since the epoch, as signature validity periods count them
+fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as u32)
+}
+
+// The OPT record to send along with anything sent on behalf of `query`: only
+// clients that used EDNS get one back, and the DO bit is passed through
+fn own_edns(query: &Packet) -> Option<Edns> {
+    query.edns.as_ref().map(|edns| Edns {
+        dnssec_ok: edns.dnssec_ok,
+        ..Edns::new(UDP_PAYLOAD_SIZE)
+    })
+}
+
+fn reply(query: &Packet, rcode: u16) -> Packet {
+    let mut response = Packet::response(&query.header, 0);
+    response.edns = own_edns(query);
+    response.set_rcode(rcode);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use dns_starter_rust::{field::Class, question::Question};
+
+    use super::*;
+
+    fn dns(rules: &[&str]) -> Dns {
+        let rules = rules
+            .iter()
+            .map(|spec| Rule::parse(spec, Strategy::Failover).unwrap())
+            .collect();
+        let (prefetch, _) = mpsc::sync_channel(1);
+        Dns::new(
+            Forwarding::new(rules),
+            Zones::default(),
+            None,
+            Some(Validator::with_root_anchors()),
+            Cache::new(10, 0, 86400),
+            prefetch,
+        )
+    }
+
+    fn query(name: &str, authentic_data: bool) -> Packet {
+        let header = Header::default()
+            .id(7)
+            .recursion_desired(true)
+            .authentic_data(authentic_data)
+            .build();
+        let mut packet = Packet::new(header);
+        packet
+            .questions
+            .push(Question::new(name.parse().unwrap(), QType::A, Class::IN));
+        packet.header = packet.counted_header();
+        packet
+    }
+
+    #[test]
+    fn test_client_ad_bit_is_not_echoed() {
+        let dns = dns(&["blocked.test=nxdomain", "refused.test=refused"]);
+
+        let response = dns.resolve(&mut query("www.blocked.test", true));
+        assert_eq!(response.rcode(), NXDOMAIN);
+        assert!(!response.header.authentic_data);
+
+        let response = dns.resolve(&mut query("www.refused.test", true));
+        assert_eq!(response.rcode(), REFUSED);
+        assert!(!response.header.authentic_data);
+        assert!(!response.header.authoritative_answer);
+    }
+
+    // A TCP server on a free loopback port, answering from forwarding rules
+    fn tcp_server(pool: Arc<ThreadPool>, limits: TcpLimits) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let dns = Arc::new(dns(&["blocked.test=nxdomain"]));
+        thread::spawn(move || serve_tcp(listener, dns, pool, limits));
+        address
+    }
+
+    fn connect(address: SocketAddr) -> TcpStream {
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream
+    }
+
+    fn send_query(stream: &mut TcpStream, id: u16) {
+        let mut packet = query("www.blocked.test", false);
+        packet.header.id = id;
+        tcp::write_message(stream, &packet.to_bytes().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_tcp_pipelining_and_idle_timeout() {
+        let limits = TcpLimits {
+            connections: 4,
+            pipeline: 2,
+            idle_timeout: Duration::from_millis(300),
+        };
+        let address = tcp_server(Arc::new(ThreadPool::new(2, 8)), limits);
+
+        // more queries at once than the pipeline takes, all answered
+        let mut stream = connect(address);
+        for id in 1..=4 {
+            send_query(&mut stream, id);
         }
+        let mut ids: Vec<u16> = (0..4)
+            .map(|_| {
+                let bytes = tcp::read_message(&mut stream).unwrap();
+                let response = Packet::from_bytes(&bytes).unwrap();
+                assert_eq!(response.rcode(), NXDOMAIN);
+                response.header.id
+            })
+            .collect();
+        ids.sort();
+        assert_eq!(ids, [1, 2, 3, 4]);
+
+        // closed by the server well before the client gives up reading
+        assert_eq!(
+            tcp::read_message(&mut stream).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
     }
 
-    (labels.join("."), cursor)
+    #[test]
+    fn test_tcp_connection_limit() {
+        let limits = TcpLimits {
+            connections: 2,
+            pipeline: 4,
+            idle_timeout: Duration::from_secs(5),
+        };
+        // the only worker is busy until `release`
+        let pool = Arc::new(ThreadPool::new(1, 8));
+        let (release, blocked) = mpsc::channel::<()>();
+        pool.execute(move || {
+            blocked.recv().ok();
+        });
+        let address = tcp_server(Arc::clone(&pool), limits);
+
+        // a query left waiting for the worker by a client that is gone, and
+        // an idle connection
+        let mut gone = connect(address);
+        send_query(&mut gone, 1);
+        drop(gone);
+        let _idle = connect(address);
+        thread::sleep(Duration::from_millis(200));
+
+        // the query still counts, so one more connection is one too many
+        let mut refused = connect(address);
+        assert_eq!(
+            tcp::read_message(&mut refused).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        // answered once the worker gets to the query
+        release.send(()).unwrap();
+        let answered = (0..20).any(|_| {
+            thread::sleep(Duration::from_millis(50));
+            let mut stream = connect(address);
+            send_query(&mut stream, 2);
+            tcp::read_message(&mut stream).is_ok()
+        });
+        assert!(answered);
+    }
 }
@@ -0,0 +1,461 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use crate::error::{read_u16, take, DecodeError, ParseError};
+
+pub const MAX_LABEL_LEN: usize = 63;
+pub const MAX_NAME_LEN: usize = 255;
+
+// A name has at most 127 labels, so no valid pointer chain is longer than that
+const MAX_POINTER_HOPS: usize = 127;
+
+// Domain name kept as raw label bytes, without the terminating root label.
+// Comparison and hashing are ASCII case-insensitive (RFC 4343).
+#[derive(Debug, Clone, Default)]
+pub struct Name {
+    labels: Vec<Vec<u8>>,
+}
+
+impl Name {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Result<Self, ParseError> {
+        let name = Self { labels };
+
+        if name.labels.iter().any(|label| label.is_empty()) {
+            return Err(ParseError::EmptyLabel(name.to_string()));
+        }
+        if name.labels.iter().any(|label| label.len() > MAX_LABEL_LEN) {
+            return Err(ParseError::LabelTooLong(name.to_string()));
+        }
+        if name.wire_len() > MAX_NAME_LEN {
+            return Err(ParseError::NameTooLong(name.to_string()));
+        }
+
+        Ok(name)
+    }
+
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    // `self` followed by the labels of `suffix`
+    pub fn append(&self, suffix: &Name) -> Result<Name, ParseError> {
+        let mut labels = self.labels.clone();
+        labels.extend(suffix.labels.iter().cloned());
+        Name::from_labels(labels)
+    }
+
+    // The name left after dropping the first `skip` labels
+    pub fn suffix(&self, skip: usize) -> Name {
+        Name {
+            labels: self.labels[skip.min(self.labels.len())..].to_vec(),
+        }
+    }
+
+    // True for the name itself and every name below it
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        self.labels.len() >= other.labels.len()
+            && self.suffix(self.labels.len() - other.labels.len()) == *other
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // Canonical form for DNSSEC: ASCII letters in lower case (RFC 4034 6.2)
+    pub fn to_lowercase(&self) -> Name {
+        Name {
+            labels: self
+                .labels
+                .iter()
+                .map(|label| label.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    // Canonical DNS name order (RFC 4034 6.1): label by label from the root,
+    // each compared as lower case octets, with a name before its subdomains
+    pub fn canonical_cmp(&self, other: &Name) -> Ordering {
+        let lower = |label: &Vec<u8>| label.to_ascii_lowercase();
+        self.labels
+            .iter()
+            .rev()
+            .map(lower)
+            .cmp(other.labels.iter().rev().map(lower))
+    }
+
+    // Length of the uncompressed wire form, root label included
+    pub fn wire_len(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.wire_len());
+
+        for label in &self.labels {
+            bytes.push(label.len() as u8);
+            bytes.extend_from_slice(label);
+        }
+        // Null byte to terminate the domain name
+        bytes.push(0);
+
+        bytes
+    }
+
+    // Returns the decoded name and the position right after it in `data`
+    pub fn from_bytes(data: &[u8], start_pos: usize) -> Result<(Name, usize), DecodeError> {
+        let mut labels: Vec<Vec<u8>> = Vec::new();
+        let mut wire_len = 1;
+        let mut cursor = start_pos;
+        // where the name ends in the original stream once a pointer is followed
+        let mut end: Option<usize> = None;
+        let mut hops = 0;
+
+        loop {
+            let length = take(data, cursor, 1)?[0];
+
+            match length & 0b11000000 {
+                0b11000000 => {
+                    let target = (read_u16(data, cursor)? & 0x3FFF) as usize;
+                    if target == cursor || hops == MAX_POINTER_HOPS {
+                        return Err(DecodeError::PointerLoop(cursor));
+                    }
+                    if target > cursor {
+                        return Err(DecodeError::ForwardPointer(cursor));
+                    }
+                    end.get_or_insert(cursor + 2);
+                    hops += 1;
+                    cursor = target;
+                }
+                0 if length == 0 => {
+                    cursor += 1;
+                    break;
+                }
+                0 => {
+                    let label = take(data, cursor + 1, length as usize)?;
+                    wire_len += label.len() + 1;
+                    if wire_len > MAX_NAME_LEN {
+                        return Err(DecodeError::NameTooLong(start_pos));
+                    }
+                    labels.push(label.to_vec());
+                    cursor += label.len() + 1;
+                }
+                // 0b01 and 0b10 label types are extended or reserved
+                _ => {
+                    return Err(DecodeError::BadLabelLength {
+                        offset: cursor,
+                        length,
+                    })
+                }
+            }
+        }
+
+        Ok((Name { labels }, end.unwrap_or(cursor)))
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in &self.labels {
+            state.write_u8(label.len() as u8);
+            for byte in label {
+                state.write_u8(byte.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+// Presentation format (RFC 1035 5.1): special characters are escaped with a
+// backslash and non-printable octets as `\DDD`. The root name is ".". The
+// alternate flag (`{:#}`) adds the trailing dot of a fully qualified name.
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            for &byte in label {
+                match byte {
+                    b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                        write!(f, "\\{}", byte as char)?
+                    }
+                    0x21..=0x7E => write!(f, "{}", byte as char)?,
+                    _ => write!(f, "\\{:03}", byte)?,
+                }
+            }
+        }
+
+        if f.alternate() {
+            write!(f, ".")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Name {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "." {
+            return Ok(Name::root());
+        }
+
+        let mut labels: Vec<Vec<u8>> = Vec::new();
+        let mut label: Vec<u8> = Vec::new();
+        let mut bytes = s.bytes();
+
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'.' => {
+                    if label.is_empty() {
+                        return Err(ParseError::EmptyLabel(s.to_string()));
+                    }
+                    labels.push(std::mem::take(&mut label));
+                }
+                b'\\' => {
+                    let escaped = bytes
+                        .next()
+                        .ok_or_else(|| ParseError::BadEscape(s.to_string()))?;
+                    if escaped.is_ascii_digit() {
+                        // `\DDD` is always exactly three decimal digits
+                        let mut value = (escaped - b'0') as u16;
+                        for _ in 0..2 {
+                            match bytes.next() {
+                                Some(digit) if digit.is_ascii_digit() => {
+                                    value = value * 10 + (digit - b'0') as u16
+                                }
+                                _ => return Err(ParseError::BadEscape(s.to_string())),
+                            }
+                        }
+                        let value = u8::try_from(value)
+                            .map_err(|_| ParseError::BadEscape(s.to_string()))?;
+                        label.push(value);
+                    } else {
+                        label.push(escaped);
+                    }
+                }
+                _ => label.push(byte),
+            }
+        }
+
+        // a trailing dot only marks the name as fully qualified
+        if !label.is_empty() {
+            labels.push(label);
+        } else if labels.is_empty() {
+            return Err(ParseError::EmptyLabel(s.to_string()));
+        }
+
+        Name::from_labels(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_to_bytes() {
+        let name: Name = "example.com".parse().unwrap();
+        let expected_bytes = vec![
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+        ];
+        assert_eq!(name.to_bytes(), expected_bytes);
+        assert_eq!(name.wire_len(), expected_bytes.len());
+        assert_eq!(Name::root().to_bytes(), vec![0]);
+    }
+
+    #[test]
+    fn test_compressed_name_from_bytes() {
+        let bytes = vec![
+            144, 189, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, // header
+            2, 101, 110, // "en" label
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            2, 101, 115, // "es" label
+            192, 15, // pointer to "example" label
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+        ];
+        let (name, cursor) = Name::from_bytes(&bytes, 12).unwrap();
+        assert_eq!(name.to_string(), "en.example.com");
+        assert_eq!(cursor, 28);
+        let (name, cursor) = Name::from_bytes(&bytes, 32).unwrap();
+        assert_eq!(name.to_string(), "es.example.com");
+        assert_eq!(cursor, 37);
+    }
+
+    #[test]
+    fn test_malformed_name_from_bytes() {
+        // pointer to itself
+        assert_eq!(
+            Name::from_bytes(&[192, 0], 0),
+            Err(DecodeError::PointerLoop(0))
+        );
+
+        // pointer past itself
+        assert_eq!(
+            Name::from_bytes(&[192, 2, 0], 0),
+            Err(DecodeError::ForwardPointer(0))
+        );
+
+        // two backwards pointers bouncing between each other
+        let bytes = [1, 97, 192, 0];
+        assert_eq!(
+            Name::from_bytes(&bytes, 0),
+            Err(DecodeError::NameTooLong(0))
+        );
+
+        // reserved label type 0b01
+        assert_eq!(
+            Name::from_bytes(&[64, 0], 0),
+            Err(DecodeError::BadLabelLength {
+                offset: 0,
+                length: 64
+            })
+        );
+
+        // 5 labels of 63 bytes are longer than 255 bytes
+        let mut bytes = Vec::new();
+        for _ in 0..5 {
+            bytes.push(63);
+            bytes.extend([b'a'; 63]);
+        }
+        bytes.push(0);
+        assert_eq!(
+            Name::from_bytes(&bytes, 0),
+            Err(DecodeError::NameTooLong(0))
+        );
+    }
+
+    #[test]
+    fn test_name_presentation_round_trip() {
+        let name =
+            Name::from_labels(vec![b"a.b".to_vec(), vec![0, 0xff, b' '], b"com".to_vec()]).unwrap();
+        assert_eq!(name.to_string(), "a\\.b.\\000\\255\\032.com");
+        assert_eq!(
+            name.to_string().parse::<Name>().unwrap().labels(),
+            name.labels()
+        );
+
+        assert_eq!(
+            "example.com.".parse::<Name>().unwrap().to_string(),
+            "example.com"
+        );
+        assert_eq!(".".parse::<Name>().unwrap(), Name::root());
+        assert_eq!(Name::root().to_string(), ".");
+        assert_eq!(
+            format!("{:#}", "example.com".parse::<Name>().unwrap()),
+            "example.com."
+        );
+        assert_eq!(format!("{:#}", Name::root()), ".");
+    }
+
+    #[test]
+    fn test_invalid_name_from_str() {
+        assert!(matches!(
+            "a..b".parse::<Name>(),
+            Err(ParseError::EmptyLabel(_))
+        ));
+        assert!(matches!("".parse::<Name>(), Err(ParseError::EmptyLabel(_))));
+        assert!(matches!(
+            "a\\25".parse::<Name>(),
+            Err(ParseError::BadEscape(_))
+        ));
+        assert!(matches!(
+            "a\\300".parse::<Name>(),
+            Err(ParseError::BadEscape(_))
+        ));
+        assert!(matches!(
+            "a".repeat(64).parse::<Name>(),
+            Err(ParseError::LabelTooLong(_))
+        ));
+        assert!(matches!(
+            vec!["a".repeat(63); 5].join(".").parse::<Name>(),
+            Err(ParseError::NameTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn test_name_eq_ignores_case() {
+        let lower: Name = "example.com".parse().unwrap();
+        let upper: Name = "EXAMPLE.Com".parse().unwrap();
+        assert_eq!(lower, upper);
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(lower);
+        assert!(set.contains(&upper));
+    }
+
+    #[test]
+    fn test_is_subdomain_of() {
+        let name: Name = "www.Corp.internal".parse().unwrap();
+        assert!(name.is_subdomain_of(&"corp.INTERNAL".parse().unwrap()));
+        assert!(name.is_subdomain_of(&name));
+        assert!(name.is_subdomain_of(&Name::root()));
+        assert!(!name.is_subdomain_of(&"orp.internal".parse().unwrap()));
+        assert!(!Name::root().is_subdomain_of(&name));
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        // the example from RFC 4034 6.1, in order
+        let names: Vec<Name> = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "\\001.z.example",
+            "*.z.example",
+            "\\200.z.example",
+        ]
+        .iter()
+        .map(|name| name.parse().unwrap())
+        .collect();
+
+        let mut sorted = names.clone();
+        sorted.reverse();
+        sorted.sort_by(Name::canonical_cmp);
+        assert_eq!(sorted, names);
+
+        assert_eq!(
+            names[4].to_lowercase().labels(),
+            &[b"zabc".to_vec(), b"a".to_vec(), b"example".to_vec()]
+        );
+    }
+}
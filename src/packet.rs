@@ -1,17 +1,52 @@
 use crate::{
-    header::{self, Header},
-    question::{self, Question},
-    resource_records::{self, Record},
+    edns::Edns,
+    encoder::Encoder,
+    error::{DecodeError, EncodeError},
+    field::QType,
+    header::Header,
+    question::Question,
+    resource_records::ResourceRecord,
 };
 
-#[derive(Debug, Clone)]
+// Largest message that fits a UDP datagram without EDNS (RFC 1035 4.2.1)
+pub const MAX_UDP_SIZE: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
     pub header: Header,
     pub questions: Vec<Question>,
-    pub records: Vec<Record>,
+    pub answers: Vec<ResourceRecord>,
+    pub authorities: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>,
+    pub edns: Option<Edns>,
 }
 
 impl Packet {
+    pub fn new(header: Header) -> Self {
+        Self {
+            header,
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+            edns: None,
+        }
+    }
+
+    // Empty reply to `query` carrying its id, opcode and RD and CD flags
+    pub fn response(query: &Header, response_code: u8) -> Self {
+        let header = Header::default()
+            .id(query.id)
+            .query_response(true)
+            .opcode(query.opcode)
+            .recursion_desired(query.recursion_desired)
+            .checking_disabled(query.checking_disabled)
+            .response_code(response_code)
+            .build();
+
+        Self::new(header)
+    }
+
     pub fn split(&mut self) -> Vec<Packet> {
         let header = self.header.question_count(1).build();
 
@@ -19,61 +54,438 @@ impl Packet {
             .clone()
             .into_iter()
             .map(|question| Self {
-                header,
                 questions: vec![question],
-                records: vec![],
+                edns: self.edns.clone(),
+                ..Self::new(header)
             })
             .collect()
     }
 
     pub fn merge(packets: Vec<Packet>) -> Packet {
-        let mut header = packets[0].header;
-        let header = header
-            .question_count(packets.len() as u16)
-            .answer_count(packets.len() as u16)
-            .build();
-
-        let mut questions: Vec<Question> = Vec::new();
-        let mut records: Vec<Record> = Vec::new();
+        let mut packet = Packet::new(packets.first().map(|p| p.header).unwrap_or_default());
+        packet.edns = packets.first().and_then(|p| p.edns.clone());
 
-        packets.into_iter().for_each(|packet| {
-            questions.extend(packet.questions);
-            records.extend(packet.records);
+        packets.into_iter().for_each(|p| {
+            packet.questions.extend(p.questions);
+            packet.answers.extend(p.answers);
+            packet.authorities.extend(p.authorities);
+            packet.additionals.extend(p.additionals);
         });
 
-        Packet {
-            header,
-            questions,
-            records,
+        packet.header = packet.counted_header();
+        packet
+    }
+
+    // Header with the section counts taken from what is actually in the packet
+    pub fn counted_header(&self) -> Header {
+        let mut header = self.header;
+        header
+            .question_count(self.questions.len() as u16)
+            .answer_count(self.answers.len() as u16)
+            .authority_count(self.authorities.len() as u16)
+            .additional_count((self.additionals.len() + self.edns.is_some() as usize) as u16)
+            .build()
+    }
+
+    // The 12-bit RCODE, header bits extended by the OPT record (RFC 6891 6.1.3)
+    pub fn rcode(&self) -> u16 {
+        let extended = self.edns.as_ref().map_or(0, |edns| edns.extended_rcode);
+        (extended as u16) << 4 | self.header.response_code as u16
+    }
+
+    // Codes above 15 only fit when there is an OPT record to carry them
+    pub fn set_rcode(&mut self, rcode: u16) {
+        self.header.response_code = (rcode & 0x0F) as u8;
+        if let Some(edns) = self.edns.as_mut() {
+            edns.extended_rcode = (rcode >> 4) as u8;
         }
     }
 
-    pub fn from_bytes(buf: [u8; 512]) -> Packet {
-        let header = Header::from_bytes(&buf[..12]);
+    // Largest UDP message the sender of this packet accepts
+    pub fn max_udp_size(&self) -> usize {
+        self.edns.as_ref().map_or(MAX_UDP_SIZE, Edns::max_udp_size)
+    }
+
+    // Drops whole RRsets from the end until the message fits in `max_size`
+    // bytes. Losing additional data is fine, losing anything from the answer
+    // or authority sections sets TC (RFC 2181 9).
+    pub fn truncate(&mut self, max_size: usize) -> Result<(), EncodeError> {
+        while self.to_bytes()?.len() > max_size && pop_rrset(&mut self.additionals) {}
+
+        while self.to_bytes()?.len() > max_size
+            && (pop_rrset(&mut self.authorities) || pop_rrset(&mut self.answers))
+        {
+            self.header.truncated_msg = true;
+        }
+        Ok(())
+    }
+
+    // Encodes the message, compressing repeated names
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut encoder = Encoder::new();
+
+        encoder.put_slice(&self.counted_header().to_bytes());
+
+        for question in &self.questions {
+            question.encode(&mut encoder);
+        }
+
+        for record in self
+            .answers
+            .iter()
+            .chain(&self.authorities)
+            .chain(&self.additionals)
+        {
+            record.encode(&mut encoder)?;
+        }
+
+        if let Some(edns) = &self.edns {
+            edns.to_record().encode(&mut encoder)?;
+        }
+
+        Ok(encoder.finish())
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Packet, DecodeError> {
+        let header = Header::from_bytes(buf)?;
 
         let mut questions: Vec<Question> = Vec::new();
 
         let mut idx = 12;
 
         for _ in 0..header.question_count {
-            let mut end_of_q = idx;
-            while buf[end_of_q] != 0 {
-                end_of_q += 1;
-            }
-            // add 4 corressponding to the `QueryType` (2 bytes) and `Class` (2 bytes)
-            end_of_q += 4;
+            let (question, next) = Question::from_bytes(buf, idx)?;
+            questions.push(question);
+            idx = next;
+        }
 
-            end_of_q += 1;
+        let mut records = |count: u16| -> Result<Vec<ResourceRecord>, DecodeError> {
+            (0..count)
+                .map(|_| {
+                    let (record, next) = ResourceRecord::from_bytes(buf, idx)?;
+                    idx = next;
+                    Ok(record)
+                })
+                .collect()
+        };
 
-            let question = Question::from_bytes(&buf, idx);
-            questions.push(question);
-            idx = end_of_q;
+        let answers = records(header.answer_count)?;
+        let authorities = records(header.authority_count)?;
+
+        // at most one OPT record, and only in the additional section
+        let mut additionals: Vec<ResourceRecord> = Vec::new();
+        let mut edns: Option<Edns> = None;
+        for _ in 0..header.additional_count {
+            let (record, next) = ResourceRecord::from_bytes(buf, idx)?;
+            if record.qtype() == QType::OPT {
+                if edns.is_some() {
+                    return Err(DecodeError::BadOpt(idx));
+                }
+                edns = Some(Edns::from_record(&record, idx)?);
+            } else {
+                additionals.push(record);
+            }
+            idx = next;
+        }
+
+        if idx != buf.len() {
+            return Err(DecodeError::TrailingData(buf.len() - idx));
         }
 
-        Packet {
+        Ok(Packet {
             header,
             questions,
-            records: vec![],
-        }
+            answers,
+            authorities,
+            additionals,
+            edns,
+        })
+    }
+}
+
+// Removes the RRset of the last record, wherever its records are in the
+// section. Returns false when there is nothing left to remove.
+fn pop_rrset(records: &mut Vec<ResourceRecord>) -> bool {
+    let Some(last) = records.last() else {
+        return false;
+    };
+
+    let (name, qtype, class) = (last.name.clone(), last.qtype(), last.class);
+    records
+        .retain(|record| record.name != name || record.qtype() != qtype || record.class != class);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{
+        field::{Class, QType},
+        rdata::RData,
+    };
+
+    #[test]
+    fn test_packet_to_bytes() {
+        let mut header = Header::default();
+        // the counts set here disagree with the sections and must be recomputed
+        let header = header.id(1234).question_count(7).answer_count(0).build();
+
+        let packet = Packet {
+            header,
+            questions: vec![Question::new(
+                "example.com".parse().unwrap(),
+                QType::A,
+                Class::IN,
+            )],
+            answers: vec![ResourceRecord::new(
+                "example.com".parse().unwrap(),
+                Class::IN,
+                60,
+                RData::A(Ipv4Addr::new(8, 8, 8, 8)),
+            )],
+            authorities: vec![],
+            additionals: vec![],
+            edns: None,
+        };
+
+        let expected_bytes = vec![
+            0x04, 0xD2, // ID: 1234
+            0x00, 0x00, // Flags
+            0x00, 0x01, // Question Count: 1
+            0x00, 0x01, // Answer Count: 1
+            0x00, 0x00, // Authority Count: 0
+            0x00, 0x00, // Additional Count: 0
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            192, 12, // pointer to "example.com"
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            0, 0, 0, 60, // ttl
+            0, 4, // rdlength
+            8, 8, 8, 8, // rdata
+        ];
+
+        assert_eq!(packet.to_bytes().unwrap(), expected_bytes);
+    }
+
+    #[test]
+    fn test_packet_merge_counts_sections() {
+        let mut header = Header::default();
+        let header = header.id(1).question_count(2).build();
+        let mut packet = Packet {
+            questions: vec![
+                Question::new("a.com".parse().unwrap(), QType::A, Class::IN),
+                Question::new("b.com".parse().unwrap(), QType::A, Class::IN),
+            ],
+            ..Packet::new(header)
+        };
+
+        let mut packets = packet.split();
+        assert_eq!(packets.len(), 2);
+        packets[1].authorities.push(ResourceRecord::new(
+            "com".parse().unwrap(),
+            Class::IN,
+            60,
+            RData::NS("ns.com".parse().unwrap()),
+        ));
+
+        let merged = Packet::merge(packets);
+        assert_eq!(merged.header.question_count, 2);
+        assert_eq!(merged.header.answer_count, 0);
+        assert_eq!(merged.header.authority_count, 1);
+        assert_eq!(merged.header.additional_count, 0);
+    }
+
+    #[test]
+    fn test_packet_from_bytes_reads_all_sections() {
+        let bytes = [
+            0x04, 0xD2, 0x81, 0x80, // ID, flags
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, // counts
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            192, 12, // answer: pointer to "example.com"
+            0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4, // A 1.2.3.4
+            192, 20, // authority: pointer to "com"
+            0, 2, 0, 1, 0, 0, 0, 60, 0, 5, // NS
+            2, 110, 115, 192, 20, // "ns.com"
+            192, 57, // additional: pointer to "ns.com"
+            0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 5, 6, 7, 8, // A 5.6.7.8
+        ];
+        let packet = Packet::from_bytes(&bytes).unwrap();
+        assert_eq!(packet.questions.len(), 1);
+        assert_eq!(packet.answers.len(), 1);
+        assert_eq!(packet.answers[0].name.to_string(), "example.com");
+        assert_eq!(packet.answers[0].rdata, RData::A(Ipv4Addr::new(1, 2, 3, 4)));
+        assert_eq!(packet.authorities.len(), 1);
+        assert_eq!(packet.authorities[0].name.to_string(), "com");
+        assert_eq!(packet.authorities[0].qtype(), QType::NS);
+        assert_eq!(packet.additionals.len(), 1);
+        assert_eq!(packet.additionals[0].name.to_string(), "ns.com");
+        assert_eq!(
+            packet.additionals[0].rdata,
+            RData::A(Ipv4Addr::new(5, 6, 7, 8))
+        );
+
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_packet_from_malformed_bytes() {
+        let query = [
+            0x04, 0xD2, 0x01, 0x00, // ID, flags
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // counts
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+        ];
+        assert!(Packet::from_bytes(&query).is_ok());
+
+        assert_eq!(
+            Packet::from_bytes(&query[..query.len() - 1]),
+            Err(DecodeError::Truncated {
+                offset: 19,
+                needed: 2
+            })
+        );
+
+        let mut padded = query.to_vec();
+        padded.extend([0, 0, 0]);
+        assert_eq!(
+            Packet::from_bytes(&padded),
+            Err(DecodeError::TrailingData(3))
+        );
+
+        // claims an answer that is not there
+        let mut counted = query.to_vec();
+        counted[7] = 1;
+        assert!(matches!(
+            Packet::from_bytes(&counted),
+            Err(DecodeError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn test_packet_from_bytes_with_compressed_questions() {
+        let bytes = [
+            0x04, 0xD2, 0x01, 0x00, // ID, flags
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // counts
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 2, // qtype NS (2)
+            0, 1, // class IN (1)
+            2, 109, 120, 192, 12, // "mx" + pointer to "com"
+            0, 15, // qtype MX (15)
+            0, 1, // class IN (1)
+        ];
+
+        let packet = Packet::from_bytes(&bytes).unwrap();
+        assert_eq!(packet.questions[0].qtype, QType::NS);
+        assert_eq!(packet.questions[1].name.to_string(), "mx.com");
+        assert_eq!(packet.questions[1].qtype, QType::MX);
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_packet_with_edns() {
+        let query = [
+            0x04, 0xD2, 0x01, 0x00, // ID, flags
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // counts
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            0, // OPT: root name
+            0, 41, // qtype OPT (41)
+            4, 208, // udp payload size 1232
+            0, 0, 128, 0, // extended rcode, version, DO
+            0, 0, // rdlength
+        ];
+
+        let mut packet = Packet::from_bytes(&query).unwrap();
+        assert!(packet.additionals.is_empty());
+        let edns = packet.edns.clone().unwrap();
+        assert_eq!(edns.udp_payload_size, 1232);
+        assert!(edns.dnssec_ok);
+        assert_eq!(packet.max_udp_size(), 1232);
+        assert_eq!(packet.to_bytes().unwrap(), query);
+
+        // BADVERS (16) does not fit the header alone
+        packet.set_rcode(crate::edns::BADVERS);
+        assert_eq!(packet.header.response_code, 0);
+        assert_eq!(packet.edns.as_ref().unwrap().extended_rcode, 1);
+        assert_eq!(
+            Packet::from_bytes(&packet.to_bytes().unwrap())
+                .unwrap()
+                .rcode(),
+            crate::edns::BADVERS
+        );
+
+        assert_eq!(Packet::new(Header::default()).max_udp_size(), MAX_UDP_SIZE);
+    }
+
+    #[test]
+    fn test_packet_with_two_opt_records() {
+        let opt = [0, 0, 41, 4, 208, 0, 0, 0, 0, 0, 0];
+        let mut bytes = vec![
+            0x04, 0xD2, 0x01, 0x00, // ID, flags
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // counts
+        ];
+        bytes.extend(opt);
+        bytes.extend(opt);
+        assert_eq!(Packet::from_bytes(&bytes), Err(DecodeError::BadOpt(23)));
+    }
+
+    #[test]
+    fn test_packet_truncate_drops_whole_rrsets() {
+        let record = |name: &str, last_octet: u8| {
+            ResourceRecord::new(
+                name.parse().unwrap(),
+                Class::IN,
+                60,
+                RData::A(Ipv4Addr::new(10, 0, 0, last_octet)),
+            )
+        };
+        let mut packet = Packet {
+            questions: vec![Question::new("a.com".parse().unwrap(), QType::A, Class::IN)],
+            answers: vec![record("a.com", 1), record("b.com", 2), record("a.com", 3)],
+            additionals: vec![record("c.com", 4)],
+            ..Packet::new(Header::default())
+        };
+
+        // dropping the additional record is enough
+        let mut trimmed = packet.clone();
+        trimmed
+            .truncate(packet.to_bytes().unwrap().len() - 1)
+            .unwrap();
+        assert!(trimmed.additionals.is_empty());
+        assert_eq!(trimmed.answers.len(), 3);
+        assert!(!trimmed.header.truncated_msg);
+
+        // both a.com records go together even though they are not adjacent
+        packet.truncate(60).unwrap();
+        assert!(packet.header.truncated_msg);
+        assert_eq!(packet.answers, vec![record("b.com", 2)]);
+        assert!(packet.to_bytes().unwrap().len() <= 60);
+        assert_eq!(
+            Packet::from_bytes(&packet.to_bytes().unwrap())
+                .unwrap()
+                .answers
+                .len(),
+            1
+        );
+
+        let mut small = packet.clone();
+        small.truncate(MAX_UDP_SIZE).unwrap();
+        assert_eq!(small, packet);
     }
 }
@@ -0,0 +1,138 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// Fixed number of worker threads fed through a bounded queue. A full queue
+// is how callers notice the workers cannot keep up.
+pub struct ThreadPool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    pub fn new(workers: usize, queue_size: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || work(&receiver))
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    // Queues `job`, waiting for room if the queue is full
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        if let Some(sender) = &self.sender {
+            // only fails once every worker is gone
+            let _ = sender.send(Box::new(job));
+        }
+    }
+
+    // Queues `job` unless the queue is full, in which case it is dropped and
+    // false returned
+    pub fn try_execute<F: FnOnce() + Send + 'static>(&self, job: F) -> bool {
+        self.sender
+            .as_ref()
+            .is_some_and(|sender| sender.try_send(Box::new(job)).is_ok())
+    }
+}
+
+// Runs jobs until the pool is dropped. A panicking job takes down neither the
+// worker nor the server.
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        match job {
+            Ok(job) => {
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    eprintln!("Worker job panicked");
+                }
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+// Waits for the queued jobs to finish
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicUsize, atomic::Ordering, Barrier};
+
+    use super::*;
+
+    #[test]
+    fn test_pool_runs_every_job() {
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let pool = ThreadPool::new(4, 8);
+        for _ in 0..100 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(pool);
+
+        assert_eq!(done.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_pool_rejects_jobs_when_full() {
+        let pool = ThreadPool::new(1, 1);
+        let barrier = Arc::new(Barrier::new(2));
+
+        // keep the only worker busy
+        let worker_barrier = Arc::clone(&barrier);
+        pool.execute(move || {
+            worker_barrier.wait();
+            worker_barrier.wait();
+        });
+        barrier.wait();
+
+        assert!(pool.try_execute(|| {}));
+        assert!(!pool.try_execute(|| {}));
+
+        barrier.wait();
+    }
+
+    #[test]
+    fn test_pool_survives_panicking_jobs() {
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let pool = ThreadPool::new(1, 4);
+        pool.execute(|| panic!("job failed"));
+        let job_done = Arc::clone(&done);
+        pool.execute(move || {
+            job_done.fetch_add(1, Ordering::SeqCst);
+        });
+        drop(pool);
+
+        assert_eq!(done.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,485 @@
+// Helpers for the text form of record data used in master files and by tools
+// such as dig (RFC 1035 5.1)
+
+use std::str::FromStr;
+
+use crate::{error::ParseError, name::Name};
+
+// Splits on unquoted whitespace. Quoted strings stay one token, quotes and
+// escapes included, so the field parsers can tell `"a b"` from `a b`.
+pub fn tokenize(s: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                token.push(c);
+                if let Some(escaped) = chars.next() {
+                    token.push(escaped);
+                }
+            }
+            '"' => {
+                token.push(c);
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ParseError::Unterminated(s.to_string()));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// `@` is the origin, names without a trailing dot are relative to it
+pub fn parse_name(token: &str, origin: &Name) -> Result<Name, ParseError> {
+    if token == "@" {
+        return Ok(origin.clone());
+    }
+
+    let name: Name = token.parse()?;
+
+    // a trailing dot preceded by an even number of backslashes is unescaped
+    let trailing_backslashes = token
+        .trim_end_matches('.')
+        .bytes()
+        .rev()
+        .take_while(|&b| b == b'\\')
+        .count();
+    let absolute = token.ends_with('.') && trailing_backslashes % 2 == 0;
+
+    if absolute {
+        Ok(name)
+    } else {
+        name.append(origin)
+    }
+}
+
+// <character-string>, either a bare word or a quoted string
+pub fn parse_character_string(token: &str) -> Result<Vec<u8>, ParseError> {
+    let bytes = unescape(unquote(token)?)?;
+
+    if bytes.len() > 255 {
+        return Err(ParseError::InvalidValue(token.to_string()));
+    }
+
+    Ok(bytes)
+}
+
+// Removes the surrounding quotes, if any
+pub fn unquote(token: &str) -> Result<&str, ParseError> {
+    match token.strip_prefix('"') {
+        Some(inner) => inner
+            .strip_suffix('"')
+            .ok_or_else(|| ParseError::Unterminated(token.to_string())),
+        None => Ok(token),
+    }
+}
+
+// Resolves `\X` and `\DDD` escapes into raw bytes
+pub fn unescape(s: &str) -> Result<Vec<u8>, ParseError> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let escaped = iter
+            .next()
+            .ok_or_else(|| ParseError::BadEscape(s.to_string()))?;
+        if !escaped.is_ascii_digit() {
+            bytes.push(escaped);
+            continue;
+        }
+
+        let mut value = (escaped - b'0') as u16;
+        for _ in 0..2 {
+            match iter.next() {
+                Some(digit) if digit.is_ascii_digit() => value = value * 10 + (digit - b'0') as u16,
+                _ => return Err(ParseError::BadEscape(s.to_string())),
+            }
+        }
+        bytes.push(u8::try_from(value).map_err(|_| ParseError::BadEscape(s.to_string()))?);
+    }
+
+    Ok(bytes)
+}
+
+pub fn parse_number<T: FromStr>(token: &str) -> Result<T, ParseError> {
+    token
+        .parse()
+        .map_err(|_| ParseError::InvalidValue(token.to_string()))
+}
+
+// TTLs and SOA timers, either plain seconds or BIND style units (`1h30m`)
+pub fn parse_ttl(token: &str) -> Result<u32, ParseError> {
+    if let Ok(seconds) = token.parse::<u32>() {
+        return Ok(seconds);
+    }
+
+    let invalid = || ParseError::InvalidValue(token.to_string());
+    let mut total: u32 = 0;
+    let mut value: Option<u32> = None;
+
+    for c in token.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let next = value
+                .unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit));
+            value = Some(next.ok_or_else(invalid)?);
+            continue;
+        }
+
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return Err(invalid()),
+        };
+        let seconds = value.take().ok_or_else(invalid)?.checked_mul(unit);
+        total = seconds
+            .and_then(|s| total.checked_add(s))
+            .ok_or_else(invalid)?;
+    }
+
+    if value.is_some() || token.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>, ParseError> {
+    let invalid = || ParseError::InvalidValue(s.to_string());
+
+    if s.len() % 2 == 1 {
+        return Err(invalid());
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Standard padded base64 (RFC 4648 4)
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let block = chunk.iter().enumerate().fold(0u32, |block, (i, &byte)| {
+            block | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(block >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+pub fn from_base64(s: &str) -> Result<Vec<u8>, ParseError> {
+    let invalid = || ParseError::InvalidValue(s.to_string());
+
+    let data = s.trim_end_matches('=');
+    if s.len() % 4 == 1 || s.len() - data.len() > 2 {
+        return Err(invalid());
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(data.len() * 3 / 4);
+    let mut block: u32 = 0;
+    let mut bits = 0;
+
+    for c in data.bytes() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&b| b == c)
+            .ok_or_else(invalid)?;
+        block = (block << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((block >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+const BASE32HEX_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+// Base32 with the extended hex alphabet and no padding, as NSEC3 uses it
+// (RFC 4648 7, RFC 5155 3.3)
+pub fn to_base32hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut block: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        block = (block << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32HEX_ALPHABET[(block >> bits) as usize & 0x1F] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32HEX_ALPHABET[(block << (5 - bits)) as usize & 0x1F] as char);
+    }
+
+    out
+}
+
+pub fn from_base32hex(s: &str) -> Result<Vec<u8>, ParseError> {
+    let invalid = || ParseError::InvalidValue(s.to_string());
+
+    // only whole bytes may be left over
+    if matches!(s.len() % 8, 1 | 3 | 6) {
+        return Err(invalid());
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len() * 5 / 8);
+    let mut block: u32 = 0;
+    let mut bits = 0;
+
+    for c in s.bytes() {
+        let value = BASE32HEX_ALPHABET
+            .iter()
+            .position(|&b| b == c.to_ascii_uppercase())
+            .ok_or_else(invalid)?;
+        block = (block << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((block >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+// RRSIG timestamps are shown as YYYYMMDDHHmmSS in UTC (RFC 4034 3.2)
+pub fn to_timestamp(seconds: u32) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// Also takes the plain number of seconds since the epoch
+pub fn parse_timestamp(token: &str) -> Result<u32, ParseError> {
+    let invalid = || ParseError::InvalidValue(token.to_string());
+
+    if token.len() != 14 {
+        return parse_number(token);
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let field = |range: std::ops::Range<usize>| token[range].parse::<i64>().unwrap();
+    let (year, month, day) = (field(0..4), field(4..6), field(6..8));
+    let (hour, minute, second) = (field(8..10), field(10..12), field(12..14));
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(invalid());
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    u32::try_from(seconds).map_err(|_| invalid())
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar, counted in
+// 400 year eras starting on March 1st
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"10  "a b" c\ d "x\"y""#).unwrap(),
+            vec!["10", "\"a b\"", "c\\ d", "\"x\\\"y\""]
+        );
+        assert!(matches!(
+            tokenize("\"open"),
+            Err(ParseError::Unterminated(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_name() {
+        let origin: Name = "example.com".parse().unwrap();
+        assert_eq!(parse_name("@", &origin).unwrap(), origin);
+        assert_eq!(
+            parse_name("www", &origin).unwrap().to_string(),
+            "www.example.com"
+        );
+        assert_eq!(
+            parse_name("ns.other.", &origin).unwrap().to_string(),
+            "ns.other"
+        );
+        // the final dot is part of the label, not the end of the name
+        assert_eq!(
+            parse_name("a\\.", &origin).unwrap().to_string(),
+            "a\\..example.com"
+        );
+    }
+
+    #[test]
+    fn test_parse_character_string() {
+        assert_eq!(parse_character_string("abc").unwrap(), b"abc");
+        assert_eq!(parse_character_string("\"a b\"").unwrap(), b"a b");
+        assert_eq!(parse_character_string("\"\\\"\\007\"").unwrap(), b"\"\x07");
+        assert!(parse_character_string(&"a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("3600").unwrap(), 3600);
+        assert_eq!(parse_ttl("1h30m").unwrap(), 5400);
+        assert_eq!(parse_ttl("1W").unwrap(), 604800);
+        assert!(parse_ttl("1h30").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("").is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0xde, 0xad, 0x01]), "DEAD01");
+        assert_eq!(from_hex("dead01").unwrap(), vec![0xde, 0xad, 0x01]);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(to_base64(bytes), encoded);
+            assert_eq!(from_base64(encoded).unwrap(), bytes);
+        }
+        assert!(from_base64("Zm9v!").is_err());
+        assert!(from_base64("Z").is_err());
+    }
+
+    #[test]
+    fn test_base32hex() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "CO"),
+            (b"fo", "CPNG"),
+            (b"foo", "CPNMU"),
+            (b"foob", "CPNMUOG"),
+            (b"fooba", "CPNMUOJ1"),
+            (b"foobar", "CPNMUOJ1E8"),
+        ] {
+            assert_eq!(to_base32hex(bytes), encoded);
+            assert_eq!(from_base32hex(encoded).unwrap(), bytes);
+            assert_eq!(from_base32hex(&encoded.to_lowercase()).unwrap(), bytes);
+        }
+        assert!(from_base32hex("CPNMW").is_err());
+        assert!(from_base32hex("C").is_err());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(to_timestamp(0), "19700101000000");
+        assert_eq!(to_timestamp(u32::MAX), "21060207062815");
+        // RFC 4034 3.3
+        assert_eq!(parse_timestamp("20030322173103").unwrap(), 1048354263);
+        assert_eq!(to_timestamp(1048354263), "20030322173103");
+        assert_eq!(parse_timestamp("20240229120000").unwrap(), 1709208000);
+        assert_eq!(parse_timestamp("1048354263").unwrap(), 1048354263);
+        assert!(parse_timestamp("20230229120000").is_err());
+        assert!(parse_timestamp("20031322173103").is_err());
+        assert!(parse_timestamp("19691231235959").is_err());
+        assert!(parse_timestamp("2003032217310x").is_err());
+    }
+}
@@ -1,14 +1,19 @@
-use crate::field::{Class, QType};
-
-#[derive(Debug, Clone)]
+use crate::{
+    encoder::Encoder,
+    error::{read_u16, DecodeError},
+    field::{Class, QType},
+    name::Name,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Question {
-    pub name: String,
+    pub name: Name,
     pub qtype: QType,
     pub class: Class,
 }
 
 impl Question {
-    pub fn new(name: String, qtype: QType, class: Class) -> Self {
+    pub fn new(name: Name, qtype: QType, class: Class) -> Self {
         Self { name, qtype, class }
     }
 
@@ -16,12 +21,7 @@ impl Question {
         let mut bytes: Vec<u8> = Vec::new();
 
         // Encode the name
-        for label in self.name.split('.') {
-            bytes.push(label.len() as u8);
-            bytes.extend_from_slice(label.as_bytes());
-        }
-        // Null byte to terminate the domain name
-        bytes.push(0);
+        bytes.extend(self.name.to_bytes());
 
         // Encode `qtype` (2 bytes)
         let qtype = self.qtype.to_u16();
@@ -36,37 +36,27 @@ impl Question {
         bytes
     }
 
-    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Question {
-        let (label, _) = labels_from_bytes(buf, start_pos);
-
-        Question::new(label, QType::A, Class::IN)
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_name(&self.name, true);
+        encoder.put_u16(self.qtype.to_u16());
+        encoder.put_u16(self.class.to_u16());
     }
-}
 
-// TODO: move as Label struct method
-fn labels_from_bytes(data: &[u8], start_pos: usize) -> (String, usize) {
-    let mut cursor = start_pos;
-    let mut labels: Vec<String> = Vec::new();
-
-    while data[cursor] != 0 {
-        if data[cursor] & 0b11000000 == 0b11000000 {
-            let offset =
-                (((data[cursor] & 0b00111111) as u16) << 8 | data[cursor + 1] as u16) as usize;
-            println!("=>> {:?}", offset);
-            let (label, _) = labels_from_bytes(&data, offset);
-            labels.push(label);
-            // increase the index by tw0 since the offset used 2 bytes
-            cursor += 2;
-        } else {
-            let length = data[cursor] as usize;
-            cursor += 1;
-            let label = std::str::from_utf8(&data[cursor..cursor + length]).unwrap();
-            labels.push(label.to_string());
-            cursor += length;
-        }
-    }
+    // Returns the decoded question and the position right after it in `buf`
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<(Question, usize), DecodeError> {
+        // Decode the name
+        let (name, mut idx) = Name::from_bytes(buf, start_pos)?;
 
-    (labels.join("."), cursor)
+        // Decode `qtype` (2 bytes)
+        let qtype = QType::from_u16(read_u16(buf, idx)?);
+        idx += 2;
+
+        // Decode `class` (2 bytes)
+        let class = Class::from_u16(read_u16(buf, idx)?);
+        idx += 2;
+
+        Ok((Question::new(name, qtype, class), idx))
+    }
 }
 
 #[cfg(test)]
@@ -75,7 +65,7 @@ mod tests {
 
     #[test]
     fn test_question_to_bytes() {
-        let question = Question::new("example.com".to_string(), QType::A, Class::IN);
+        let question = Question::new("example.com".parse().unwrap(), QType::A, Class::IN);
         let expected_bytes = vec![
             7, 101, 120, 97, 109, 112, 108, 101, // "example" label
             3, 99, 111, 109, // "com" label
@@ -96,33 +86,64 @@ mod tests {
             0, 1, // qtype A (1)
             0, 1, // class IN (1)
         ];
-        let question = Question::from_bytes(&bytes, 12);
-        assert_eq!(question.name, "example.com");
+        let (question, cursor) = Question::from_bytes(&bytes, 12).unwrap();
+        assert_eq!(question.name.to_string(), "example.com");
         assert_eq!(question.qtype, QType::A);
         assert_eq!(question.class, Class::IN);
+        assert_eq!(cursor, bytes.len());
     }
 
     #[test]
     fn test_compressed_question_from_bytes() {
         let bytes = vec![
-            144, 189, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, // header
-            2, 101, 110, // "en" label
+            144, 189, 1, 0, 0, 2, 0, 0, 0, 0, 0, 0, // header
             7, 101, 120, 97, 109, 112, 108, 101, // "example" label
             3, 99, 111, 109, // "com" label
             0,   // null terminator
-            0, 1, // qtype A (1)
-            0, 1, // class IN (1)
-            2, 101, 115, // "es" label
-            192, 15, // pointer to "example" label
-            0,  // null terminator
-            0, 1, // qtype A (1)
+            0, 15, // qtype MX (15)
             0, 1, // class IN (1)
+            192, 12, // pointer to "example.com"
+            0, 16, // qtype TXT (16)
+            0, 3, // class CH (3)
         ];
-        let (label, cursor) = labels_from_bytes(&bytes, 12);
-        assert_eq!(label, "en.example.com");
-        assert_eq!(cursor, 27);
-        let (label, cursor) = labels_from_bytes(&bytes, 32);
-        assert_eq!(label, "es.example.com");
-        assert_eq!(cursor, 37);
+        let (question, cursor) = Question::from_bytes(&bytes, 12).unwrap();
+        assert_eq!(question.qtype, QType::MX);
+        assert_eq!(cursor, 29);
+
+        let (question, cursor) = Question::from_bytes(&bytes, cursor).unwrap();
+        assert_eq!(question.name.to_string(), "example.com");
+        assert_eq!(question.qtype, QType::TXT);
+        assert_eq!(question.class, Class::CH);
+        assert_eq!(cursor, bytes.len());
+    }
+
+    #[test]
+    fn test_malformed_question_from_bytes() {
+        // label runs past the end of the buffer
+        let bytes = vec![7, 101, 120, 97];
+        assert_eq!(
+            Question::from_bytes(&bytes, 0),
+            Err(DecodeError::Truncated {
+                offset: 1,
+                needed: 7
+            })
+        );
+
+        // pointer to itself
+        let bytes = vec![192, 0];
+        assert_eq!(
+            Question::from_bytes(&bytes, 0),
+            Err(DecodeError::PointerLoop(0))
+        );
+
+        // reserved label type 0b01
+        let bytes = vec![64, 0];
+        assert_eq!(
+            Question::from_bytes(&bytes, 0),
+            Err(DecodeError::BadLabelLength {
+                offset: 0,
+                length: 64
+            })
+        );
     }
 }
@@ -0,0 +1,1621 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{
+    dnssec::{Dnskey, Ds, Nsec, Nsec3, Nsec3Param, Rrsig},
+    encoder::Encoder,
+    error::{read_u16, read_u32, take, DecodeError, EncodeError, ParseError},
+    field::{Class, QType},
+    name::Name,
+    presentation::{
+        from_hex, parse_character_string, parse_name, parse_number, parse_ttl, to_hex, tokenize,
+        unescape, unquote,
+    },
+    svcb::Svcb,
+};
+
+// Typed RDATA for the record types the server understands. Anything else is
+// kept as the raw bytes it arrived as, together with its type (RFC 3597).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RData {
+    A(Ipv4Addr),
+    NS(Name),
+    MD(Name),
+    MF(Name),
+    CNAME(Name),
+    SOA {
+        mname: Name,
+        rname: Name,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    MB(Name),
+    MG(Name),
+    MR(Name),
+    NULL(Vec<u8>),
+    WKS {
+        address: Ipv4Addr,
+        protocol: u8,
+        bitmap: Vec<u8>,
+    },
+    PTR(Name),
+    HINFO {
+        cpu: Vec<u8>,
+        os: Vec<u8>,
+    },
+    MINFO {
+        rmailbx: Name,
+        emailbx: Name,
+    },
+    MX {
+        preference: u16,
+        exchange: Name,
+    },
+    TXT(Vec<Vec<u8>>),
+    RP {
+        mbox: Name,
+        txt: Name,
+    },
+    AFSDB {
+        subtype: u16,
+        hostname: Name,
+    },
+    AAAA(Ipv6Addr),
+    // RFC 1876 version 0. Sizes and precisions are in the packed
+    // mantissa/exponent centimetre form, coordinates in thousandths of an
+    // arc second offset by 2^31 and the altitude in centimetres above
+    // 100 km below the WGS 84 spheroid.
+    LOC {
+        size: u8,
+        horiz_pre: u8,
+        vert_pre: u8,
+        latitude: u32,
+        longitude: u32,
+        altitude: u32,
+    },
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Name,
+    },
+    NAPTR {
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: Name,
+    },
+    DNAME(Name),
+    SSHFP {
+        algorithm: u8,
+        fp_type: u8,
+        fingerprint: Vec<u8>,
+    },
+    TLSA {
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        data: Vec<u8>,
+    },
+    URI {
+        priority: u16,
+        weight: u16,
+        target: Vec<u8>,
+    },
+    CAA {
+        flags: u8,
+        tag: Vec<u8>,
+        value: Vec<u8>,
+    },
+    SVCB(Svcb),
+    HTTPS(Svcb),
+    DS(Ds),
+    CDS(Ds),
+    RRSIG(Rrsig),
+    NSEC(Nsec),
+    DNSKEY(Dnskey),
+    CDNSKEY(Dnskey),
+    NSEC3(Nsec3),
+    NSEC3PARAM(Nsec3Param),
+    Opaque(QType, Vec<u8>),
+}
+
+impl RData {
+    pub fn qtype(&self) -> QType {
+        match self {
+            RData::A(_) => QType::A,
+            RData::NS(_) => QType::NS,
+            RData::MD(_) => QType::MD,
+            RData::MF(_) => QType::MF,
+            RData::CNAME(_) => QType::CNAME,
+            RData::SOA { .. } => QType::SOA,
+            RData::MB(_) => QType::MB,
+            RData::MG(_) => QType::MG,
+            RData::MR(_) => QType::MR,
+            RData::NULL(_) => QType::NULL,
+            RData::WKS { .. } => QType::WKS,
+            RData::PTR(_) => QType::PTR,
+            RData::HINFO { .. } => QType::HINFO,
+            RData::MINFO { .. } => QType::MINFO,
+            RData::MX { .. } => QType::MX,
+            RData::TXT(_) => QType::TXT,
+            RData::RP { .. } => QType::RP,
+            RData::AFSDB { .. } => QType::AFSDB,
+            RData::AAAA(_) => QType::AAAA,
+            RData::LOC { .. } => QType::LOC,
+            RData::SRV { .. } => QType::SRV,
+            RData::NAPTR { .. } => QType::NAPTR,
+            RData::DNAME(_) => QType::DNAME,
+            RData::SSHFP { .. } => QType::SSHFP,
+            RData::TLSA { .. } => QType::TLSA,
+            RData::URI { .. } => QType::URI,
+            RData::CAA { .. } => QType::CAA,
+            RData::SVCB(_) => QType::SVCB,
+            RData::HTTPS(_) => QType::HTTPS,
+            RData::DS(_) => QType::DS,
+            RData::CDS(_) => QType::CDS,
+            RData::RRSIG(_) => QType::RRSIG,
+            RData::NSEC(_) => QType::NSEC,
+            RData::DNSKEY(_) => QType::DNSKEY,
+            RData::CDNSKEY(_) => QType::CDNSKEY,
+            RData::NSEC3(_) => QType::NSEC3,
+            RData::NSEC3PARAM(_) => QType::NSEC3PARAM,
+            RData::Opaque(qtype, _) => *qtype,
+        }
+    }
+
+    // Only the names inside the RFC 1035 types may be compressed (RFC 3597 4)
+    pub fn encode(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        match self {
+            RData::A(address) => encoder.put_slice(&address.octets()),
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CNAME(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => encoder.put_name(name, true),
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                encoder.put_name(mname, true);
+                encoder.put_name(rname, true);
+                for value in [serial, refresh, retry, expire, minimum] {
+                    encoder.put_u32(*value);
+                }
+            }
+            RData::NULL(data) | RData::Opaque(_, data) => encoder.put_slice(data),
+            RData::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                encoder.put_slice(&address.octets());
+                encoder.put_u8(*protocol);
+                encoder.put_slice(bitmap);
+            }
+            RData::HINFO { cpu, os } => {
+                encoder.put_character_string(cpu)?;
+                encoder.put_character_string(os)?;
+            }
+            RData::MINFO { rmailbx, emailbx } => {
+                encoder.put_name(rmailbx, true);
+                encoder.put_name(emailbx, true);
+            }
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                encoder.put_u16(*preference);
+                encoder.put_name(exchange, true);
+            }
+            RData::TXT(strings) => {
+                for string in strings {
+                    encoder.put_character_string(string)?;
+                }
+            }
+            RData::RP { mbox, txt } => {
+                encoder.put_name(mbox, false);
+                encoder.put_name(txt, false);
+            }
+            RData::AFSDB { subtype, hostname } => {
+                encoder.put_u16(*subtype);
+                encoder.put_name(hostname, false);
+            }
+            RData::AAAA(address) => encoder.put_slice(&address.octets()),
+            RData::LOC {
+                size,
+                horiz_pre,
+                vert_pre,
+                latitude,
+                longitude,
+                altitude,
+            } => {
+                encoder.put_slice(&[0, *size, *horiz_pre, *vert_pre]);
+                encoder.put_u32(*latitude);
+                encoder.put_u32(*longitude);
+                encoder.put_u32(*altitude);
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                encoder.put_u16(*priority);
+                encoder.put_u16(*weight);
+                encoder.put_u16(*port);
+                encoder.put_name(target, false);
+            }
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                encoder.put_u16(*order);
+                encoder.put_u16(*preference);
+                encoder.put_character_string(flags)?;
+                encoder.put_character_string(services)?;
+                encoder.put_character_string(regexp)?;
+                encoder.put_name(replacement, false);
+            }
+            RData::DNAME(name) => encoder.put_name(name, false),
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => {
+                encoder.put_u8(*algorithm);
+                encoder.put_u8(*fp_type);
+                encoder.put_slice(fingerprint);
+            }
+            RData::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => {
+                encoder.put_slice(&[*usage, *selector, *matching_type]);
+                encoder.put_slice(data);
+            }
+            RData::URI {
+                priority,
+                weight,
+                target,
+            } => {
+                encoder.put_u16(*priority);
+                encoder.put_u16(*weight);
+                encoder.put_slice(target);
+            }
+            RData::CAA { flags, tag, value } => {
+                encoder.put_u8(*flags);
+                encoder.put_character_string(tag)?;
+                encoder.put_slice(value);
+            }
+            RData::SVCB(svcb) | RData::HTTPS(svcb) => svcb.encode(encoder),
+            RData::DS(ds) | RData::CDS(ds) => ds.encode(encoder),
+            RData::RRSIG(rrsig) => rrsig.encode(encoder),
+            RData::NSEC(nsec) => nsec.encode(encoder),
+            RData::DNSKEY(dnskey) | RData::CDNSKEY(dnskey) => dnskey.encode(encoder),
+            RData::NSEC3(nsec3) => nsec3.encode(encoder)?,
+            RData::NSEC3PARAM(param) => param.encode(encoder)?,
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut encoder = Encoder::uncompressed();
+        self.encode(&mut encoder)?;
+        Ok(encoder.finish())
+    }
+
+    // Canonical form for DNSSEC: the names inside the types listed in RFC 4034
+    // 6.2 in lower case. NSEC is left alone (RFC 6840 5.1).
+    pub fn to_canonical(&self) -> RData {
+        let mut rdata = self.clone();
+        match &mut rdata {
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CNAME(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name)
+            | RData::DNAME(name)
+            | RData::MX { exchange: name, .. }
+            | RData::AFSDB { hostname: name, .. }
+            | RData::SRV { target: name, .. }
+            | RData::NAPTR {
+                replacement: name, ..
+            }
+            | RData::RRSIG(Rrsig { signer: name, .. }) => *name = name.to_lowercase(),
+            RData::SOA { mname, rname, .. } => {
+                *mname = mname.to_lowercase();
+                *rname = rname.to_lowercase();
+            }
+            RData::MINFO { rmailbx, emailbx } => {
+                *rmailbx = rmailbx.to_lowercase();
+                *emailbx = emailbx.to_lowercase();
+            }
+            RData::RP { mbox, txt } => {
+                *mbox = mbox.to_lowercase();
+                *txt = txt.to_lowercase();
+            }
+            _ => {}
+        }
+        rdata
+    }
+
+    // Decodes the `rdlength` bytes at `start_pos`. `buf` is the whole message
+    // so that compressed names can be followed.
+    pub fn from_bytes(
+        buf: &[u8],
+        start_pos: usize,
+        rdlength: u16,
+        qtype: QType,
+        class: Class,
+    ) -> Result<RData, DecodeError> {
+        let data = take(buf, start_pos, rdlength as usize)?;
+        let end = start_pos + data.len();
+        // nothing inside the rdata may be read past its end
+        let buf = &buf[..end];
+        let mut idx = start_pos;
+
+        let rdata = match qtype {
+            // class ANY only appears in dynamic updates (RFC 2136), with no rdata
+            _ if class == Class::ANY => {
+                idx = end;
+                RData::Opaque(qtype, data.to_vec())
+            }
+            // A and WKS are only defined for the Internet class
+            QType::A if class == Class::IN => {
+                let address = Ipv4Addr::from(read_u32(buf, idx)?);
+                idx += 4;
+                RData::A(address)
+            }
+            QType::NS
+            | QType::MD
+            | QType::MF
+            | QType::CNAME
+            | QType::MB
+            | QType::MG
+            | QType::MR
+            | QType::PTR => {
+                let (name, next) = Name::from_bytes(buf, idx)?;
+                idx = next;
+                match qtype {
+                    QType::NS => RData::NS(name),
+                    QType::MD => RData::MD(name),
+                    QType::MF => RData::MF(name),
+                    QType::CNAME => RData::CNAME(name),
+                    QType::MB => RData::MB(name),
+                    QType::MG => RData::MG(name),
+                    QType::MR => RData::MR(name),
+                    _ => RData::PTR(name),
+                }
+            }
+            QType::SOA => {
+                let (mname, next) = Name::from_bytes(buf, idx)?;
+                let (rname, next) = Name::from_bytes(buf, next)?;
+                let mut values = [0u32; 5];
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = read_u32(buf, next + i * 4)?;
+                }
+                idx = next + 20;
+                let [serial, refresh, retry, expire, minimum] = values;
+                RData::SOA {
+                    mname,
+                    rname,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                }
+            }
+            QType::NULL => {
+                idx = end;
+                RData::NULL(data.to_vec())
+            }
+            QType::WKS if class == Class::IN => {
+                let address = Ipv4Addr::from(read_u32(buf, idx)?);
+                let protocol = take(buf, idx + 4, 1)?[0];
+                let bitmap = buf[idx + 5..].to_vec();
+                idx = end;
+                RData::WKS {
+                    address,
+                    protocol,
+                    bitmap,
+                }
+            }
+            QType::HINFO => {
+                let (cpu, next) = character_string_from_bytes(buf, idx)?;
+                let (os, next) = character_string_from_bytes(buf, next)?;
+                idx = next;
+                RData::HINFO { cpu, os }
+            }
+            QType::MINFO => {
+                let (rmailbx, next) = Name::from_bytes(buf, idx)?;
+                let (emailbx, next) = Name::from_bytes(buf, next)?;
+                idx = next;
+                RData::MINFO { rmailbx, emailbx }
+            }
+            QType::MX => {
+                let preference = read_u16(buf, idx)?;
+                let (exchange, next) = Name::from_bytes(buf, idx + 2)?;
+                idx = next;
+                RData::MX {
+                    preference,
+                    exchange,
+                }
+            }
+            QType::TXT => {
+                let mut strings = Vec::new();
+                while idx < end {
+                    let (string, next) = character_string_from_bytes(buf, idx)?;
+                    strings.push(string);
+                    idx = next;
+                }
+                RData::TXT(strings)
+            }
+            QType::RP => {
+                let (mbox, next) = Name::from_bytes(buf, idx)?;
+                let (txt, next) = Name::from_bytes(buf, next)?;
+                idx = next;
+                RData::RP { mbox, txt }
+            }
+            QType::AFSDB => {
+                let subtype = read_u16(buf, idx)?;
+                let (hostname, next) = Name::from_bytes(buf, idx + 2)?;
+                idx = next;
+                RData::AFSDB { subtype, hostname }
+            }
+            QType::AAAA if class == Class::IN => {
+                let octets: [u8; 16] = take(buf, idx, 16)?.try_into().unwrap();
+                idx += 16;
+                RData::AAAA(Ipv6Addr::from(octets))
+            }
+            // other versions have a layout we cannot know
+            QType::LOC if data.first() == Some(&0) && data.len() == 16 => {
+                idx = end;
+                RData::LOC {
+                    size: data[1],
+                    horiz_pre: data[2],
+                    vert_pre: data[3],
+                    latitude: read_u32(data, 4)?,
+                    longitude: read_u32(data, 8)?,
+                    altitude: read_u32(data, 12)?,
+                }
+            }
+            QType::SRV => {
+                let priority = read_u16(buf, idx)?;
+                let weight = read_u16(buf, idx + 2)?;
+                let port = read_u16(buf, idx + 4)?;
+                let (target, next) = Name::from_bytes(buf, idx + 6)?;
+                idx = next;
+                RData::SRV {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                }
+            }
+            QType::NAPTR => {
+                let order = read_u16(buf, idx)?;
+                let preference = read_u16(buf, idx + 2)?;
+                let (flags, next) = character_string_from_bytes(buf, idx + 4)?;
+                let (services, next) = character_string_from_bytes(buf, next)?;
+                let (regexp, next) = character_string_from_bytes(buf, next)?;
+                let (replacement, next) = Name::from_bytes(buf, next)?;
+                idx = next;
+                RData::NAPTR {
+                    order,
+                    preference,
+                    flags,
+                    services,
+                    regexp,
+                    replacement,
+                }
+            }
+            QType::DNAME => {
+                let (name, next) = Name::from_bytes(buf, idx)?;
+                idx = next;
+                RData::DNAME(name)
+            }
+            QType::SSHFP => {
+                let header = take(buf, idx, 2)?;
+                idx = end;
+                RData::SSHFP {
+                    algorithm: header[0],
+                    fp_type: header[1],
+                    fingerprint: data[2..].to_vec(),
+                }
+            }
+            QType::TLSA => {
+                let header = take(buf, idx, 3)?;
+                idx = end;
+                RData::TLSA {
+                    usage: header[0],
+                    selector: header[1],
+                    matching_type: header[2],
+                    data: data[3..].to_vec(),
+                }
+            }
+            QType::URI => {
+                let priority = read_u16(buf, idx)?;
+                let weight = read_u16(buf, idx + 2)?;
+                idx = end;
+                RData::URI {
+                    priority,
+                    weight,
+                    target: data[4..].to_vec(),
+                }
+            }
+            QType::CAA => {
+                let flags = take(buf, idx, 1)?[0];
+                let (tag, next) = character_string_from_bytes(buf, idx + 1)?;
+                idx = end;
+                RData::CAA {
+                    flags,
+                    tag,
+                    value: buf[next..].to_vec(),
+                }
+            }
+            QType::SVCB | QType::HTTPS => {
+                let (svcb, next) = Svcb::from_bytes(buf, idx)?;
+                idx = next;
+                if qtype == QType::SVCB {
+                    RData::SVCB(svcb)
+                } else {
+                    RData::HTTPS(svcb)
+                }
+            }
+            QType::DS | QType::CDS => {
+                let ds = Ds::from_bytes(buf, idx)?;
+                idx = end;
+                if qtype == QType::DS {
+                    RData::DS(ds)
+                } else {
+                    RData::CDS(ds)
+                }
+            }
+            QType::RRSIG => {
+                let rrsig = Rrsig::from_bytes(buf, idx)?;
+                idx = end;
+                RData::RRSIG(rrsig)
+            }
+            QType::NSEC => {
+                let nsec = Nsec::from_bytes(buf, idx)?;
+                idx = end;
+                RData::NSEC(nsec)
+            }
+            QType::DNSKEY | QType::CDNSKEY => {
+                let dnskey = Dnskey::from_bytes(buf, idx)?;
+                idx = end;
+                if qtype == QType::DNSKEY {
+                    RData::DNSKEY(dnskey)
+                } else {
+                    RData::CDNSKEY(dnskey)
+                }
+            }
+            QType::NSEC3 => {
+                let nsec3 = Nsec3::from_bytes(buf, idx)?;
+                idx = end;
+                RData::NSEC3(nsec3)
+            }
+            QType::NSEC3PARAM => {
+                let (param, next) = Nsec3Param::from_bytes(buf, idx)?;
+                idx = next;
+                RData::NSEC3PARAM(param)
+            }
+            _ => {
+                idx = end;
+                RData::Opaque(qtype, data.to_vec())
+            }
+        };
+
+        if idx != end {
+            return Err(DecodeError::BadRdata(start_pos));
+        }
+
+        Ok(rdata)
+    }
+}
+
+impl RData {
+    // Parses presentation format. Relative names are taken as fully qualified.
+    pub fn parse(qtype: QType, s: &str) -> Result<RData, ParseError> {
+        RData::from_tokens(qtype, &tokenize(s)?, &Name::root())
+    }
+
+    // Builds the rdata from its presentation fields, with relative names
+    // completed from `origin`. Every type also accepts the RFC 3597 generic
+    // `\# <length> <hex>` form.
+    pub fn from_tokens(
+        qtype: QType,
+        tokens: &[String],
+        origin: &Name,
+    ) -> Result<RData, ParseError> {
+        let wrong_count = || ParseError::FieldCount(qtype.to_string());
+        let fields = |count: usize| {
+            if tokens.len() == count {
+                Ok(tokens)
+            } else {
+                Err(wrong_count())
+            }
+        };
+
+        if tokens.first().map(String::as_str) == Some("\\#") {
+            return generic_from_tokens(qtype, &tokens[1..]);
+        }
+
+        let rdata = match qtype {
+            QType::A => RData::A(parse_number(&fields(1)?[0])?),
+            QType::NS
+            | QType::MD
+            | QType::MF
+            | QType::CNAME
+            | QType::MB
+            | QType::MG
+            | QType::MR
+            | QType::PTR
+            | QType::DNAME => {
+                let name = parse_name(&fields(1)?[0], origin)?;
+                match qtype {
+                    QType::NS => RData::NS(name),
+                    QType::MD => RData::MD(name),
+                    QType::MF => RData::MF(name),
+                    QType::CNAME => RData::CNAME(name),
+                    QType::MB => RData::MB(name),
+                    QType::MG => RData::MG(name),
+                    QType::MR => RData::MR(name),
+                    QType::DNAME => RData::DNAME(name),
+                    _ => RData::PTR(name),
+                }
+            }
+            QType::SOA => {
+                let fields = fields(7)?;
+                RData::SOA {
+                    mname: parse_name(&fields[0], origin)?,
+                    rname: parse_name(&fields[1], origin)?,
+                    serial: parse_number(&fields[2])?,
+                    refresh: parse_ttl(&fields[3])?,
+                    retry: parse_ttl(&fields[4])?,
+                    expire: parse_ttl(&fields[5])?,
+                    minimum: parse_ttl(&fields[6])?,
+                }
+            }
+            QType::WKS => {
+                if tokens.len() < 2 {
+                    return Err(wrong_count());
+                }
+                let protocol = match tokens[1].to_ascii_lowercase().as_str() {
+                    "tcp" => 6,
+                    "udp" => 17,
+                    other => parse_number(other)?,
+                };
+                let mut bitmap: Vec<u8> = Vec::new();
+                for token in &tokens[2..] {
+                    let port: u16 = parse_number(token)?;
+                    let byte = port as usize / 8;
+                    if bitmap.len() <= byte {
+                        bitmap.resize(byte + 1, 0);
+                    }
+                    bitmap[byte] |= 0x80 >> (port % 8);
+                }
+                RData::WKS {
+                    address: parse_number(&tokens[0])?,
+                    protocol,
+                    bitmap,
+                }
+            }
+            QType::HINFO => {
+                let fields = fields(2)?;
+                RData::HINFO {
+                    cpu: parse_character_string(&fields[0])?,
+                    os: parse_character_string(&fields[1])?,
+                }
+            }
+            QType::MINFO => {
+                let fields = fields(2)?;
+                RData::MINFO {
+                    rmailbx: parse_name(&fields[0], origin)?,
+                    emailbx: parse_name(&fields[1], origin)?,
+                }
+            }
+            QType::MX => {
+                let fields = fields(2)?;
+                RData::MX {
+                    preference: parse_number(&fields[0])?,
+                    exchange: parse_name(&fields[1], origin)?,
+                }
+            }
+            QType::TXT => {
+                if tokens.is_empty() {
+                    return Err(wrong_count());
+                }
+                RData::TXT(
+                    tokens
+                        .iter()
+                        .map(|token| parse_character_string(token))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            QType::RP => {
+                let fields = fields(2)?;
+                RData::RP {
+                    mbox: parse_name(&fields[0], origin)?,
+                    txt: parse_name(&fields[1], origin)?,
+                }
+            }
+            QType::AFSDB => {
+                let fields = fields(2)?;
+                RData::AFSDB {
+                    subtype: parse_number(&fields[0])?,
+                    hostname: parse_name(&fields[1], origin)?,
+                }
+            }
+            QType::AAAA => RData::AAAA(parse_number(&fields(1)?[0])?),
+            QType::LOC => loc_from_tokens(tokens)?,
+            QType::SRV => {
+                let fields = fields(4)?;
+                RData::SRV {
+                    priority: parse_number(&fields[0])?,
+                    weight: parse_number(&fields[1])?,
+                    port: parse_number(&fields[2])?,
+                    target: parse_name(&fields[3], origin)?,
+                }
+            }
+            QType::NAPTR => {
+                let fields = fields(6)?;
+                RData::NAPTR {
+                    order: parse_number(&fields[0])?,
+                    preference: parse_number(&fields[1])?,
+                    flags: parse_character_string(&fields[2])?,
+                    services: parse_character_string(&fields[3])?,
+                    regexp: parse_character_string(&fields[4])?,
+                    replacement: parse_name(&fields[5], origin)?,
+                }
+            }
+            QType::SSHFP => {
+                if tokens.len() < 3 {
+                    return Err(wrong_count());
+                }
+                RData::SSHFP {
+                    algorithm: parse_number(&tokens[0])?,
+                    fp_type: parse_number(&tokens[1])?,
+                    fingerprint: from_hex(&tokens[2..].concat())?,
+                }
+            }
+            QType::TLSA => {
+                if tokens.len() < 4 {
+                    return Err(wrong_count());
+                }
+                RData::TLSA {
+                    usage: parse_number(&tokens[0])?,
+                    selector: parse_number(&tokens[1])?,
+                    matching_type: parse_number(&tokens[2])?,
+                    data: from_hex(&tokens[3..].concat())?,
+                }
+            }
+            QType::URI => {
+                let fields = fields(3)?;
+                RData::URI {
+                    priority: parse_number(&fields[0])?,
+                    weight: parse_number(&fields[1])?,
+                    target: unescape(unquote(&fields[2])?)?,
+                }
+            }
+            QType::CAA => {
+                let fields = fields(3)?;
+                let tag = fields[1].as_bytes().to_vec();
+                if tag.is_empty() || tag.len() > 255 || !tag.iter().all(u8::is_ascii_alphanumeric) {
+                    return Err(ParseError::InvalidValue(fields[1].clone()));
+                }
+                RData::CAA {
+                    flags: parse_number(&fields[0])?,
+                    tag,
+                    value: unescape(unquote(&fields[2])?)?,
+                }
+            }
+            QType::SVCB => RData::SVCB(Svcb::from_tokens(tokens, origin)?),
+            QType::HTTPS => RData::HTTPS(Svcb::from_tokens(tokens, origin)?),
+            QType::DS => RData::DS(Ds::from_tokens(tokens)?),
+            QType::CDS => RData::CDS(Ds::from_tokens(tokens)?),
+            QType::RRSIG => RData::RRSIG(Rrsig::from_tokens(tokens, origin)?),
+            QType::NSEC => RData::NSEC(Nsec::from_tokens(tokens, origin)?),
+            QType::DNSKEY => RData::DNSKEY(Dnskey::from_tokens(tokens)?),
+            QType::CDNSKEY => RData::CDNSKEY(Dnskey::from_tokens(tokens)?),
+            QType::NSEC3 => RData::NSEC3(Nsec3::from_tokens(tokens)?),
+            QType::NSEC3PARAM => RData::NSEC3PARAM(Nsec3Param::from_tokens(tokens)?),
+            // the remaining types only have the generic form
+            _ => return Err(ParseError::InvalidValue(tokens.join(" "))),
+        };
+
+        Ok(rdata)
+    }
+}
+
+// `<length> <hex>...` following the `\#` of the RFC 3597 generic form. Known
+// types are decoded so they come out typed.
+fn generic_from_tokens(qtype: QType, tokens: &[String]) -> Result<RData, ParseError> {
+    let length: u16 = parse_number(
+        tokens
+            .first()
+            .ok_or_else(|| ParseError::FieldCount(qtype.to_string()))?,
+    )?;
+    let data = from_hex(&tokens[1..].concat())?;
+
+    if data.len() != length as usize {
+        return Err(ParseError::InvalidValue(tokens.join(" ")));
+    }
+
+    RData::from_bytes(&data, 0, length, qtype, Class::IN)
+        .map_err(|_| ParseError::InvalidValue(tokens.join(" ")))
+}
+
+// `d1 [m1 [s1]] {N|S} d2 [m2 [s2]] {E|W} alt[m] [siz[m] [hp[m] [vp[m]]]]`
+fn loc_from_tokens(tokens: &[String]) -> Result<RData, ParseError> {
+    let wrong_count = || ParseError::FieldCount(QType::LOC.to_string());
+    let mut tokens = tokens.iter().peekable();
+
+    let mut coordinate = |hemispheres: [&str; 2], max_degrees: u32| -> Result<u32, ParseError> {
+        let degrees: u32 = parse_number(tokens.next().ok_or_else(wrong_count)?)?;
+        let mut minutes: u32 = 0;
+        let mut thousandths = 0;
+        let mut hemisphere = tokens.next().ok_or_else(wrong_count)?;
+        if !hemispheres
+            .iter()
+            .any(|h| hemisphere.eq_ignore_ascii_case(h))
+        {
+            minutes = parse_number(hemisphere)?;
+            hemisphere = tokens.next().ok_or_else(wrong_count)?;
+            if !hemispheres
+                .iter()
+                .any(|h| hemisphere.eq_ignore_ascii_case(h))
+            {
+                thousandths = parse_decimal(hemisphere, 3)?;
+                hemisphere = tokens.next().ok_or_else(wrong_count)?;
+            }
+        }
+
+        let invalid = || ParseError::InvalidValue(degrees.to_string());
+        if minutes >= 60 || !(0..60_000).contains(&thousandths) {
+            return Err(invalid());
+        }
+        let value = degrees
+            .checked_mul(3_600_000)
+            .and_then(|value| value.checked_add(minutes * 60_000 + thousandths as u32))
+            .filter(|&value| value <= max_degrees * 3_600_000)
+            .ok_or_else(invalid)?;
+
+        if hemisphere.eq_ignore_ascii_case(hemispheres[0]) {
+            Ok(LOC_EQUATOR + value)
+        } else if hemisphere.eq_ignore_ascii_case(hemispheres[1]) {
+            Ok(LOC_EQUATOR - value)
+        } else {
+            Err(ParseError::InvalidValue(hemisphere.clone()))
+        }
+    };
+
+    let latitude = coordinate(["N", "S"], 90)?;
+    let longitude = coordinate(["E", "W"], 180)?;
+
+    let altitude_token = tokens.next().ok_or_else(wrong_count)?;
+    let altitude =
+        parse_decimal(altitude_token.trim_end_matches(['m', 'M']), 2)? + LOC_ALTITUDE_BASE;
+    let altitude =
+        u32::try_from(altitude).map_err(|_| ParseError::InvalidValue(altitude_token.clone()))?;
+
+    // defaults from RFC 1876: 1m size, 10km horizontal and 10m vertical
+    let mut precisions = [0x12, 0x16, 0x13];
+    for precision in precisions.iter_mut() {
+        let Some(token) = tokens.next() else {
+            break;
+        };
+        let centimetres = parse_decimal(token.trim_end_matches(['m', 'M']), 2)?;
+        *precision = precision_from_centimetres(centimetres)
+            .ok_or_else(|| ParseError::InvalidValue(token.clone()))?;
+    }
+
+    if tokens.next().is_some() {
+        return Err(wrong_count());
+    }
+
+    let [size, horiz_pre, vert_pre] = precisions;
+    Ok(RData::LOC {
+        size,
+        horiz_pre,
+        vert_pre,
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+// Fixed point decimal, e.g. "-2.5" with 2 digits is -250
+fn parse_decimal(token: &str, digits: u32) -> Result<i64, ParseError> {
+    let invalid = || ParseError::InvalidValue(token.to_string());
+    let (negative, unsigned) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    if whole.is_empty()
+        || fraction.len() > digits as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: i64 = whole.parse().map_err(|_| invalid())?;
+    let fraction: i64 = format!("{:0<width$}", fraction, width = digits as usize)
+        .parse()
+        .unwrap_or(0);
+    let value = whole
+        .checked_mul(10i64.pow(digits))
+        .and_then(|v| v.checked_add(fraction))
+        .ok_or_else(invalid)?;
+
+    Ok(if negative { -value } else { value })
+}
+
+// Packs centimetres into the LOC mantissa/exponent byte, rounding down
+fn precision_from_centimetres(centimetres: i64) -> Option<u8> {
+    if centimetres < 0 {
+        return None;
+    }
+    let mut mantissa = centimetres;
+    let mut exponent = 0;
+    while mantissa >= 10 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    (exponent <= 9).then_some(((mantissa as u8) << 4) | exponent)
+}
+
+// Returns the <character-string> at `start_pos` and the position after it
+pub fn character_string_from_bytes(
+    buf: &[u8],
+    start_pos: usize,
+) -> Result<(Vec<u8>, usize), DecodeError> {
+    let length = take(buf, start_pos, 1)?[0] as usize;
+    let string = take(buf, start_pos + 1, length)?.to_vec();
+
+    Ok((string, start_pos + 1 + length))
+}
+
+// Quoted <character-string> with `"` and `\` escaped and non-printable
+// octets written as `\DDD`
+pub fn fmt_character_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+            0x20..=0x7E => write!(f, "{}", byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// RFC 3597 5 generic encoding: `\# <length> <hex>`
+pub fn fmt_generic(f: &mut fmt::Formatter<'_>, data: &[u8]) -> fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        write!(f, " {}", to_hex(data))?;
+    }
+    Ok(())
+}
+
+// RFC 1876 coordinate: degrees, minutes, seconds and hemisphere
+fn fmt_coordinate(f: &mut fmt::Formatter<'_>, value: u32, hemispheres: [char; 2]) -> fmt::Result {
+    let offset = value as i64 - LOC_EQUATOR as i64;
+    let hemisphere = if offset < 0 {
+        hemispheres[1]
+    } else {
+        hemispheres[0]
+    };
+    let thousandths = offset.abs();
+
+    write!(
+        f,
+        "{} {} {}.{:03} {}",
+        thousandths / 3_600_000,
+        thousandths % 3_600_000 / 60_000,
+        thousandths % 60_000 / 1000,
+        thousandths % 1000,
+        hemisphere
+    )
+}
+
+// RFC 1876 size and precision, stored as mantissa and power of ten in cm
+fn fmt_precision(f: &mut fmt::Formatter<'_>, value: u8) -> fmt::Result {
+    let centimetres = (value >> 4) as u64 * 10u64.pow((value & 0x0F) as u32);
+    let (metres, rest) = (centimetres / 100, centimetres % 100);
+    if rest == 0 {
+        write!(f, "{}m", metres)
+    } else {
+        write!(f, "{}.{:02}m", metres, rest)
+    }
+}
+
+// `2^31` is the equator and the prime meridian in LOC coordinates
+const LOC_EQUATOR: u32 = 1 << 31;
+// LOC altitudes are counted from 100 000 m below the reference spheroid
+const LOC_ALTITUDE_BASE: i64 = 10_000_000;
+
+// Presentation format of the RDATA as it appears in master files, with every
+// name fully qualified
+impl fmt::Display for RData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RData::A(address) => write!(f, "{}", address),
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CNAME(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => write!(f, "{:#}", name),
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{:#} {:#} {} {} {} {} {}",
+                mname, rname, serial, refresh, retry, expire, minimum
+            ),
+            RData::NULL(data) | RData::Opaque(_, data) => fmt_generic(f, data),
+            RData::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                write!(f, "{} {}", address, protocol)?;
+                // bit N of the map (from the most significant bit) is port N
+                for (i, byte) in bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            RData::HINFO { cpu, os } => {
+                fmt_character_string(f, cpu)?;
+                write!(f, " ")?;
+                fmt_character_string(f, os)
+            }
+            RData::MINFO { rmailbx, emailbx } => write!(f, "{:#} {:#}", rmailbx, emailbx),
+            RData::MX {
+                preference,
+                exchange,
+            } => write!(f, "{} {:#}", preference, exchange),
+            RData::TXT(strings) => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    fmt_character_string(f, string)?;
+                }
+                Ok(())
+            }
+            RData::RP { mbox, txt } => write!(f, "{:#} {:#}", mbox, txt),
+            RData::AFSDB { subtype, hostname } => write!(f, "{} {:#}", subtype, hostname),
+            RData::AAAA(address) => write!(f, "{}", address),
+            RData::LOC {
+                size,
+                horiz_pre,
+                vert_pre,
+                latitude,
+                longitude,
+                altitude,
+            } => {
+                fmt_coordinate(f, *latitude, ['N', 'S'])?;
+                write!(f, " ")?;
+                fmt_coordinate(f, *longitude, ['E', 'W'])?;
+                let centimetres = *altitude as i64 - LOC_ALTITUDE_BASE;
+                write!(
+                    f,
+                    " {}{}.{:02}m",
+                    if centimetres < 0 { "-" } else { "" },
+                    centimetres.abs() / 100,
+                    centimetres.abs() % 100
+                )?;
+                for precision in [size, horiz_pre, vert_pre] {
+                    write!(f, " ")?;
+                    fmt_precision(f, *precision)?;
+                }
+                Ok(())
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{} {} {} {:#}", priority, weight, port, target),
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                write!(f, "{} {} ", order, preference)?;
+                for string in [flags, services, regexp] {
+                    fmt_character_string(f, string)?;
+                    write!(f, " ")?;
+                }
+                write!(f, "{:#}", replacement)
+            }
+            RData::DNAME(name) => write!(f, "{:#}", name),
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => write!(f, "{} {} {}", algorithm, fp_type, to_hex(fingerprint)),
+            RData::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                usage,
+                selector,
+                matching_type,
+                to_hex(data)
+            ),
+            RData::URI {
+                priority,
+                weight,
+                target,
+            } => {
+                write!(f, "{} {} ", priority, weight)?;
+                fmt_character_string(f, target)
+            }
+            RData::CAA { flags, tag, value } => {
+                write!(f, "{} {} ", flags, String::from_utf8_lossy(tag))?;
+                fmt_character_string(f, value)
+            }
+            RData::SVCB(svcb) | RData::HTTPS(svcb) => write!(f, "{}", svcb),
+            RData::DS(ds) | RData::CDS(ds) => write!(f, "{}", ds),
+            RData::RRSIG(rrsig) => write!(f, "{}", rrsig),
+            RData::NSEC(nsec) => write!(f, "{}", nsec),
+            RData::DNSKEY(dnskey) | RData::CDNSKEY(dnskey) => write!(f, "{}", dnskey),
+            RData::NSEC3(nsec3) => write!(f, "{}", nsec3),
+            RData::NSEC3PARAM(param) => write!(f, "{}", param),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{presentation::tokenize, svcb::SvcParam};
+
+    fn name(name: &str) -> Name {
+        name.parse().unwrap()
+    }
+
+    fn round_trip(rdata: RData) {
+        let bytes = rdata.to_bytes().unwrap();
+        let decoded =
+            RData::from_bytes(&bytes, 0, bytes.len() as u16, rdata.qtype(), Class::IN).unwrap();
+        assert_eq!(decoded, rdata);
+    }
+
+    #[test]
+    fn test_rdata_round_trip() {
+        round_trip(RData::A(Ipv4Addr::new(8, 8, 8, 8)));
+        round_trip(RData::NS(name("ns1.example.com")));
+        round_trip(RData::CNAME(name("www.example.com")));
+        round_trip(RData::PTR(name("host.example.com")));
+        round_trip(RData::MB(name("mb.example.com")));
+        round_trip(RData::SOA {
+            mname: name("ns1.example.com"),
+            rname: name("hostmaster.example.com"),
+            serial: 2024010101,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 300,
+        });
+        round_trip(RData::MX {
+            preference: 10,
+            exchange: name("mail.example.com"),
+        });
+        round_trip(RData::TXT(vec![b"v=spf1 -all".to_vec(), vec![]]));
+        round_trip(RData::HINFO {
+            cpu: b"x86_64".to_vec(),
+            os: b"Linux".to_vec(),
+        });
+        round_trip(RData::MINFO {
+            rmailbx: name("admin.example.com"),
+            emailbx: name("errors.example.com"),
+        });
+        round_trip(RData::WKS {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            protocol: 6,
+            bitmap: vec![0, 0, 0, 0x40],
+        });
+        round_trip(RData::NULL(vec![1, 2, 3]));
+        round_trip(RData::Opaque(QType::from_u16(4660), vec![0xde, 0xad]));
+    }
+
+    #[test]
+    fn test_compressed_rdata_from_bytes() {
+        let bytes = vec![
+            3, 99, 111, 109, 0, // "com"
+            0, 10, // preference
+            2, 109, 120, 192, 0, // "mx" + pointer to "com"
+        ];
+        let rdata = RData::from_bytes(&bytes, 5, 7, QType::MX, Class::IN).unwrap();
+        assert_eq!(
+            rdata,
+            RData::MX {
+                preference: 10,
+                exchange: name("mx.com"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_rdata_from_bytes() {
+        // A record with 5 bytes of rdata
+        assert_eq!(
+            RData::from_bytes(&[1, 2, 3, 4, 5], 0, 5, QType::A, Class::IN),
+            Err(DecodeError::BadRdata(0))
+        );
+
+        // name running past the rdata into the next record
+        let bytes = vec![3, 99, 111, 109, 0];
+        assert!(matches!(
+            RData::from_bytes(&bytes, 0, 3, QType::CNAME, Class::IN),
+            Err(DecodeError::Truncated { .. })
+        ));
+
+        // TXT string longer than the rdata
+        assert!(matches!(
+            RData::from_bytes(&[5, 97, 98], 0, 3, QType::TXT, Class::IN),
+            Err(DecodeError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn test_class_specific_rdata_outside_in() {
+        // Chaosnet A records are not IPv4 addresses
+        let bytes = vec![3, 99, 111, 109, 0, 0, 1];
+        let rdata = RData::from_bytes(&bytes, 0, 7, QType::A, Class::CH).unwrap();
+        assert_eq!(rdata, RData::Opaque(QType::A, bytes));
+    }
+
+    #[test]
+    fn test_rdata_presentation() {
+        assert_eq!(RData::A(Ipv4Addr::new(8, 8, 4, 4)).to_string(), "8.8.4.4");
+        assert_eq!(
+            RData::MX {
+                preference: 10,
+                exchange: name("mail.example.com"),
+            }
+            .to_string(),
+            "10 mail.example.com."
+        );
+        assert_eq!(
+            RData::TXT(vec![b"say \"hi\"".to_vec(), vec![0x07]]).to_string(),
+            "\"say \\\"hi\\\"\" \"\\007\""
+        );
+        assert_eq!(
+            RData::WKS {
+                address: Ipv4Addr::new(10, 0, 0, 1),
+                protocol: 6,
+                bitmap: vec![0, 0, 0, 0x40],
+            }
+            .to_string(),
+            "10.0.0.1 6 25"
+        );
+        assert_eq!(
+            RData::Opaque(QType::from_u16(4660), vec![0xde, 0xad]).to_string(),
+            "\\# 2 DEAD"
+        );
+        assert_eq!(RData::NULL(vec![]).to_string(), "\\# 0");
+    }
+
+    fn modern_rdata() -> Vec<RData> {
+        vec![
+            RData::AAAA("2001:db8::1".parse().unwrap()),
+            RData::SRV {
+                priority: 10,
+                weight: 60,
+                port: 5060,
+                target: name("sip.example.com"),
+            },
+            RData::CAA {
+                flags: 128,
+                tag: b"issue".to_vec(),
+                value: b"letsencrypt.org; validationmethods=dns-01".to_vec(),
+            },
+            RData::NAPTR {
+                order: 100,
+                preference: 10,
+                flags: b"S".to_vec(),
+                services: b"SIP+D2U".to_vec(),
+                regexp: b"!^.*$!sip:info@example.com!".to_vec(),
+                replacement: name("_sip._udp.example.com"),
+            },
+            RData::DNAME(name("example.net")),
+            RData::URI {
+                priority: 10,
+                weight: 1,
+                target: b"ftp://ftp1.example.com/public".to_vec(),
+            },
+            RData::SSHFP {
+                algorithm: 4,
+                fp_type: 2,
+                fingerprint: vec![0x12; 32],
+            },
+            RData::TLSA {
+                usage: 3,
+                selector: 1,
+                matching_type: 1,
+                data: vec![0xab; 32],
+            },
+            RData::LOC {
+                size: 0x12,
+                horiz_pre: 0x16,
+                vert_pre: 0x13,
+                latitude: 0x8b3cf018,
+                longitude: 0x810cbce0,
+                altitude: 0x0098957c,
+            },
+            RData::RP {
+                mbox: name("admin.example.com"),
+                txt: name("info.example.com"),
+            },
+            RData::AFSDB {
+                subtype: 1,
+                hostname: name("afs.example.com"),
+            },
+            RData::HTTPS(Svcb {
+                priority: 1,
+                target: Name::root(),
+                params: vec![
+                    SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+                    SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+                ],
+            }),
+            RData::DS(Ds {
+                key_tag: 60485,
+                algorithm: 5,
+                digest_type: 1,
+                digest: vec![0x2b; 20],
+            }),
+            RData::CDS(Ds {
+                key_tag: 0,
+                algorithm: 0,
+                digest_type: 0,
+                digest: vec![0],
+            }),
+            RData::RRSIG(Rrsig {
+                type_covered: QType::A,
+                algorithm: 13,
+                labels: 3,
+                original_ttl: 86400,
+                expiration: 1048354263,
+                inception: 1045762263,
+                key_tag: 2642,
+                signer: name("example.com"),
+                signature: b"signature".to_vec(),
+            }),
+            RData::NSEC(Nsec {
+                next: name("host.example.com"),
+                types: vec![QType::A, QType::MX, QType::RRSIG, QType::NSEC],
+            }),
+            RData::DNSKEY(Dnskey {
+                flags: 257,
+                protocol: 3,
+                algorithm: 15,
+                public_key: b"public key".to_vec(),
+            }),
+            RData::CDNSKEY(Dnskey {
+                flags: 0,
+                protocol: 3,
+                algorithm: 0,
+                public_key: vec![0],
+            }),
+            RData::NSEC3(Nsec3 {
+                hash_algorithm: 1,
+                flags: 1,
+                iterations: 0,
+                salt: Vec::new(),
+                next_hashed: b"hashed owner".to_vec(),
+                types: Vec::new(),
+            }),
+            RData::NSEC3PARAM(Nsec3Param {
+                hash_algorithm: 1,
+                flags: 0,
+                iterations: 12,
+                salt: vec![0xaa, 0xbb, 0xcc, 0xdd],
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_modern_rdata_round_trip() {
+        for rdata in modern_rdata() {
+            round_trip(rdata);
+        }
+    }
+
+    #[test]
+    fn test_modern_rdata_is_not_compressed() {
+        for rdata in modern_rdata() {
+            let mut encoder = Encoder::new();
+            encoder.put_name(&name("example.com"), true);
+            rdata.encode(&mut encoder).unwrap();
+            assert_eq!(encoder.finish()[13..], rdata.to_bytes().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_modern_rdata_presentation() {
+        let presentation: Vec<String> = modern_rdata().iter().map(RData::to_string).collect();
+        assert_eq!(
+            presentation,
+            vec![
+                "2001:db8::1",
+                "10 60 5060 sip.example.com.",
+                "128 issue \"letsencrypt.org; validationmethods=dns-01\"",
+                "100 10 \"S\" \"SIP+D2U\" \"!^.*$!sip:info@example.com!\" _sip._udp.example.com.",
+                "example.net.",
+                "10 1 \"ftp://ftp1.example.com/public\"",
+                &format!("4 2 {}", "12".repeat(32)),
+                &format!("3 1 1 {}", "AB".repeat(32)),
+                "52 22 23.000 N 4 53 32.000 E -2.60m 1m 10000m 10m",
+                "admin.example.com. info.example.com.",
+                "1 afs.example.com.",
+                "1 . alpn=\"h2,h3\" ipv4hint=192.0.2.1",
+                &format!("60485 5 1 {}", "2B".repeat(20)),
+                "0 0 0 00",
+                "A 13 3 86400 20030322173103 20030220173103 2642 example.com. c2lnbmF0dXJl",
+                "host.example.com. A MX RRSIG NSEC",
+                "257 3 15 cHVibGljIGtleQ==",
+                "0 3 0 AA==",
+                "1 1 0 - D1GN6Q35CGG6UTRECLP0",
+                "1 0 12 AABBCCDD",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rdata_presentation_round_trip() {
+        let mut all = modern_rdata();
+        all.extend([
+            RData::A(Ipv4Addr::new(8, 8, 8, 8)),
+            RData::NS(name("ns1.example.com")),
+            RData::SOA {
+                mname: name("ns1.example.com"),
+                rname: name("hostmaster.example.com"),
+                serial: 2024010101,
+                refresh: 7200,
+                retry: 3600,
+                expire: 1209600,
+                minimum: 300,
+            },
+            RData::MX {
+                preference: 10,
+                exchange: name("mail.example.com"),
+            },
+            RData::TXT(vec![b"say \"hi\"".to_vec(), vec![0, 255]]),
+            RData::HINFO {
+                cpu: b"x86 64".to_vec(),
+                os: b"Linux".to_vec(),
+            },
+            RData::MINFO {
+                rmailbx: name("admin.example.com"),
+                emailbx: name("errors.example.com"),
+            },
+            RData::WKS {
+                address: Ipv4Addr::new(10, 0, 0, 1),
+                protocol: 6,
+                bitmap: vec![0, 0, 0, 0x40],
+            },
+            RData::NULL(vec![1, 2, 3]),
+            RData::Opaque(QType::from_u16(4660), vec![0xde, 0xad]),
+            RData::Opaque(QType::from_u16(4660), vec![]),
+        ]);
+
+        for rdata in all {
+            let parsed = RData::parse(rdata.qtype(), &rdata.to_string()).unwrap();
+            assert_eq!(parsed, rdata, "{}", rdata);
+        }
+    }
+
+    #[test]
+    fn test_rdata_from_tokens() {
+        let origin = name("example.com");
+        let tokens = |s: &str| tokenize(s).unwrap();
+
+        assert_eq!(
+            RData::from_tokens(QType::MX, &tokens("10 mail"), &origin).unwrap(),
+            RData::MX {
+                preference: 10,
+                exchange: name("mail.example.com"),
+            }
+        );
+        assert_eq!(
+            RData::from_tokens(QType::SOA, &tokens("@ hostmaster 1 1h 15m 1w 5m"), &origin)
+                .unwrap()
+                .to_string(),
+            "example.com. hostmaster.example.com. 1 3600 900 604800 300"
+        );
+        // generic syntax for a known type comes out typed
+        assert_eq!(
+            RData::from_tokens(QType::A, &tokens("\\# 4 0A000001"), &origin).unwrap(),
+            RData::A(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            RData::from_tokens(QType::TXT, &tokens("unquoted \"two words\""), &origin).unwrap(),
+            RData::TXT(vec![b"unquoted".to_vec(), b"two words".to_vec()])
+        );
+        assert_eq!(
+            RData::from_tokens(
+                QType::LOC,
+                &tokens("42 21 54 N 71 06 18 W -24m 30m"),
+                &origin
+            )
+            .unwrap()
+            .to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rdata_presentation() {
+        assert_eq!(
+            RData::parse(QType::MX, "10"),
+            Err(ParseError::FieldCount("MX".to_string()))
+        );
+        assert!(RData::parse(QType::A, "10.0.0.256").is_err());
+        assert!(RData::parse(QType::A, "\\# 3 0A0000").is_err());
+        assert!(RData::parse(QType::A, "\\# 4 0A0000").is_err());
+        assert!(RData::parse(QType::CAA, "0 is-sue \"x\"").is_err());
+        assert!(RData::parse(QType::LOC, "91 N 0 E 0m").is_err());
+        assert!(RData::parse(QType::LOC, "-52 22 23.000 N 4 53 32.000 E -2.00m").is_err());
+        assert!(RData::parse(QType::LOC, "52 -22 N 4 E 0m").is_err());
+        assert!(RData::parse(QType::LOC, "4294967295 N 0 E 0m").is_err());
+        assert!(RData::parse(QType::from_u16(4660), "dead").is_err());
+    }
+}
@@ -0,0 +1,903 @@
+// Iterative resolution (RFC 1034 5.3.3): start at the root servers and follow
+// referrals down to the servers of the zone holding the answer.
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use rand::seq::SliceRandom;
+
+use crate::{
+    edns::Edns,
+    error::{ParseError, ResolveError, UpstreamError},
+    field::{Class, QType},
+    header::Header,
+    name::Name,
+    packet::Packet,
+    question::Question,
+    rdata::RData,
+    resource_records::ResourceRecord,
+    upstream::{Upstream, Upstreams, UDP_PAYLOAD_SIZE},
+};
+
+// a.root-servers.net to m.root-servers.net
+const ROOT_HINTS: [Ipv4Addr; 13] = [
+    Ipv4Addr::new(198, 41, 0, 4),
+    Ipv4Addr::new(170, 247, 170, 2),
+    Ipv4Addr::new(192, 33, 4, 12),
+    Ipv4Addr::new(199, 7, 91, 13),
+    Ipv4Addr::new(192, 203, 230, 10),
+    Ipv4Addr::new(192, 5, 5, 241),
+    Ipv4Addr::new(192, 112, 36, 4),
+    Ipv4Addr::new(198, 97, 190, 53),
+    Ipv4Addr::new(192, 36, 148, 17),
+    Ipv4Addr::new(192, 58, 128, 30),
+    Ipv4Addr::new(193, 0, 14, 129),
+    Ipv4Addr::new(199, 7, 83, 42),
+    Ipv4Addr::new(202, 12, 27, 33),
+];
+
+const DNS_PORT: u16 = 53;
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+const NXDOMAIN: u16 = 3;
+
+// Limits on the work done for one question, nameserver lookups included
+const MAX_REFERRALS: usize = 16;
+const MAX_QUERIES: usize = 64;
+// Nameserver names looked up to reach the nameservers of other nameservers
+const MAX_NS_DEPTH: usize = 4;
+const MAX_CNAME_CHAIN: usize = 8;
+
+// Minimised queries for one name. The first few reveal one label each, the
+// rest of the name is spread over what is left (RFC 9156 2.3).
+const MAX_MINIMISE_COUNT: usize = 10;
+const MINIMISE_ONE_LAB: usize = 4;
+
+// QNAME minimisation (RFC 9156): servers are asked about the name only as
+// far as it takes to find their child zone, with NS queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Minimisation {
+    Off,
+    // an NXDOMAIN for part of the name stands for all of it (RFC 8020), and
+    // failing minimised queries fail the resolution
+    Strict,
+    // the full name is asked for instead when a minimised query fails or
+    // gets NXDOMAIN, which broken servers send for empty non-terminals
+    Relaxed,
+}
+
+impl FromStr for Minimisation {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Minimisation::Off),
+            "strict" => Ok(Minimisation::Strict),
+            "relaxed" => Ok(Minimisation::Relaxed),
+            _ => Err(ParseError::InvalidValue(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Minimisation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Minimisation::Off => "off",
+            Minimisation::Strict => "strict",
+            Minimisation::Relaxed => "relaxed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Recursor {
+    pub roots: Vec<SocketAddr>,
+    // port of the nameservers found along the way
+    pub port: u16,
+    // how long each nameserver gets to answer
+    pub timeout: Duration,
+    pub minimisation: Minimisation,
+    // ask for DNSSEC records and keep them in answers, for validation
+    pub dnssec: bool,
+    // resolvers asked instead when iterative resolution fails
+    pub fallback: Option<Upstreams>,
+}
+
+impl Recursor {
+    pub fn new(roots: Vec<SocketAddr>) -> Self {
+        Self {
+            roots,
+            port: DNS_PORT,
+            timeout: DEFAULT_TIMEOUT,
+            minimisation: Minimisation::Relaxed,
+            dnssec: false,
+            fallback: None,
+        }
+    }
+
+    pub fn from_root_hints() -> Self {
+        let roots = ROOT_HINTS
+            .iter()
+            .map(|&ip| SocketAddr::new(ip.into(), DNS_PORT))
+            .collect();
+        Self::new(roots)
+    }
+
+    // The answer to `question` with the CNAMEs leading to it, or for a
+    // negative answer the authority section that came with it
+    pub fn resolve(&self, question: &Question) -> Result<Packet, ResolveError> {
+        let result = self.resolve_at_depth(question, &mut 0, 0);
+        let (Err(e), Some(fallback)) = (&result, &self.fallback) else {
+            return result;
+        };
+        eprintln!(
+            "Resolving {} failed, asking the fallback: {}",
+            question.name, e
+        );
+
+        let header = Header::default()
+            .recursion_desired(true)
+            .checking_disabled(self.dnssec)
+            .build();
+        let mut query = Packet::new(header);
+        query.questions.push(question.clone());
+        query.edns = Some(Edns {
+            dnssec_ok: self.dnssec,
+            ..Edns::new(UDP_PAYLOAD_SIZE)
+        });
+        Ok(fallback.query(&query)?)
+    }
+
+    fn resolve_at_depth(
+        &self,
+        question: &Question,
+        queries: &mut usize,
+        depth: usize,
+    ) -> Result<Packet, ResolveError> {
+        let mut answers: Vec<ResourceRecord> = Vec::new();
+        let mut name = question.name.clone();
+        let mut links = 0;
+
+        loop {
+            let asked = Question::new(name.clone(), question.qtype, question.class);
+            let (reply, zone) = self.iterate(&asked, queries, depth)?;
+
+            // follow the chain as far as the reply goes, trusting only the
+            // records of the zone that sent it
+            let records: Vec<&ResourceRecord> = reply
+                .answers
+                .iter()
+                .filter(|record| record.class == question.class)
+                .filter(|record| record.name.is_subdomain_of(&zone))
+                .collect();
+            loop {
+                let rrset: Vec<ResourceRecord> = records
+                    .iter()
+                    .filter(|record| record.name == name)
+                    .filter(|record| {
+                        question.qtype == QType::ANY || record.qtype() == question.qtype
+                    })
+                    .map(|&record| record.clone())
+                    .collect();
+                if !rrset.is_empty() {
+                    answers.extend(rrset);
+                    answers.extend(self.signatures(&records, &name, question.qtype));
+                    // wildcard answers come with the proof that the name
+                    // itself does not exist
+                    return Ok(response(question, 0, answers, self.proofs(&reply)));
+                }
+
+                let cname = records.iter().find_map(|record| match &record.rdata {
+                    RData::CNAME(target) if record.name == name => Some((record, target)),
+                    _ => None,
+                });
+                match cname {
+                    Some((record, target)) if question.qtype != QType::CNAME => {
+                        links += 1;
+                        if links > MAX_CNAME_CHAIN {
+                            return Err(ResolveError::CnameChain(MAX_CNAME_CHAIN));
+                        }
+                        answers.push((*record).clone());
+                        answers.extend(self.signatures(&records, &name, QType::CNAME));
+                        name = target.clone();
+                    }
+                    _ => break,
+                }
+            }
+
+            // the chain leads out of the zone, start over for its target
+            if name == asked.name {
+                return Ok(response(
+                    question,
+                    reply.rcode(),
+                    answers,
+                    reply.authorities,
+                ));
+            }
+        }
+    }
+
+    // Follows referrals from the root for `question`, returning the final
+    // reply and the zone of the servers that sent it
+    fn iterate(
+        &self,
+        question: &Question,
+        queries: &mut usize,
+        depth: usize,
+    ) -> Result<(Packet, Name), ResolveError> {
+        let mut zone = Name::root();
+        let mut servers = self.roots.clone();
+        servers.shuffle(&mut rand::thread_rng());
+        let mut referrals = 0;
+
+        let total = question.name.labels().len();
+        let mut minimise = self.minimisation != Minimisation::Off;
+        // labels of the name the current servers were asked about already
+        let mut known = 0;
+        let mut steps = 0;
+
+        loop {
+            let revealed = if minimise {
+                minimised_labels(known.max(zone.labels().len()), total, steps)
+            } else {
+                total
+            };
+            let minimised = revealed < total;
+            let asked = if minimised {
+                steps += 1;
+                let name = question.name.suffix(total - revealed);
+                Question::new(name, QType::NS, question.class)
+            } else {
+                question.clone()
+            };
+
+            let reply = match self.ask(&servers, &asked, queries) {
+                Ok(reply) => reply,
+                Err(ResolveError::TooManyQueries(limit)) => {
+                    return Err(ResolveError::TooManyQueries(limit))
+                }
+                // some servers choke on NS queries for names they hold
+                // nothing for
+                Err(_) if minimised && self.minimisation == Minimisation::Relaxed => {
+                    minimise = false;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let Some((child, nameservers)) = referral(&reply, &asked.name, &zone) else {
+                if !minimised {
+                    return Ok((reply, zone));
+                }
+                match (reply.rcode(), self.minimisation) {
+                    (NXDOMAIN, Minimisation::Strict) => return Ok((reply, zone)),
+                    (NXDOMAIN, _) => minimise = false,
+                    // no zone cut here, the same servers know more
+                    _ => known = revealed,
+                }
+                continue;
+            };
+
+            // DS records live on the parent side of the zone cut (RFC 4035 4.2)
+            if question.qtype == QType::DS && child == question.name {
+                if minimised {
+                    minimise = false;
+                    continue;
+                }
+                return Ok((reply, zone));
+            }
+
+            referrals += 1;
+            if referrals > MAX_REFERRALS {
+                return Err(ResolveError::TooManyReferrals(MAX_REFERRALS));
+            }
+
+            servers = self.glue(&reply, &nameservers, &zone);
+            if servers.is_empty() {
+                servers = self.nameserver_addresses(&nameservers, queries, depth)?;
+            }
+            if servers.is_empty() {
+                return Err(ResolveError::NoNameserver(child));
+            }
+            zone = child;
+        }
+    }
+
+    // Asks each server in turn until one answers with NOERROR or NXDOMAIN
+    fn ask(
+        &self,
+        servers: &[SocketAddr],
+        question: &Question,
+        queries: &mut usize,
+    ) -> Result<Packet, ResolveError> {
+        let mut query = Packet::new(Header::default());
+        query.questions.push(question.clone());
+        query.edns = Some(Edns {
+            dnssec_ok: self.dnssec,
+            ..Edns::new(UDP_PAYLOAD_SIZE)
+        });
+
+        let mut last_error = ResolveError::Upstream(UpstreamError::NoUpstream);
+        for &address in servers {
+            *queries += 1;
+            if *queries > MAX_QUERIES {
+                return Err(ResolveError::TooManyQueries(MAX_QUERIES));
+            }
+
+            let upstream = Upstream {
+                timeout: self.timeout,
+                attempts: 1,
+                ..Upstream::new(address)
+            };
+            match upstream.query(&query) {
+                Ok(reply) if matches!(reply.rcode(), 0 | NXDOMAIN) => return Ok(reply),
+                Ok(reply) => last_error = ResolveError::Rcode(address, reply.rcode()),
+                Err(e) => last_error = e.into(),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    // The RRSIGs among `records` over the `qtype` RRset at `name`
+    fn signatures(
+        &self,
+        records: &[&ResourceRecord],
+        name: &Name,
+        qtype: QType,
+    ) -> Vec<ResourceRecord> {
+        if !self.dnssec {
+            return Vec::new();
+        }
+        records
+            .iter()
+            .filter(|record| match &record.rdata {
+                RData::RRSIG(rrsig) => record.name == *name && rrsig.type_covered == qtype,
+                _ => false,
+            })
+            .map(|&record| record.clone())
+            .collect()
+    }
+
+    // The NSEC and NSEC3 records in the authority section of `reply`, with
+    // their RRSIGs
+    fn proofs(&self, reply: &Packet) -> Vec<ResourceRecord> {
+        if !self.dnssec {
+            return Vec::new();
+        }
+        reply
+            .authorities
+            .iter()
+            .filter(|record| match &record.rdata {
+                RData::NSEC(_) | RData::NSEC3(_) => true,
+                RData::RRSIG(rrsig) => matches!(rrsig.type_covered, QType::NSEC | QType::NSEC3),
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Addresses of `nameservers` in the additional section of a referral from
+    // `zone`. Records from outside the zone are not the sender's to give.
+    fn glue(&self, reply: &Packet, nameservers: &[Name], zone: &Name) -> Vec<SocketAddr> {
+        let glue = reply.additionals.iter().filter(|record| {
+            nameservers.contains(&record.name) && record.name.is_subdomain_of(zone)
+        });
+
+        // IPv4 first, it works in more places
+        let mut addresses: Vec<SocketAddr> = glue
+            .clone()
+            .filter_map(|record| match record.rdata {
+                RData::A(ip) => Some(SocketAddr::new(ip.into(), self.port)),
+                _ => None,
+            })
+            .collect();
+        addresses.extend(glue.filter_map(|record| match record.rdata {
+            RData::AAAA(ip) => Some(SocketAddr::new(ip.into(), self.port)),
+            _ => None,
+        }));
+
+        addresses
+    }
+
+    // Resolves nameserver names given without glue, up to the first one that
+    // has an address
+    fn nameserver_addresses(
+        &self,
+        nameservers: &[Name],
+        queries: &mut usize,
+        depth: usize,
+    ) -> Result<Vec<SocketAddr>, ResolveError> {
+        if depth >= MAX_NS_DEPTH {
+            return Err(ResolveError::TooDeep(MAX_NS_DEPTH));
+        }
+
+        let mut last_error = None;
+        for nameserver in nameservers {
+            let question = Question::new(nameserver.clone(), QType::A, Class::IN);
+            match self.resolve_at_depth(&question, queries, depth + 1) {
+                Ok(reply) => {
+                    let addresses: Vec<SocketAddr> = reply
+                        .answers
+                        .iter()
+                        .filter_map(|record| match record.rdata {
+                            RData::A(ip) => Some(SocketAddr::new(ip.into(), self.port)),
+                            _ => None,
+                        })
+                        .collect();
+                    if !addresses.is_empty() {
+                        return Ok(addresses);
+                    }
+                }
+                // the budget is shared, no other nameserver can do better
+                Err(e @ ResolveError::TooManyQueries(_)) => return Err(e),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+// Labels of a name with `total` of them to reveal to servers knowing about
+// `known` of them, after `steps` minimised queries
+fn minimised_labels(known: usize, total: usize, steps: usize) -> usize {
+    let add = if steps < MINIMISE_ONE_LAB {
+        1
+    } else {
+        let left = MAX_MINIMISE_COUNT.saturating_sub(steps).max(1);
+        (total - known.min(total)).div_ceil(left)
+    };
+    (known + add).min(total)
+}
+
+// The zone `reply` delegates `name` to and its nameservers, when it is a
+// referral to a zone below `zone`
+fn referral(reply: &Packet, name: &Name, zone: &Name) -> Option<(Name, Vec<Name>)> {
+    if reply.rcode() != 0 || !reply.answers.is_empty() {
+        return None;
+    }
+
+    let child = &reply
+        .authorities
+        .iter()
+        .find(|record| {
+            record.qtype() == QType::NS
+                && name.is_subdomain_of(&record.name)
+                && record.name.is_subdomain_of(zone)
+                && record.name != *zone
+        })?
+        .name;
+    let nameservers = reply
+        .authorities
+        .iter()
+        .filter_map(|record| match &record.rdata {
+            RData::NS(nameserver) if record.name == *child => Some(nameserver.clone()),
+            _ => None,
+        })
+        .collect();
+
+    Some((child.clone(), nameservers))
+}
+
+fn response(
+    question: &Question,
+    rcode: u16,
+    answers: Vec<ResourceRecord>,
+    authorities: Vec<ResourceRecord>,
+) -> Packet {
+    let mut packet = Packet::response(&Header::default(), 0);
+    packet.set_rcode(rcode);
+    packet.questions.push(question.clone());
+    packet.answers = answers;
+    packet.authorities = authorities;
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, UdpSocket},
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::*;
+    use crate::{
+        dnssec::{Ds, Rrsig},
+        testing::{a, ns, record, soa},
+        upstream::Strategy,
+    };
+
+    // Never checked here, so any signature does
+    fn rrsig(name: &str, type_covered: QType, signer: &str) -> ResourceRecord {
+        let rrsig = Rrsig {
+            type_covered,
+            algorithm: 15,
+            labels: 3,
+            original_ttl: 300,
+            expiration: 2,
+            inception: 1,
+            key_tag: 1,
+            signer: signer.parse().unwrap(),
+            signature: vec![0; 64],
+        };
+        record(name, RData::RRSIG(rrsig))
+    }
+
+    fn reply(
+        rcode: u16,
+        answers: Vec<ResourceRecord>,
+        authorities: Vec<ResourceRecord>,
+        additionals: Vec<ResourceRecord>,
+    ) -> Packet {
+        let mut packet = Packet::new(Header::default());
+        packet.set_rcode(rcode);
+        packet.answers = answers;
+        packet.authorities = authorities;
+        packet.additionals = additionals;
+        packet
+    }
+
+    // The root zone, delegating com
+    fn root(question: &Question) -> Packet {
+        if question.name.is_subdomain_of(&"com".parse().unwrap()) {
+            reply(
+                0,
+                vec![],
+                vec![ns("com", "ns.gtld.com")],
+                vec![a("ns.gtld.com", "127.0.0.3")],
+            )
+        } else {
+            reply(NXDOMAIN, vec![], vec![soa(".")], vec![])
+        }
+    }
+
+    // com, delegating example.com with glue, other.com to a nameserver in
+    // example.com, and loop1.com and loop2.com to each other
+    fn com(question: &Question) -> Packet {
+        let within = |zone: &str| question.name.is_subdomain_of(&zone.parse().unwrap());
+        if question.qtype == QType::DS && question.name.to_string() == "example.com" {
+            let ds = Ds {
+                key_tag: 1,
+                algorithm: 15,
+                digest_type: 2,
+                digest: vec![0; 32],
+            };
+            reply(
+                0,
+                vec![
+                    record("example.com", RData::DS(ds)),
+                    rrsig("example.com", QType::DS, "com"),
+                ],
+                vec![],
+                vec![],
+            )
+        } else if within("example.com") {
+            reply(
+                0,
+                vec![],
+                vec![ns("example.com", "ns1.example.com")],
+                // not the business of com, and not followed
+                vec![
+                    a("ns1.example.com", "127.0.0.4"),
+                    a("www.example.net", "127.0.0.9"),
+                ],
+            )
+        } else if within("other.com") {
+            reply(0, vec![], vec![ns("other.com", "ns.example.com")], vec![])
+        } else if within("loop1.com") {
+            reply(0, vec![], vec![ns("loop1.com", "ns.loop2.com")], vec![])
+        } else if within("loop2.com") {
+            reply(0, vec![], vec![ns("loop2.com", "ns.loop1.com")], vec![])
+        } else {
+            reply(NXDOMAIN, vec![], vec![soa("com")], vec![])
+        }
+    }
+
+    const DEEP: &str = "a.b.c.d.e.f.g.h.i.j.k.l.m.n.o.p.deep.example.com";
+
+    // Authoritative for example.com and other.com. It wrongly answers
+    // NXDOMAIN for the empty non-terminal ent.example.com.
+    fn example(question: &Question) -> Packet {
+        let name = question.name.to_string();
+        if question
+            .name
+            .is_subdomain_of(&"deep.example.com".parse().unwrap())
+        {
+            return match (name.as_str(), question.qtype) {
+                (DEEP, QType::A) => reply(0, vec![a(DEEP, "10.0.0.4")], vec![], vec![]),
+                _ => reply(0, vec![], vec![soa("example.com")], vec![]),
+            };
+        }
+
+        let answers = match (name.as_str(), question.qtype) {
+            ("www.example.com", QType::A) => vec![
+                a("www.example.com", "10.0.0.1"),
+                rrsig("www.example.com", QType::A, "example.com"),
+            ],
+            ("ns1.example.com" | "ns.example.com", QType::A) => vec![a(&name, "127.0.0.4")],
+            ("www.other.com", QType::A) => vec![a("www.other.com", "10.0.0.2")],
+            ("host.ent.example.com", QType::A) => vec![a(&name, "10.0.0.3")],
+            ("alias.example.com", _) => vec![
+                record(
+                    "alias.example.com",
+                    RData::CNAME("www.other.com".parse().unwrap()),
+                ),
+                // out of the zone the question went to
+                a("www.other.com", "10.6.6.6"),
+            ],
+            ("www.example.com" | "www.other.com", _) => {
+                return reply(0, vec![], vec![soa("example.com")], vec![])
+            }
+            _ => return reply(NXDOMAIN, vec![], vec![soa("example.com")], vec![]),
+        };
+        reply(0, answers, vec![], vec![])
+    }
+
+    // Binds every address to one port, as nameservers found through
+    // referrals are all asked on `Recursor::port`
+    fn bind_same_port(ips: &[&str]) -> Vec<UdpSocket> {
+        for _ in 0..16 {
+            let first = UdpSocket::bind((ips[0], 0)).unwrap();
+            let port = first.local_addr().unwrap().port();
+            let rest: Result<Vec<UdpSocket>, _> = ips[1..]
+                .iter()
+                .map(|ip| UdpSocket::bind((*ip, port)))
+                .collect();
+            if let Ok(rest) = rest {
+                return std::iter::once(first).chain(rest).collect();
+            }
+        }
+        panic!("no port free on every address");
+    }
+
+    // Every question asked, with the address of the server it went to
+    type Log = Arc<Mutex<Vec<(IpAddr, Question)>>>;
+
+    fn serve(socket: UdpSocket, answer: fn(&Question) -> Packet, log: &Log) {
+        let log = Arc::clone(log);
+        thread::spawn(move || {
+            let ip = socket.local_addr().unwrap().ip();
+            let mut buf = [0u8; 1232];
+            while let Ok((size, client)) = socket.recv_from(&mut buf) {
+                let query = Packet::from_bytes(&buf[..size]).unwrap();
+                log.lock().unwrap().push((ip, query.questions[0].clone()));
+                let mut reply = answer(&query.questions[0]);
+                reply.header.id = query.header.id;
+                reply.header.query_response = true;
+                reply.questions = query.questions;
+                socket.send_to(&reply.to_bytes().unwrap(), client).unwrap();
+            }
+        });
+    }
+
+    fn hierarchy(minimisation: Minimisation) -> (Recursor, Log) {
+        let mut sockets = bind_same_port(&["127.0.0.2", "127.0.0.3", "127.0.0.4"]).into_iter();
+        let root_socket = sockets.next().unwrap();
+        let roots = vec![root_socket.local_addr().unwrap()];
+        let port = roots[0].port();
+
+        let log = Log::default();
+        serve(root_socket, root, &log);
+        serve(sockets.next().unwrap(), com, &log);
+        serve(sockets.next().unwrap(), example, &log);
+
+        let recursor = Recursor {
+            port,
+            timeout: Duration::from_millis(500),
+            minimisation,
+            ..Recursor::new(roots)
+        };
+        (recursor, log)
+    }
+
+    fn recursor() -> Recursor {
+        hierarchy(Minimisation::Relaxed).0
+    }
+
+    fn question(name: &str, qtype: QType) -> Question {
+        Question::new(name.parse().unwrap(), qtype, Class::IN)
+    }
+
+    #[test]
+    fn test_recursor_follows_referrals() {
+        let reply = recursor()
+            .resolve(&question("www.example.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.rcode(), 0);
+        assert_eq!(reply.answers, vec![a("www.example.com", "10.0.0.1")]);
+    }
+
+    #[test]
+    fn test_recursor_chases_cnames() {
+        // www.other.com is looked up from the root again, which needs the
+        // address of ns.example.com first
+        let reply = recursor()
+            .resolve(&question("alias.example.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.rcode(), 0);
+        assert_eq!(reply.answers.len(), 2);
+        assert_eq!(reply.answers[0].qtype(), QType::CNAME);
+        assert_eq!(reply.answers[1], a("www.other.com", "10.0.0.2"));
+    }
+
+    #[test]
+    fn test_recursor_negative_answers() {
+        let recursor = recursor();
+
+        let reply = recursor
+            .resolve(&question("missing.example.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.rcode(), NXDOMAIN);
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities, vec![soa("example.com")]);
+
+        let reply = recursor
+            .resolve(&question("www.other.com", QType::AAAA))
+            .unwrap();
+        assert_eq!(reply.rcode(), 0);
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities[0].qtype(), QType::SOA);
+    }
+
+    #[test]
+    fn test_recursor_gives_up_on_nameserver_loops() {
+        let result = recursor().resolve(&question("www.loop1.com", QType::A));
+        assert!(matches!(
+            result,
+            Err(ResolveError::TooDeep(_) | ResolveError::TooManyQueries(_))
+        ));
+    }
+
+    #[test]
+    fn test_recursor_falls_back() {
+        let socket = UdpSocket::bind("127.0.0.5:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        serve(
+            socket,
+            |question| {
+                reply(
+                    0,
+                    vec![a(&question.name.to_string(), "10.0.0.8")],
+                    vec![],
+                    vec![],
+                )
+            },
+            &Log::default(),
+        );
+        let recursor = Recursor {
+            fallback: Some(Upstreams::new(
+                vec![Upstream::new(address)],
+                Strategy::Failover,
+            )),
+            ..recursor()
+        };
+
+        let reply = recursor
+            .resolve(&question("www.loop1.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.answers, vec![a("www.loop1.com", "10.0.0.8")]);
+        // answers found iteratively do not touch it
+        let reply = recursor
+            .resolve(&question("www.example.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.answers, vec![a("www.example.com", "10.0.0.1")]);
+    }
+
+    #[test]
+    fn test_recursor_keeps_dnssec_records() {
+        let (recursor, log) = hierarchy(Minimisation::Strict);
+        let recursor = Recursor {
+            dnssec: true,
+            ..recursor
+        };
+
+        let reply = recursor
+            .resolve(&question("www.example.com", QType::A))
+            .unwrap();
+        assert_eq!(reply.answers.len(), 2);
+        assert_eq!(reply.answers[1].qtype(), QType::RRSIG);
+
+        // asked of com, on the parent side of the cut, after the minimised
+        // NS query found it
+        let reply = recursor
+            .resolve(&question("example.com", QType::DS))
+            .unwrap();
+        assert_eq!(reply.answers.len(), 2);
+        assert_eq!(reply.answers[0].qtype(), QType::DS);
+        let log = log.lock().unwrap();
+        let (ip, _) = log
+            .iter()
+            .rev()
+            .find(|(_, q)| q.qtype == QType::DS)
+            .unwrap();
+        assert_eq!(ip.to_string(), "127.0.0.3");
+    }
+
+    #[test]
+    fn test_minimisation_from_str() {
+        for minimisation in [
+            Minimisation::Off,
+            Minimisation::Strict,
+            Minimisation::Relaxed,
+        ] {
+            assert_eq!(minimisation.to_string().parse(), Ok(minimisation));
+        }
+        assert!("lenient".parse::<Minimisation>().is_err());
+    }
+
+    #[test]
+    fn test_minimised_queries() {
+        // which server is asked what while resolving www.example.com
+        let asked = |minimisation, expected: &[(&str, &str, QType)]| {
+            let (recursor, log) = hierarchy(minimisation);
+            recursor
+                .resolve(&question("www.example.com", QType::A))
+                .unwrap();
+
+            let log = log.lock().unwrap();
+            assert_eq!(log.len(), expected.len());
+            for ((ip, question), (expected_ip, name, qtype)) in log.iter().zip(expected) {
+                assert_eq!(ip.to_string(), *expected_ip);
+                assert_eq!(question.name.to_string(), *name);
+                assert_eq!(question.qtype, *qtype);
+            }
+        };
+
+        // the root and com only learn the next label down
+        asked(
+            Minimisation::Strict,
+            &[
+                ("127.0.0.2", "com", QType::NS),
+                ("127.0.0.3", "example.com", QType::NS),
+                ("127.0.0.4", "www.example.com", QType::A),
+            ],
+        );
+        asked(
+            Minimisation::Off,
+            &[
+                ("127.0.0.2", "www.example.com", QType::A),
+                ("127.0.0.3", "www.example.com", QType::A),
+                ("127.0.0.4", "www.example.com", QType::A),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_minimisation_nxdomain_for_empty_non_terminal() {
+        let host = question("host.ent.example.com", QType::A);
+
+        let (recursor, _) = hierarchy(Minimisation::Strict);
+        assert_eq!(recursor.resolve(&host).unwrap().rcode(), NXDOMAIN);
+
+        // asking for the whole name gets past the broken server
+        let (recursor, _) = hierarchy(Minimisation::Relaxed);
+        let reply = recursor.resolve(&host).unwrap();
+        assert_eq!(reply.answers, vec![a("host.ent.example.com", "10.0.0.3")]);
+    }
+
+    #[test]
+    fn test_minimisation_caps_queries_for_deep_names() {
+        let (recursor, log) = hierarchy(Minimisation::Strict);
+        let reply = recursor.resolve(&question(DEEP, QType::A)).unwrap();
+        assert_eq!(reply.answers, vec![a(DEEP, "10.0.0.4")]);
+
+        let log = log.lock().unwrap();
+        let minimised = log.iter().filter(|(_, q)| q.qtype == QType::NS).count();
+        assert!(minimised <= MAX_MINIMISE_COUNT);
+        assert_eq!(log.len(), minimised + 1);
+
+        assert_eq!(minimised_labels(0, 19, 0), 1);
+        assert_eq!(minimised_labels(4, 19, 4), 7);
+        assert_eq!(minimised_labels(17, 19, 9), 19);
+    }
+}
@@ -1,155 +1,313 @@
+use std::cmp::Ordering;
+
 use crate::{
+    encoder::Encoder,
+    error::{read_u16, read_u32, DecodeError, EncodeError},
     field::{Class, QType},
-    header::Header,
+    name::Name,
+    rdata::RData,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ResourceRecord {
-    pub name: String,
-    pub qtype: QType,
+    pub name: Name,
     pub class: Class,
     pub ttl: u32,
-    pub rdlength: u16,
-    pub rdata: Vec<u8>,
+    pub rdata: RData,
 }
 
 impl ResourceRecord {
-    pub fn new(
-        name: String,
-        qtype: QType,
-        class: Class,
-        ttl: u32,
-        rdlength: u16,
-        rdata: Vec<u8>,
-    ) -> Self {
+    pub fn new(name: Name, class: Class, ttl: u32, rdata: RData) -> Self {
         Self {
             name,
-            qtype,
             class,
             ttl,
-            rdlength,
             rdata,
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes: Vec<u8> = Vec::new();
+    pub fn qtype(&self) -> QType {
+        self.rdata.qtype()
+    }
 
-        // Encode the name
-        for label in self.name.split('.') {
-            bytes.push(label.len() as u8);
-            bytes.extend_from_slice(label.as_bytes());
-        }
-        // Null byte to terminate the domain name
-        bytes.push(0);
-
-        // Encode `qtype` (2 bytes)
-        let qtype = self.qtype.to_u16();
-        bytes.push((qtype >> 8) as u8);
-        bytes.push(qtype as u8);
-
-        // Encode `class` (2 bytes)
-        let class = self.class.to_u16();
-        bytes.push((class >> 8) as u8);
-        bytes.push(class as u8);
-
-        // Encode `ttl` (4 bytes)
-        bytes.push((self.ttl >> 24) as u8);
-        bytes.push((self.ttl >> 16) as u8);
-        bytes.push((self.ttl >> 8) as u8);
-        bytes.push(self.ttl as u8);
-
-        // Encode `length` (2 bytes)
-        bytes.push((self.rdlength >> 8) as u8);
-        bytes.push(self.rdlength as u8);
-
-        // Encode `data` (rdlength bytes)
-        bytes.extend_from_slice(&self.rdata);
-
-        bytes
-    }
-
-    pub fn from_bytes(buf: &[u8]) -> ResourceRecord {
-        let header = Header::from_bytes(&buf[..12]);
-
-        println!("rr {:?}", buf);
-        let mut idx = 12;
-
-        for _ in 0..header.answer_count {
-            // Decode the name
-            let (name, pos) = labels_from_bytes(buf, idx);
-            // let mut name = String::new();
-            // while buf[idx] != 0 {
-            //     let len = buf[idx] as usize;
-            //     idx += 1;
-            //     name.push_str(std::str::from_utf8(&buf[idx..idx + len]).unwrap());
-            //     idx += len;
-            //     if buf[idx] != 0 {
-            //         name.push('.');
-            //     }
-            // }
-            // idx += 1; // Skip the null byte
-            println!("idx {:?}", idx);
-            println!("name {:?}", name);
-
-            idx = pos + 1; // Skip the null byte
-
-            // Decode `qtype` (2 bytes)
-            let qtype = QType::from_u16(u16::from_be_bytes([buf[idx], buf[idx + 1]]));
-            idx += 2;
-            println!("qtype {:?}", qtype);
-
-            // Decode `class` (2 bytes)
-            let class = Class::from_u16(u16::from_be_bytes([buf[idx], buf[idx + 1]]));
-            idx += 2;
-            println!("class {:?}", class);
+    // Length of the uncompressed rdata
+    pub fn rdlength(&self) -> Result<u16, EncodeError> {
+        Ok(self.rdata.to_bytes()?.len() as u16)
+    }
+
+    // Owner and embedded names in lower case, as signed (RFC 4034 6.2)
+    pub fn to_canonical(&self) -> ResourceRecord {
+        ResourceRecord {
+            name: self.name.to_lowercase(),
+            class: self.class,
+            ttl: self.ttl,
+            rdata: self.rdata.to_canonical(),
         }
+    }
+
+    // Canonical order: by owner name, then class and type, and within an
+    // RRset by the canonical rdata as a left-justified octet string
+    // (RFC 4034 6.1, 6.3). Rdata that cannot be encoded comes first.
+    pub fn canonical_cmp(&self, other: &ResourceRecord) -> Ordering {
+        self.name
+            .canonical_cmp(&other.name)
+            .then_with(|| self.class.to_u16().cmp(&other.class.to_u16()))
+            .then_with(|| self.qtype().to_u16().cmp(&other.qtype().to_u16()))
+            .then_with(|| {
+                self.rdata
+                    .to_canonical()
+                    .to_bytes()
+                    .ok()
+                    .cmp(&other.rdata.to_canonical().to_bytes().ok())
+            })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut encoder = Encoder::uncompressed();
+        self.encode(&mut encoder)?;
+        Ok(encoder.finish())
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        encoder.put_name(&self.name, true);
+        encoder.put_u16(self.qtype().to_u16());
+        encoder.put_u16(self.class.to_u16());
+        encoder.put_u32(self.ttl);
+
+        // `rdlength` is only known once the names inside are compressed
+        let rdlength_pos = encoder.len();
+        encoder.put_u16(0);
+        self.rdata.encode(encoder)?;
+        let rdlength = encoder.len() - rdlength_pos - 2;
+        encoder.set_u16(rdlength_pos, rdlength as u16);
+        Ok(())
+    }
+
+    pub fn from_bytes(
+        buf: &[u8],
+        start_pos: usize,
+    ) -> Result<(ResourceRecord, usize), DecodeError> {
+        // Decode the name
+        let (name, mut idx) = Name::from_bytes(buf, start_pos)?;
+
+        // Decode `qtype` (2 bytes)
+        let qtype = QType::from_u16(read_u16(buf, idx)?);
+        idx += 2;
+
+        // Decode `class` (2 bytes)
+        let class = Class::from_u16(read_u16(buf, idx)?);
+        idx += 2;
 
         // Decode `ttl` (4 bytes)
-        let ttl = u32::from_be_bytes([buf[idx], buf[idx + 1], buf[idx + 2], buf[idx + 3]]);
+        let ttl = read_u32(buf, idx)?;
         idx += 4;
-        println!("ttl {:?}", ttl);
 
         // Decode `rdlength` (2 bytes)
-        let rdlength = u16::from_be_bytes([buf[idx], buf[idx + 1]]);
+        let rdlength = read_u16(buf, idx)?;
         idx += 2;
-        println!("rdlength {:?}", rdlength);
 
         // Decode `rdata` (rdlength bytes)
-        let rdata = buf[idx..idx + rdlength as usize].to_vec();
-        println!("rdata {:?}", rdata);
+        let rdata = RData::from_bytes(buf, idx, rdlength, qtype, class)?;
+        idx += rdlength as usize;
 
-        ResourceRecord {
-            name: "jlsadjfal".to_string(),
-            qtype: QType::A,
-            class: Class::IN,
+        let record = ResourceRecord {
+            name,
+            class,
             ttl,
-            rdlength,
             rdata,
-        }
+        };
+
+        Ok((record, idx))
     }
 }
 
-fn labels_from_bytes(data: &[u8], start_pos: usize) -> (String, usize) {
-    let mut cursor = start_pos;
-    let mut labels: Vec<String> = Vec::new();
-
-    while data[cursor] != 0 {
-        if data[cursor] & 0b11000000 == 0b11000000 {
-            let offset =
-                (((data[cursor] & 0b00111111) as u16) << 8 | data[cursor + 1] as u16) as usize;
-            let (label, _) = labels_from_bytes(&data, offset);
-            labels.push(label);
-            // increase the index by tw0 since the offset used 2 bytes
-            cursor += 2;
-        } else {
-            let length = data[cursor] as usize;
-            cursor += 1;
-            let label = std::str::from_utf8(&data[cursor..cursor + length]).unwrap();
-            labels.push(label.to_string());
-            cursor += length;
-        }
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn test_resource_record_to_bytes() {
+        let record = ResourceRecord::new(
+            "example.com".parse().unwrap(),
+            Class::IN,
+            60,
+            RData::A(Ipv4Addr::new(8, 8, 8, 8)),
+        );
+        let expected_bytes = vec![
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            0, 0, 0, 60, // ttl
+            0, 4, // rdlength
+            8, 8, 8, 8, // rdata
+        ];
+        assert_eq!(record.to_bytes().unwrap(), expected_bytes);
+    }
+
+    #[test]
+    fn test_compressed_resource_record_from_bytes() {
+        let bytes = vec![
+            144, 189, 129, 128, 0, 1, 0, 1, 0, 0, 0, 0, // header
+            7, 101, 120, 97, 109, 112, 108, 101, // "example" label
+            3, 99, 111, 109, // "com" label
+            0,   // null terminator
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            192, 12, // pointer to "example.com"
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            0, 0, 14, 16, // ttl
+            0, 4, // rdlength
+            93, 184, 216, 34, // rdata
+        ];
+        let (record, cursor) = ResourceRecord::from_bytes(&bytes, 29).unwrap();
+        assert_eq!(record.name.to_string(), "example.com");
+        assert_eq!(record.qtype(), QType::A);
+        assert_eq!(record.class, Class::IN);
+        assert_eq!(record.ttl, 3600);
+        assert_eq!(record.rdlength().unwrap(), 4);
+        assert_eq!(record.rdata, RData::A(Ipv4Addr::new(93, 184, 216, 34)));
+        assert_eq!(cursor, bytes.len());
+    }
+
+    #[test]
+    fn test_truncated_resource_record_from_bytes() {
+        let bytes = vec![
+            0, // root name
+            0, 1, // qtype A (1)
+            0, 1, // class IN (1)
+            0, 0, 14, 16, // ttl
+            0, 4, // rdlength
+            93, 184, // only half of the rdata
+        ];
+        assert_eq!(
+            ResourceRecord::from_bytes(&bytes, 0),
+            Err(DecodeError::Truncated {
+                offset: 11,
+                needed: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_compressed_rdata_from_bytes() {
+        let bytes = vec![
+            3, 99, 111, 109, 0, // "com"
+            192, 0, // pointer to "com"
+            0, 15, // qtype MX (15)
+            0, 1, // class IN (1)
+            0, 0, 14, 16, // ttl
+            0, 7, // rdlength
+            0, 10, // preference
+            2, 109, 120, 192, 0, // "mx" + pointer to "com"
+        ];
+        let (record, cursor) = ResourceRecord::from_bytes(&bytes, 5).unwrap();
+        assert_eq!(cursor, bytes.len());
+        assert_eq!(record.rdlength().unwrap(), 10);
+        assert_eq!(
+            record.rdata.to_bytes().unwrap(),
+            vec![0, 10, 2, 109, 120, 3, 99, 111, 109, 0]
+        );
+
+        let mut encoder = Encoder::new();
+        encoder.put_name(&"com".parse().unwrap(), true);
+        record.encode(&mut encoder).unwrap();
+        assert_eq!(encoder.finish(), bytes);
+    }
+
+    #[test]
+    fn test_opaque_rdata_is_not_compressed() {
+        let name: Name = "example.com".parse().unwrap();
+        let record = ResourceRecord::new(
+            name.clone(),
+            Class::IN,
+            60,
+            RData::Opaque(QType::from_u16(4660), name.to_bytes()),
+        );
+
+        let mut encoder = Encoder::new();
+        record.encode(&mut encoder).unwrap();
+        let bytes = encoder.finish();
+        assert_eq!(bytes.len(), 13 + 10 + 13);
+        assert_eq!(bytes[23..], name.to_bytes());
+    }
+
+    #[test]
+    fn test_unknown_type_and_class_from_bytes() {
+        let bytes = vec![
+            0, // root name
+            0x12, 0x34, // qtype TYPE4660
+            0, 77, // class CLASS77
+            0, 0, 0, 60, // ttl
+            0, 2, // rdlength
+            1, 2, // rdata
+        ];
+        let (record, _) = ResourceRecord::from_bytes(&bytes, 0).unwrap();
+        assert_eq!(record.qtype().to_string(), "TYPE4660");
+        assert_eq!(record.class.to_string(), "CLASS77");
+        assert_eq!(record.to_bytes().unwrap(), bytes);
     }
 
-    (labels.join("."), cursor)
+    #[test]
+    fn test_canonical_form() {
+        let record = ResourceRecord::new(
+            "WWW.Example.COM".parse().unwrap(),
+            Class::IN,
+            60,
+            RData::MX {
+                preference: 10,
+                exchange: "Mail.Example.com".parse().unwrap(),
+            },
+        );
+        assert_eq!(
+            record.to_canonical().to_bytes(),
+            ResourceRecord::new(
+                "www.example.com".parse().unwrap(),
+                Class::IN,
+                60,
+                RData::MX {
+                    preference: 10,
+                    exchange: "mail.example.com".parse().unwrap(),
+                },
+            )
+            .to_bytes()
+        );
+
+        // NSEC keeps the case of its next name
+        let nsec = RData::parse(QType::NSEC, "Host.Example.com. A").unwrap();
+        assert_eq!(nsec.to_canonical().to_bytes(), nsec.to_bytes());
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let record = |name: &str, rdata: &str| {
+            let (qtype, rdata) = rdata.split_once(' ').unwrap();
+            let qtype: QType = qtype.parse().unwrap();
+            ResourceRecord::new(
+                name.parse().unwrap(),
+                Class::IN,
+                60,
+                RData::parse(qtype, rdata).unwrap(),
+            )
+        };
+        let records = vec![
+            record("example", "NS a.example."),
+            record("example", "NS B.example."),
+            record("example", "NS c.example."),
+            record("a.example", "A 192.0.2.2"),
+            record("a.example", "A 192.0.2.10"),
+            record("a.example", "MX 1 A.example."),
+            record("A.a.example", "A 192.0.2.1"),
+            record("z.example", "TXT \"x\""),
+        ];
+
+        let mut sorted = records.clone();
+        sorted.reverse();
+        sorted.sort_by(ResourceRecord::canonical_cmp);
+        assert_eq!(sorted, records);
+    }
 }
@@ -0,0 +1,495 @@
+// SVCB and HTTPS record data (RFC 9460)
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{
+    encoder::Encoder,
+    error::{read_u16, take, DecodeError, ParseError},
+    name::Name,
+    presentation::{from_base64, parse_name, parse_number, to_base64, unescape, unquote},
+    rdata::fmt_character_string,
+};
+
+const MANDATORY: u16 = 0;
+const ALPN: u16 = 1;
+const NO_DEFAULT_ALPN: u16 = 2;
+const PORT: u16 = 3;
+const IPV4HINT: u16 = 4;
+const ECH: u16 = 5;
+const IPV6HINT: u16 = 6;
+
+const KEY_NAMES: [(u16, &str); 7] = [
+    (MANDATORY, "mandatory"),
+    (ALPN, "alpn"),
+    (NO_DEFAULT_ALPN, "no-default-alpn"),
+    (PORT, "port"),
+    (IPV4HINT, "ipv4hint"),
+    (ECH, "ech"),
+    (IPV6HINT, "ipv6hint"),
+];
+
+// SvcPriority 0 is AliasMode, anything else ServiceMode. A root TargetName
+// stands for the owner name in ServiceMode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Svcb {
+    pub priority: u16,
+    pub target: Name,
+    pub params: Vec<SvcParam>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    Mandatory(Vec<u16>),
+    Alpn(Vec<Vec<u8>>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => MANDATORY,
+            SvcParam::Alpn(_) => ALPN,
+            SvcParam::NoDefaultAlpn => NO_DEFAULT_ALPN,
+            SvcParam::Port(_) => PORT,
+            SvcParam::Ipv4Hint(_) => IPV4HINT,
+            SvcParam::Ech(_) => ECH,
+            SvcParam::Ipv6Hint(_) => IPV6HINT,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    fn value_to_bytes(&self) -> Vec<u8> {
+        let mut value: Vec<u8> = Vec::new();
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys {
+                    value.extend_from_slice(&key.to_be_bytes());
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    value.push(id.len() as u8);
+                    value.extend_from_slice(id);
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => value.extend_from_slice(&port.to_be_bytes()),
+            SvcParam::Ipv4Hint(addresses) => {
+                for address in addresses {
+                    value.extend_from_slice(&address.octets());
+                }
+            }
+            SvcParam::Ipv6Hint(addresses) => {
+                for address in addresses {
+                    value.extend_from_slice(&address.octets());
+                }
+            }
+            SvcParam::Ech(data) | SvcParam::Unknown(_, data) => value.extend_from_slice(data),
+        }
+
+        value
+    }
+
+    // Wire form of the value of `key`, `None` when it is malformed
+    fn from_value(key: u16, value: &[u8]) -> Option<SvcParam> {
+        let param = match key {
+            MANDATORY => {
+                let keys = value.chunks_exact(2);
+                if value.is_empty() || !keys.remainder().is_empty() {
+                    return None;
+                }
+                SvcParam::Mandatory(
+                    keys.map(|key| u16::from_be_bytes([key[0], key[1]]))
+                        .collect(),
+                )
+            }
+            ALPN => {
+                let mut ids: Vec<Vec<u8>> = Vec::new();
+                let mut rest = value;
+                while let Some((&length, tail)) = rest.split_first() {
+                    if length == 0 || tail.len() < length as usize {
+                        return None;
+                    }
+                    let (id, tail) = tail.split_at(length as usize);
+                    ids.push(id.to_vec());
+                    rest = tail;
+                }
+                if ids.is_empty() {
+                    return None;
+                }
+                SvcParam::Alpn(ids)
+            }
+            NO_DEFAULT_ALPN if value.is_empty() => SvcParam::NoDefaultAlpn,
+            NO_DEFAULT_ALPN => return None,
+            PORT => SvcParam::Port(u16::from_be_bytes(value.try_into().ok()?)),
+            IPV4HINT => {
+                let addresses = value.chunks_exact(4);
+                if value.is_empty() || !addresses.remainder().is_empty() {
+                    return None;
+                }
+                SvcParam::Ipv4Hint(
+                    addresses
+                        .map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+                        .collect(),
+                )
+            }
+            ECH => SvcParam::Ech(value.to_vec()),
+            IPV6HINT => {
+                let addresses = value.chunks_exact(16);
+                if value.is_empty() || !addresses.remainder().is_empty() {
+                    return None;
+                }
+                SvcParam::Ipv6Hint(
+                    addresses
+                        .map(|octets| Ipv6Addr::from(<[u8; 16]>::try_from(octets).unwrap()))
+                        .collect(),
+                )
+            }
+            _ => SvcParam::Unknown(key, value.to_vec()),
+        };
+
+        Some(param)
+    }
+
+    // `key=value` as written in master files, the value as one token
+    fn from_token(token: &str) -> Result<SvcParam, ParseError> {
+        let invalid = || ParseError::InvalidValue(token.to_string());
+
+        let (key, value) = match token.split_once('=') {
+            Some((key, value)) => (key, Some(unescape(unquote(value)?)?)),
+            None => (token, None),
+        };
+        let key = key_from_str(key)?;
+        let value = value.unwrap_or_default();
+
+        let param = match key {
+            MANDATORY => {
+                let mut keys: Vec<u16> = split_value_list(&value)
+                    .iter()
+                    .map(|key| key_from_str(&String::from_utf8_lossy(key)))
+                    .collect::<Result<_, _>>()?;
+                keys.sort_unstable();
+                SvcParam::Mandatory(keys)
+            }
+            ALPN => {
+                let ids = split_value_list(&value);
+                if ids.iter().any(|id| id.is_empty() || id.len() > 255) {
+                    return Err(invalid());
+                }
+                SvcParam::Alpn(ids)
+            }
+            NO_DEFAULT_ALPN if value.is_empty() => SvcParam::NoDefaultAlpn,
+            PORT => SvcParam::Port(parse_number(&String::from_utf8_lossy(&value))?),
+            IPV4HINT => SvcParam::Ipv4Hint(
+                split_value_list(&value)
+                    .iter()
+                    .map(|address| parse_number(&String::from_utf8_lossy(address)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ECH => SvcParam::Ech(from_base64(&String::from_utf8_lossy(&value))?),
+            IPV6HINT => SvcParam::Ipv6Hint(
+                split_value_list(&value)
+                    .iter()
+                    .map(|address| parse_number(&String::from_utf8_lossy(address)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            // unregistered keys carry their value as is
+            _ => SvcParam::from_value(key, &value).ok_or_else(invalid)?,
+        };
+
+        Ok(param)
+    }
+}
+
+impl Svcb {
+    // The TargetName is never compressed (RFC 9460 2.2)
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_u16(self.priority);
+        encoder.put_name(&self.target, false);
+        for param in &self.params {
+            let value = param.value_to_bytes();
+            encoder.put_u16(param.key());
+            encoder.put_u16(value.len() as u16);
+            encoder.put_slice(&value);
+        }
+    }
+
+    // Reads up to the end of `buf`, which the caller cuts at the end of the
+    // rdata
+    pub fn from_bytes(buf: &[u8], start_pos: usize) -> Result<(Svcb, usize), DecodeError> {
+        let priority = read_u16(buf, start_pos)?;
+        let (target, mut idx) = Name::from_bytes(buf, start_pos + 2)?;
+        let mut params: Vec<SvcParam> = Vec::new();
+
+        while idx < buf.len() {
+            let key = read_u16(buf, idx)?;
+            let length = read_u16(buf, idx + 2)?;
+            let value = take(buf, idx + 4, length as usize)?;
+            let param = SvcParam::from_value(key, value).ok_or(DecodeError::BadRdata(start_pos))?;
+            params.push(param);
+            idx += 4 + value.len();
+        }
+
+        let svcb = Svcb {
+            priority,
+            target,
+            params,
+        };
+        if !svcb.is_valid() {
+            return Err(DecodeError::BadRdata(start_pos));
+        }
+
+        Ok((svcb, idx))
+    }
+
+    // `priority target [key=value]...`, parameters in any order
+    pub fn from_tokens(tokens: &[String], origin: &Name) -> Result<Svcb, ParseError> {
+        if tokens.len() < 2 {
+            return Err(ParseError::FieldCount("SVCB".to_string()));
+        }
+
+        let mut params: Vec<SvcParam> = tokens[2..]
+            .iter()
+            .map(|token| SvcParam::from_token(token))
+            .collect::<Result<_, _>>()?;
+        params.sort_by_key(SvcParam::key);
+
+        let svcb = Svcb {
+            priority: parse_number(&tokens[0])?,
+            target: parse_name(&tokens[1], origin)?,
+            params,
+        };
+        if !svcb.is_valid() {
+            return Err(ParseError::InvalidValue(tokens.join(" ")));
+        }
+
+        Ok(svcb)
+    }
+
+    // Keys are unique and in increasing order, and every key listed in
+    // `mandatory` is present, with `mandatory` itself never listed (RFC 9460 8)
+    pub fn is_valid(&self) -> bool {
+        let keys: Vec<u16> = self.params.iter().map(SvcParam::key).collect();
+        if keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return false;
+        }
+
+        self.params.iter().all(|param| match param {
+            SvcParam::Mandatory(mandatory) => {
+                mandatory.windows(2).all(|pair| pair[0] < pair[1])
+                    && mandatory
+                        .iter()
+                        .all(|key| *key != MANDATORY && keys.contains(key))
+            }
+            _ => true,
+        })
+    }
+}
+
+fn key_name(key: u16) -> String {
+    match KEY_NAMES.iter().find(|(value, _)| *value == key) {
+        Some((_, name)) => name.to_string(),
+        None => format!("key{}", key),
+    }
+}
+
+fn key_from_str(s: &str) -> Result<u16, ParseError> {
+    if let Some((key, _)) = KEY_NAMES.iter().find(|(_, name)| *name == s) {
+        return Ok(*key);
+    }
+
+    s.strip_prefix("key")
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse::<u16>().ok())
+        .ok_or_else(|| ParseError::InvalidValue(s.to_string()))
+}
+
+// Comma separated list where `\,` and `\\` stand for themselves (RFC 9460 A.1)
+fn split_value_list(value: &[u8]) -> Vec<Vec<u8>> {
+    let mut items: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut bytes = value.iter();
+
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => {
+                if let Some(&escaped) = bytes.next() {
+                    items.last_mut().unwrap().push(escaped);
+                }
+            }
+            b',' => items.push(Vec::new()),
+            _ => items.last_mut().unwrap().push(byte),
+        }
+    }
+
+    items
+}
+
+fn fmt_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", key_name(self.key()))?;
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+                write!(f, "=")?;
+                fmt_list(f, &names)
+            }
+            SvcParam::Alpn(ids) => {
+                let mut list: Vec<u8> = Vec::new();
+                for (i, id) in ids.iter().enumerate() {
+                    if i > 0 {
+                        list.push(b',');
+                    }
+                    for &byte in id {
+                        if byte == b',' || byte == b'\\' {
+                            list.push(b'\\');
+                        }
+                        list.push(byte);
+                    }
+                }
+                write!(f, "=")?;
+                fmt_character_string(f, &list)
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(addresses) => {
+                write!(f, "=")?;
+                fmt_list(f, addresses)
+            }
+            SvcParam::Ech(data) => write!(f, "={}", to_base64(data)),
+            SvcParam::Ipv6Hint(addresses) => {
+                write!(f, "=")?;
+                fmt_list(f, addresses)
+            }
+            SvcParam::Unknown(_, data) if data.is_empty() => Ok(()),
+            SvcParam::Unknown(_, data) => {
+                write!(f, "=")?;
+                fmt_character_string(f, data)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Svcb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:#}", self.priority, self.target)?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presentation::tokenize;
+
+    fn parse(s: &str) -> Result<Svcb, ParseError> {
+        Svcb::from_tokens(&tokenize(s).unwrap(), &Name::root())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Svcb, DecodeError> {
+        Svcb::from_bytes(bytes, 0).map(|(svcb, _)| svcb)
+    }
+
+    fn to_bytes(svcb: &Svcb) -> Vec<u8> {
+        let mut encoder = Encoder::uncompressed();
+        svcb.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    #[test]
+    fn test_svcb_from_bytes() {
+        // RFC 9460 D.2, figure 5
+        let bytes = [
+            0, 1, // priority
+            3, 102, 111, 111, 7, 101, 120, 97, 109, 112, 108, 101, 3, 99, 111, 109,
+            0, // foo.example.com
+            0, 3, 0, 2, 0, 53, // port=53
+        ];
+        let svcb = decode(&bytes).unwrap();
+        assert_eq!(svcb.to_string(), "1 foo.example.com. port=53");
+        assert_eq!(to_bytes(&svcb), bytes);
+    }
+
+    #[test]
+    fn test_svcb_presentation_round_trip() {
+        for s in [
+            "0 foo.example.com.",
+            "1 .",
+            "16 foo.example.org. alpn=\"h2,h3-19\" mandatory=ipv4hint,alpn ipv4hint=192.0.2.1",
+            "1 foo.example.com. key667=\"hello\"",
+            "1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1",
+            "1 . alpn=\"h2\" no-default-alpn port=8443 ech=AEn+DQBF",
+            "1 foo.example.org. alpn=\"f\\\\\\\\oo\\\\,bar,h2\"",
+        ] {
+            let svcb = parse(s).unwrap();
+            assert_eq!(decode(&to_bytes(&svcb)).unwrap(), svcb, "{}", s);
+            assert_eq!(parse(&svcb.to_string()).unwrap(), svcb, "{}", s);
+        }
+
+        // parameters are sorted into key order
+        assert_eq!(
+            parse("16 foo.example.org. alpn=h2,h3-19 mandatory=ipv4hint,alpn ipv4hint=192.0.2.1")
+                .unwrap()
+                .to_string(),
+            "16 foo.example.org. mandatory=alpn,ipv4hint alpn=\"h2,h3-19\" ipv4hint=192.0.2.1"
+        );
+        // escaped commas stay inside the alpn id
+        assert_eq!(
+            parse("1 foo.example.org. alpn=\"f\\\\\\\\oo\\\\,bar,h2\"")
+                .unwrap()
+                .params,
+            vec![SvcParam::Alpn(vec![b"f\\oo,bar".to_vec(), b"h2".to_vec()])]
+        );
+        // `keyNNNNN` works for the named keys too
+        assert_eq!(
+            parse("1 . key3=53").unwrap().params,
+            vec![SvcParam::Port(53)]
+        );
+    }
+
+    #[test]
+    fn test_invalid_svcb() {
+        // mandatory key missing from the parameters
+        assert!(parse("1 . mandatory=port alpn=h2").is_err());
+        // mandatory listing itself
+        assert!(parse("1 . mandatory=mandatory").is_err());
+        // duplicate keys
+        assert!(parse("1 . port=1 port=2").is_err());
+        assert!(parse("1 . alpn=h2,,h3").is_err());
+        assert!(parse("1 . no-default-alpn=x").is_err());
+        assert!(parse("1 . bogus=1").is_err());
+        assert!(parse("1").is_err());
+
+        // keys out of order on the wire
+        let bytes = [0, 1, 0, 0, 4, 0, 4, 10, 0, 0, 1, 0, 3, 0, 2, 0, 53];
+        assert_eq!(decode(&bytes), Err(DecodeError::BadRdata(0)));
+        // port value of the wrong size
+        let bytes = [0, 1, 0, 0, 3, 0, 1, 53];
+        assert_eq!(decode(&bytes), Err(DecodeError::BadRdata(0)));
+        // mandatory=port without port
+        let bytes = [0, 1, 0, 0, 0, 0, 2, 0, 3];
+        assert_eq!(decode(&bytes), Err(DecodeError::BadRdata(0)));
+    }
+}
@@ -0,0 +1,54 @@
+// Message framing for DNS over TCP: every message is preceded by its length
+// as a two byte big-endian integer (RFC 1035 4.2.2, RFC 7766 8)
+
+use std::io::{self, Read, Write};
+
+pub fn write_message<W: Write>(stream: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(bytes.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long for TCP"))?;
+
+    // one write per message so the length never goes out in a segment alone
+    let mut frame: Vec<u8> = Vec::with_capacity(bytes.len() + 2);
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(bytes);
+    stream.write_all(&frame)
+}
+
+pub fn read_message<R: Read>(stream: &mut R) -> io::Result<Vec<u8>> {
+    let mut length = [0u8; 2];
+    stream.read_exact(&mut length)?;
+
+    let mut bytes = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_message_framing() {
+        let mut stream: Vec<u8> = Vec::new();
+        write_message(&mut stream, &[1, 2, 3]).unwrap();
+        write_message(&mut stream, &[]).unwrap();
+        assert_eq!(stream, vec![0, 3, 1, 2, 3, 0, 0]);
+
+        let mut cursor = Cursor::new(stream);
+        assert_eq!(read_message(&mut cursor).unwrap(), vec![1, 2, 3]);
+        assert_eq!(read_message(&mut cursor).unwrap(), Vec::<u8>::new());
+        assert_eq!(
+            read_message(&mut cursor).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        // length promises more than the stream has
+        let mut cursor = Cursor::new(vec![0, 5, 1, 2]);
+        assert!(read_message(&mut cursor).is_err());
+
+        assert!(write_message(&mut Vec::new(), &vec![0; 65536]).is_err());
+    }
+}
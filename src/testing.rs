@@ -0,0 +1,53 @@
+// Record fixtures shared by the tests of the modules handling records
+
+use crate::{
+    field::{Class, QType},
+    name::Name,
+    question::Question,
+    rdata::RData,
+    resource_records::ResourceRecord,
+};
+
+pub const TTL: u32 = 300;
+
+pub fn name(s: &str) -> Name {
+    s.parse().unwrap()
+}
+
+pub fn question(owner: &str, qtype: QType) -> Question {
+    Question::new(name(owner), qtype, Class::IN)
+}
+
+pub fn record(owner: &str, rdata: RData) -> ResourceRecord {
+    ResourceRecord::new(name(owner), Class::IN, TTL, rdata)
+}
+
+pub fn a(owner: &str, ip: &str) -> ResourceRecord {
+    record(owner, RData::A(ip.parse().unwrap()))
+}
+
+pub fn ns(zone: &str, nameserver: &str) -> ResourceRecord {
+    record(zone, RData::NS(name(nameserver)))
+}
+
+pub fn cname(owner: &str, target: &str) -> ResourceRecord {
+    record(owner, RData::CNAME(name(target)))
+}
+
+pub fn soa(zone: &str) -> ResourceRecord {
+    soa_with_ttls(zone, TTL, TTL)
+}
+
+// With the TTL of the record itself and the negative TTL in `minimum`
+pub fn soa_with_ttls(zone: &str, ttl: u32, minimum: u32) -> ResourceRecord {
+    let rdata = RData::SOA {
+        mname: name("ns.invalid"),
+        rname: name("hostmaster.invalid"),
+        serial: 1,
+        refresh: 7200,
+        retry: 900,
+        expire: 1209600,
+        minimum,
+    };
+    ResourceRecord::new(name(zone), Class::IN, ttl, rdata)
+}
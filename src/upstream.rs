@@ -0,0 +1,579 @@
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    error::{ParseError, UpstreamError},
+    packet::Packet,
+    tcp,
+};
+
+// EDNS payload size advertised to clients and upstreams, small enough to
+// avoid IP fragmentation on common paths. It is also the largest reply read
+// back from an upstream.
+pub const UDP_PAYLOAD_SIZE: u16 = 1232;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_ATTEMPTS: usize = 3;
+
+// Tries at a random port before leaving the choice to the OS
+const PORT_ATTEMPTS: usize = 8;
+
+const SERVFAIL: u16 = 2;
+
+// A failing upstream is left alone for this long, doubled with every
+// further failure in a row up to the maximum
+const SIDELINE_BASE: Duration = Duration::from_secs(5);
+const SIDELINE_MAX: Duration = Duration::from_secs(60);
+
+// Client for one upstream server. Every attempt goes out from a fresh socket
+// on a random port with a random ID, and only a reply from the server's
+// address carrying that ID and the same question is accepted. Anything else
+// arriving on the socket is dropped (RFC 5452).
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub address: SocketAddr,
+    // how long each attempt waits for its reply
+    pub timeout: Duration,
+    pub attempts: usize,
+}
+
+impl Upstream {
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            timeout: DEFAULT_TIMEOUT,
+            attempts: DEFAULT_ATTEMPTS,
+        }
+    }
+
+    // Sends `query` over UDP and, if the reply comes back truncated, again
+    // over TCP. The reply keeps TC set when the TCP retry fails.
+    pub fn query(&self, query: &Packet) -> Result<Packet, UpstreamError> {
+        let reply = self.query_udp(query)?;
+        if !reply.header.truncated_msg {
+            return Ok(reply);
+        }
+
+        match self.query_tcp(query) {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                eprintln!("TCP retry to {} failed: {}", self.address, e);
+                Ok(reply)
+            }
+        }
+    }
+
+    pub fn query_udp(&self, query: &Packet) -> Result<Packet, UpstreamError> {
+        for _ in 0..self.attempts.max(1) {
+            match self.attempt_udp(query)? {
+                Some(reply) => return Ok(reply),
+                None => continue,
+            }
+        }
+
+        Err(UpstreamError::Timeout(self.address))
+    }
+
+    // `None` when no acceptable reply came back in time
+    fn attempt_udp(&self, query: &Packet) -> Result<Option<Packet>, UpstreamError> {
+        let mut query = query.clone();
+        query.header.id = rand::thread_rng().gen();
+
+        let socket = bind_random_port(self.address)?;
+        socket.send_to(&query.to_bytes()?, self.address)?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = vec![0u8; UDP_PAYLOAD_SIZE as usize];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            socket.set_read_timeout(Some(remaining))?;
+
+            let (size, source) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if source != self.address {
+                continue;
+            }
+            // a spoofed reply may well be garbage, keep waiting for the real one
+            match Packet::from_bytes(&buf[..size]) {
+                Ok(reply) if is_reply_to(&reply, &query) => return Ok(Some(reply)),
+                _ => continue,
+            }
+        }
+    }
+
+    pub fn query_tcp(&self, query: &Packet) -> Result<Packet, UpstreamError> {
+        let mut query = query.clone();
+        query.header.id = rand::thread_rng().gen();
+
+        let mut stream = TcpStream::connect_timeout(&self.address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        tcp::write_message(&mut stream, &query.to_bytes()?)?;
+        let reply = Packet::from_bytes(&tcp::read_message(&mut stream)?)?;
+        if !is_reply_to(&reply, &query) {
+            return Err(UpstreamError::Mismatch(self.address));
+        }
+
+        Ok(reply)
+    }
+}
+
+fn is_reply_to(reply: &Packet, query: &Packet) -> bool {
+    reply.header.query_response
+        && reply.header.id == query.header.id
+        && reply.questions == query.questions
+}
+
+fn bind_random_port(upstream: SocketAddr) -> io::Result<UdpSocket> {
+    let ip: IpAddr = match upstream {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..PORT_ATTEMPTS {
+        // ports below 1024 are privileged
+        let port = rng.gen_range(1024..=u16::MAX);
+        if let Ok(socket) = UdpSocket::bind((ip, port)) {
+            return Ok(socket);
+        }
+    }
+
+    UdpSocket::bind((ip, 0))
+}
+
+// Order in which the upstreams of an `Upstreams` are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // always the first healthy one, in the configured order
+    Failover,
+    RoundRobin,
+    Random,
+    // the one with the lowest smoothed round trip time
+    LowestRtt,
+}
+
+impl FromStr for Strategy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "failover" => Ok(Strategy::Failover),
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "random" => Ok(Strategy::Random),
+            "lowest-rtt" => Ok(Strategy::LowestRtt),
+            _ => Err(ParseError::InvalidValue(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strategy::Failover => "failover",
+            Strategy::RoundRobin => "round-robin",
+            Strategy::Random => "random",
+            Strategy::LowestRtt => "lowest-rtt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Health {
+    // smoothed round trip time, unknown until the first reply
+    srtt: Option<Duration>,
+    failures: u32,
+    sidelined_until: Option<Instant>,
+}
+
+impl Health {
+    fn is_sidelined(&self, now: Instant) -> bool {
+        self.sidelined_until.is_some_and(|until| until > now)
+    }
+
+    fn succeeded(&mut self, rtt: Duration) {
+        // same weights as the TCP RTT estimator (RFC 6298)
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+        self.failures = 0;
+        self.sidelined_until = None;
+    }
+
+    fn failed(&mut self, now: Instant) {
+        let backoff = SIDELINE_BASE * 2u32.pow(self.failures.min(4));
+        self.failures += 1;
+        self.sidelined_until = Some(now + backoff.min(SIDELINE_MAX));
+    }
+}
+
+// Several upstreams used as one. A server that times out or answers SERVFAIL
+// is sidelined for a while and only tried once all the healthy ones failed.
+#[derive(Debug)]
+pub struct Upstreams {
+    upstreams: Vec<Upstream>,
+    strategy: Strategy,
+    health: Mutex<Vec<Health>>,
+    next: AtomicUsize,
+}
+
+impl Upstreams {
+    pub fn new(upstreams: Vec<Upstream>, strategy: Strategy) -> Self {
+        Self {
+            health: Mutex::new(vec![Health::default(); upstreams.len()]),
+            upstreams,
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn addresses(&self) -> Vec<SocketAddr> {
+        self.upstreams
+            .iter()
+            .map(|upstream| upstream.address)
+            .collect()
+    }
+
+    pub fn query(&self, query: &Packet) -> Result<Packet, UpstreamError> {
+        let mut last: Option<Result<Packet, UpstreamError>> = None;
+
+        for i in self.order(Instant::now()) {
+            let upstream = &self.upstreams[i];
+            let started = Instant::now();
+            let result = upstream.query(query);
+
+            match &result {
+                Ok(reply) if reply.rcode() != SERVFAIL => {
+                    self.update(i, |health| health.succeeded(started.elapsed()));
+                    return result;
+                }
+                Ok(_) => eprintln!("Upstream {} answered SERVFAIL", upstream.address),
+                Err(e) => eprintln!("Upstream {} failed: {}", upstream.address, e),
+            }
+
+            self.update(i, |health| health.failed(Instant::now()));
+            last = Some(result);
+        }
+
+        last.unwrap_or(Err(UpstreamError::NoUpstream))
+    }
+
+    // Indexes of the upstreams in the order to try them, the sidelined ones
+    // last
+    fn order(&self, now: Instant) -> Vec<usize> {
+        let health = self.health();
+        let mut order: Vec<usize> = (0..self.upstreams.len()).collect();
+
+        match self.strategy {
+            Strategy::Failover => {}
+            Strategy::RoundRobin if !order.is_empty() => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed) % order.len();
+                order.rotate_left(start);
+            }
+            Strategy::RoundRobin => {}
+            Strategy::Random => order.shuffle(&mut rand::thread_rng()),
+            // unmeasured servers first, so every server gets measured
+            Strategy::LowestRtt => order.sort_by_key(|&i| health[i].srtt.unwrap_or_default()),
+        }
+
+        // stable, so the strategy order holds within both groups
+        order.sort_by_key(|&i| health[i].is_sidelined(now));
+        order
+    }
+
+    fn health(&self) -> Vec<Health> {
+        match self.health.lock() {
+            Ok(health) => health.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn update(&self, i: usize, f: impl FnOnce(&mut Health)) {
+        let mut health = match self.health.lock() {
+            Ok(health) => health,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        f(&mut health[i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::{
+        field::{Class, QType},
+        header::Header,
+        question::Question,
+    };
+
+    fn query(name: &str) -> Packet {
+        Packet {
+            questions: vec![Question::new(name.parse().unwrap(), QType::A, Class::IN)],
+            ..Packet::new(Header::default())
+        }
+    }
+
+    fn answer(query: &Packet) -> Packet {
+        Packet {
+            questions: query.questions.clone(),
+            ..Packet::response(&query.header, 0)
+        }
+    }
+
+    fn upstream(address: SocketAddr) -> Upstream {
+        Upstream {
+            timeout: Duration::from_millis(300),
+            attempts: 2,
+            ..Upstream::new(address)
+        }
+    }
+
+    #[test]
+    fn test_upstream_ignores_mismatched_replies() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (size, client) = server.recv_from(&mut buf).unwrap();
+            let received = Packet::from_bytes(&buf[..size]).unwrap();
+
+            // wrong ID
+            let mut spoofed = answer(&received);
+            spoofed.header.id = received.header.id.wrapping_add(1);
+            server
+                .send_to(&spoofed.to_bytes().unwrap(), client)
+                .unwrap();
+
+            // wrong question
+            let spoofed = answer(&query("other.com"));
+            let spoofed = Packet {
+                header: answer(&received).header,
+                ..spoofed
+            };
+            server
+                .send_to(&spoofed.to_bytes().unwrap(), client)
+                .unwrap();
+
+            // wrong source
+            let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+            other
+                .send_to(&answer(&received).to_bytes().unwrap(), client)
+                .unwrap();
+
+            // garbage
+            server.send_to(&[1, 2, 3], client).unwrap();
+
+            let mut reply = answer(&received);
+            reply.header.authoritative_answer = true;
+            server.send_to(&reply.to_bytes().unwrap(), client).unwrap();
+        });
+
+        let reply = upstream(address).query(&query("example.com")).unwrap();
+        assert!(reply.header.authoritative_answer);
+    }
+
+    #[test]
+    fn test_upstream_retries_then_times_out() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let mut ids = Vec::new();
+            server
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            while let Ok((size, client)) = server.recv_from(&mut buf) {
+                let received = Packet::from_bytes(&buf[..size]).unwrap();
+                ids.push((received.header.id, client.port()));
+            }
+            sender.send(ids).unwrap();
+        });
+
+        assert!(matches!(
+            upstream(address).query(&query("example.com")),
+            Err(UpstreamError::Timeout(_))
+        ));
+
+        // both attempts, each from its own socket
+        let ids = receiver.recv().unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0].1, ids[1].1);
+    }
+
+    #[test]
+    fn test_upstream_retries_truncated_reply_over_tcp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let listener = TcpListener::bind(address).unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (size, client) = server.recv_from(&mut buf).unwrap();
+            let mut truncated = answer(&Packet::from_bytes(&buf[..size]).unwrap());
+            truncated.header.truncated_msg = true;
+            server
+                .send_to(&truncated.to_bytes().unwrap(), client)
+                .unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let received = Packet::from_bytes(&tcp::read_message(&mut stream).unwrap()).unwrap();
+            tcp::write_message(&mut stream, &answer(&received).to_bytes().unwrap()).unwrap();
+        });
+
+        let reply = upstream(address).query(&query("example.com")).unwrap();
+        assert!(!reply.header.truncated_msg);
+    }
+
+    fn silent_server() -> (UdpSocket, Upstream) {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let upstream = Upstream {
+            attempts: 1,
+            ..upstream(server.local_addr().unwrap())
+        };
+        (server, upstream)
+    }
+
+    // Answers every query with `rcode`, counting them
+    fn answering_server(rcode: u16) -> (Upstream, std::sync::Arc<AtomicUsize>) {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let upstream = upstream(server.local_addr().unwrap());
+        let count = std::sync::Arc::new(AtomicUsize::new(0));
+
+        let server_count = std::sync::Arc::clone(&count);
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((size, client)) = server.recv_from(&mut buf) {
+                server_count.fetch_add(1, Ordering::SeqCst);
+                let mut reply = answer(&Packet::from_bytes(&buf[..size]).unwrap());
+                reply.set_rcode(rcode);
+                server.send_to(&reply.to_bytes().unwrap(), client).unwrap();
+            }
+        });
+
+        (upstream, count)
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        for strategy in [
+            Strategy::Failover,
+            Strategy::RoundRobin,
+            Strategy::Random,
+            Strategy::LowestRtt,
+        ] {
+            assert_eq!(strategy.to_string().parse::<Strategy>(), Ok(strategy));
+        }
+        assert!("fastest".parse::<Strategy>().is_err());
+    }
+
+    #[test]
+    fn test_upstreams_fail_over_and_sideline() {
+        let (_silent, dead) = silent_server();
+        let (servfail, servfail_count) = answering_server(SERVFAIL);
+        let (alive, alive_count) = answering_server(0);
+        let upstreams = Upstreams::new(vec![dead, servfail, alive], Strategy::Failover);
+
+        let reply = upstreams.query(&query("example.com")).unwrap();
+        assert_eq!(reply.rcode(), 0);
+        assert_eq!(servfail_count.load(Ordering::SeqCst), 1);
+
+        // both failed servers are now skipped
+        let now = Instant::now();
+        assert_eq!(upstreams.order(now), vec![2, 0, 1]);
+        let started = Instant::now();
+        upstreams.query(&query("example.com")).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(servfail_count.load(Ordering::SeqCst), 1);
+        assert_eq!(alive_count.load(Ordering::SeqCst), 2);
+
+        // until the sideline period is over
+        assert_eq!(upstreams.order(now + SIDELINE_BASE), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_upstreams_return_last_failure() {
+        let (servfail, _) = answering_server(SERVFAIL);
+        let upstreams = Upstreams::new(vec![servfail], Strategy::Failover);
+        assert_eq!(
+            upstreams.query(&query("example.com")).unwrap().rcode(),
+            SERVFAIL
+        );
+
+        let (_silent, dead) = silent_server();
+        let upstreams = Upstreams::new(vec![dead], Strategy::Failover);
+        assert!(matches!(
+            upstreams.query(&query("example.com")),
+            Err(UpstreamError::Timeout(_))
+        ));
+
+        let upstreams = Upstreams::new(vec![], Strategy::Failover);
+        assert!(matches!(
+            upstreams.query(&query("example.com")),
+            Err(UpstreamError::NoUpstream)
+        ));
+    }
+
+    #[test]
+    fn test_upstreams_order() {
+        let addresses: Vec<Upstream> = (1..=3)
+            .map(|port| Upstream::new(SocketAddr::from(([127, 0, 0, 1], port))))
+            .collect();
+        let now = Instant::now();
+
+        let round_robin = Upstreams::new(addresses.clone(), Strategy::RoundRobin);
+        assert_eq!(round_robin.order(now), vec![0, 1, 2]);
+        assert_eq!(round_robin.order(now), vec![1, 2, 0]);
+        assert_eq!(round_robin.order(now), vec![2, 0, 1]);
+
+        let random = Upstreams::new(addresses.clone(), Strategy::Random);
+        let mut order = random.order(now);
+        order.sort();
+        assert_eq!(order, vec![0, 1, 2]);
+
+        let lowest_rtt = Upstreams::new(addresses, Strategy::LowestRtt);
+        lowest_rtt.update(0, |health| health.succeeded(Duration::from_millis(30)));
+        lowest_rtt.update(1, |health| health.succeeded(Duration::from_millis(10)));
+        lowest_rtt.update(2, |health| health.succeeded(Duration::from_millis(20)));
+        assert_eq!(lowest_rtt.order(now), vec![1, 2, 0]);
+
+        // smoothing keeps one slow reply from reordering everything
+        lowest_rtt.update(1, |health| health.succeeded(Duration::from_millis(50)));
+        assert_eq!(lowest_rtt.order(now), vec![1, 2, 0]);
+
+        lowest_rtt.update(1, |health| health.failed(now));
+        assert_eq!(lowest_rtt.order(now), vec![2, 0, 1]);
+    }
+}
@@ -0,0 +1,1373 @@
+// DNSSEC validation (RFC 4035 5). Answers are checked against the keys of
+// the zone that signed them, trusted through the DS and DNSKEY records that
+// lead down to it from the trust anchor for the root.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    crypto,
+    digest::Digest,
+    dnssec::{nsec3_hash, Dnskey, Ds, Nsec, Nsec3, Rrsig, NSEC3_SHA1},
+    encoder::Encoder,
+    error::{DnssecError, EncodeError, ResolveError},
+    field::{Class, QType},
+    name::Name,
+    packet::Packet,
+    presentation::{from_base32hex, tokenize},
+    question::Question,
+    rdata::RData,
+    resource_records::ResourceRecord,
+};
+
+// The root zone KSKs of 2017 and 2024 (https://data.iana.org/root-anchors/)
+pub const ROOT_ANCHORS: [&str; 2] = [
+    "20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+    "38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16",
+];
+
+const NXDOMAIN: u16 = 3;
+
+const MAX_CNAME_CHAIN: usize = 8;
+
+// NSEC3 records with more iterations than this are not worth the hashing and
+// leave the answer insecure (RFC 9276 3.2)
+const MAX_NSEC3_ITERATIONS: u16 = 100;
+
+// Longest a zone cut is remembered, whatever the TTLs, and how many are kept
+const MAX_CUT_TTL: u32 = 3600;
+const MAX_CUTS: usize = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    // signed all the way down from the trust anchor
+    Secure,
+    // from a zone proven to be unsigned
+    Insecure,
+}
+
+// Fetches the records the validator needs, RRSIGs and denial proofs
+// included, without validating them
+pub type Lookup<'a> = dyn Fn(&Question) -> Result<Packet, ResolveError> + 'a;
+
+// What a name is in the chain of trust
+#[derive(Debug, Clone)]
+enum Cut {
+    // apex of a signed zone, with its validated keys
+    Secure(Vec<Dnskey>),
+    // delegation to an unsigned zone, or to one signed only with algorithms
+    // the validator does not implement
+    Insecure,
+    // in the same zone as its parent
+    Interior,
+}
+
+// A secure zone and its keys
+#[derive(Debug)]
+struct Zone {
+    apex: Name,
+    keys: Vec<Dnskey>,
+}
+
+// The NSEC and NSEC3 records of a reply whose signatures were checked
+#[derive(Debug, Default)]
+struct Proofs {
+    nsec: Vec<(Name, Nsec)>,
+    nsec3: Vec<(Name, Nsec3)>,
+}
+
+#[derive(Debug)]
+pub struct Validator {
+    // DS records for the root zone
+    anchors: Vec<Ds>,
+    // the cuts found so far, with when they expire
+    cuts: Mutex<HashMap<Name, (Cut, u32)>>,
+}
+
+impl Validator {
+    pub fn new(anchors: Vec<Ds>) -> Self {
+        Self {
+            anchors,
+            cuts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_root_anchors() -> Self {
+        let anchors = ROOT_ANCHORS
+            .iter()
+            .map(|anchor| Ds::from_tokens(&tokenize(anchor).expect("valid anchor")))
+            .collect::<Result<_, _>>()
+            .expect("valid anchor");
+        Self::new(anchors)
+    }
+
+    fn cuts(&self) -> MutexGuard<'_, HashMap<Name, (Cut, u32)>> {
+        match self.cuts.lock() {
+            Ok(cuts) => cuts,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // Whether `reply` to `question` is secure or insecure, or why it is
+    // bogus. `now` is in seconds since the epoch, like the validity periods
+    // of signatures.
+    pub fn validate(
+        &self,
+        question: &Question,
+        reply: &Packet,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<Security, DnssecError> {
+        // nothing to check in failures
+        if !matches!(reply.rcode(), 0 | NXDOMAIN) {
+            return Ok(Security::Insecure);
+        }
+
+        let mut security = Security::Secure;
+        for (rrset, rrsigs) in rrsets(&reply.answers) {
+            let owner = &rrset[0].name;
+            let Some(zone) = self.zone_of(owner, rrset[0].qtype(), lookup, now)? else {
+                security = Security::Insecure;
+                continue;
+            };
+            let rrsig = verify_rrset(&rrset, &rrsigs, &zone, now)?;
+
+            // expanded from a wildcard, which only happens when the name
+            // closer to it does not exist (RFC 4035 5.3.4)
+            let labels = rrsig.labels as usize;
+            if labels < label_count(owner) {
+                let next_closer = owner.suffix(label_count(owner) - labels - 1);
+                let proofs = proofs(&reply.authorities, &zone, now)?;
+                let covered = proofs.nsec.iter().any(|(nsec_owner, nsec)| {
+                    covers(nsec_owner, &nsec.next, &next_closer)
+                        && speaks_for(nsec_owner, &nsec.types, &next_closer)
+                });
+                let chain = Nsec3Chain::new(&zone.apex, &proofs.nsec3);
+                if covered {
+                    continue;
+                }
+                // too many iterations to check, taken as unsigned (RFC 9276 3.2)
+                if chain.too_costly() {
+                    security = Security::Insecure;
+                } else if chain.covering(&next_closer).is_none() {
+                    return Err(DnssecError::NsecMissing(owner.clone()));
+                }
+            }
+        }
+
+        if let Some(name) = denied_name(question, reply) {
+            let nxdomain = reply.rcode() == NXDOMAIN;
+            let denial =
+                self.validate_denial(&name, question.qtype, nxdomain, reply, lookup, now)?;
+            if denial == Security::Insecure {
+                security = Security::Insecure;
+            }
+        }
+
+        Ok(security)
+    }
+
+    // The zone whose keys sign the `qtype` RRset at `owner`, or `None` when
+    // it lies in an insecure zone. It is found from the chain of trust: the
+    // signer named in the RRSIGs is up to whoever sent them.
+    fn zone_of(
+        &self,
+        owner: &Name,
+        qtype: QType,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<Option<Zone>, DnssecError> {
+        // DS records live on the parent side of the cut
+        let name = match qtype {
+            QType::DS if !owner.is_root() => owner.suffix(1),
+            _ => owner.clone(),
+        };
+        self.walk(&name, lookup, now)
+    }
+
+    // Checks the proof that there is no `qtype` RRset at `name`, or no `name`
+    // at all with `nxdomain` (RFC 4035 5.4, RFC 5155 8)
+    fn validate_denial(
+        &self,
+        name: &Name,
+        qtype: QType,
+        nxdomain: bool,
+        reply: &Packet,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<Security, DnssecError> {
+        // the zone is found from the chain of trust like for answers, as
+        // records of the parent or of another zone deny nothing here
+        let Some(zone) = self.zone_of(name, qtype, lookup, now)? else {
+            return Ok(Security::Insecure);
+        };
+
+        let proofs = proofs(&reply.authorities, &zone, now)?;
+        if nsec_denies(name, qtype, nxdomain, &proofs.nsec) {
+            return Ok(Security::Secure);
+        }
+        Nsec3Chain::new(&zone.apex, &proofs.nsec3)
+            .denies(name, qtype, nxdomain)
+            .ok_or_else(|| DnssecError::NsecMissing(name.clone()))
+    }
+
+    // The deepest secure zone holding `name`, found label by label from the
+    // root, or `None` below an insecure delegation
+    fn walk(&self, name: &Name, lookup: &Lookup, now: u32) -> Result<Option<Zone>, DnssecError> {
+        let Cut::Secure(keys) = self.cut(&Name::root(), None, lookup, now)? else {
+            return Ok(None);
+        };
+        let mut zone = Zone {
+            apex: Name::root(),
+            keys,
+        };
+
+        for skip in (0..name.labels().len()).rev() {
+            let child = name.suffix(skip);
+            match self.cut(&child, Some(&zone), lookup, now)? {
+                Cut::Secure(keys) => zone = Zone { apex: child, keys },
+                Cut::Insecure => return Ok(None),
+                Cut::Interior => {}
+            }
+        }
+
+        Ok(Some(zone))
+    }
+
+    // What `name` is below the secure `parent` zone, or for the root what
+    // the trust anchors make of it
+    fn cut(
+        &self,
+        name: &Name,
+        parent: Option<&Zone>,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<Cut, DnssecError> {
+        if let Some((cut, expires)) = self.cuts().get(name) {
+            if serial_before(now, *expires) {
+                return Ok(cut.clone());
+            }
+        }
+
+        let (cut, ttl) = match parent {
+            Some(parent) => self.delegation(name, parent, lookup, now)?,
+            None => self.apex(name, &self.anchors, MAX_CUT_TTL, lookup, now)?,
+        };
+
+        let mut cuts = self.cuts();
+        if cuts.len() >= MAX_CUTS {
+            cuts.retain(|_, (_, expires)| serial_before(now, *expires));
+        }
+        if cuts.len() < MAX_CUTS {
+            let expires = now.wrapping_add(ttl.min(MAX_CUT_TTL));
+            cuts.insert(name.clone(), (cut.clone(), expires));
+        }
+        Ok(cut)
+    }
+
+    // The DS RRset at `name` signed by `parent` makes it a secure zone. With
+    // none, the parent has to prove an unsigned delegation, or `name` is in
+    // the parent zone. Taking a cut for none fails safe: what the child
+    // signed then cannot be validated.
+    fn delegation(
+        &self,
+        name: &Name,
+        parent: &Zone,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<(Cut, u32), DnssecError> {
+        let reply = fetch(lookup, name, QType::DS)?;
+        let ds_rrset = rrsets(&reply.answers)
+            .into_iter()
+            .find(|(rrset, _)| rrset[0].name == *name && rrset[0].qtype() == QType::DS);
+        if let Some((rrset, rrsigs)) = ds_rrset {
+            verify_rrset(&rrset, &rrsigs, parent, now)?;
+            let ds: Vec<Ds> = rrset
+                .iter()
+                .filter_map(|record| match &record.rdata {
+                    RData::DS(ds) => Some(ds.clone()),
+                    _ => None,
+                })
+                .collect();
+            return self.apex(name, &ds, min_ttl(&rrset), lookup, now);
+        }
+
+        let proofs = proofs(&reply.authorities, parent, now)?;
+        let ttl = min_ttl(&reply.authorities);
+        let unsigned = |types: &[QType]| {
+            types.contains(&QType::NS)
+                && !types.contains(&QType::DS)
+                && !types.contains(&QType::SOA)
+        };
+
+        if proofs
+            .nsec
+            .iter()
+            .any(|(owner, nsec)| owner == name && unsigned(&nsec.types))
+        {
+            return Ok((Cut::Insecure, ttl));
+        }
+
+        let chain = Nsec3Chain::new(&parent.apex, &proofs.nsec3);
+        if chain.too_costly() {
+            return Ok((Cut::Insecure, ttl));
+        }
+        let insecure = match chain.matching(name) {
+            Some(nsec3) => unsigned(&nsec3.types),
+            // an unsigned delegation in an Opt-Out span (RFC 5155 8.6)
+            None => chain
+                .closest_encloser(name)
+                .is_some_and(|(_, next_closer)| next_closer.opt_out()),
+        };
+        if insecure {
+            return Ok((Cut::Insecure, ttl));
+        }
+
+        Ok((Cut::Interior, ttl))
+    }
+
+    // The keys at `apex`, trusted when the DNSKEY RRset is signed by one of
+    // the keys `ds` points at (RFC 4035 5.2)
+    fn apex(
+        &self,
+        apex: &Name,
+        ds: &[Ds],
+        ttl: u32,
+        lookup: &Lookup,
+        now: u32,
+    ) -> Result<(Cut, u32), DnssecError> {
+        let ds: Vec<&Ds> = ds
+            .iter()
+            .filter(|ds| crypto::is_supported(ds.algorithm))
+            .filter(|ds| Digest::from_ds_type(ds.digest_type).is_some())
+            .collect();
+        if ds.is_empty() {
+            return Ok((Cut::Insecure, ttl));
+        }
+
+        let reply = fetch(lookup, apex, QType::DNSKEY)?;
+        let Some((rrset, rrsigs)) = rrsets(&reply.answers)
+            .into_iter()
+            .find(|(rrset, _)| rrset[0].name == *apex && rrset[0].qtype() == QType::DNSKEY)
+        else {
+            return Err(DnssecError::DnskeyMissing(apex.clone()));
+        };
+
+        let keys: Vec<Dnskey> = rrset
+            .iter()
+            .filter_map(|record| match &record.rdata {
+                RData::DNSKEY(key) if key.is_zone_key() => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        let trusted: Vec<Dnskey> = keys
+            .iter()
+            .filter(|key| {
+                ds.iter()
+                    .any(|ds| key.to_ds(apex, ds.digest_type).as_ref() == Some(*ds))
+            })
+            .cloned()
+            .collect();
+        if trusted.is_empty() {
+            return Err(DnssecError::DnskeyMissing(apex.clone()));
+        }
+
+        let zone = Zone {
+            apex: apex.clone(),
+            keys: trusted,
+        };
+        verify_rrset(&rrset, &rrsigs, &zone, now)?;
+
+        Ok((Cut::Secure(keys), ttl.min(min_ttl(&rrset))))
+    }
+}
+
+fn fetch(lookup: &Lookup, name: &Name, qtype: QType) -> Result<Packet, DnssecError> {
+    let question = Question::new(name.clone(), qtype, Class::IN);
+    match lookup(&question) {
+        Ok(reply) if matches!(reply.rcode(), 0 | NXDOMAIN) => Ok(reply),
+        _ => Err(DnssecError::Lookup(name.clone(), qtype)),
+    }
+}
+
+fn min_ttl(records: &[ResourceRecord]) -> u32 {
+    records
+        .iter()
+        .map(|record| record.ttl)
+        .min()
+        .unwrap_or(MAX_CUT_TTL)
+}
+
+// The RRsets among `records`, each with the RRSIGs covering it
+fn rrsets(records: &[ResourceRecord]) -> Vec<(Vec<ResourceRecord>, Vec<Rrsig>)> {
+    let mut rrsets: Vec<(Vec<ResourceRecord>, Vec<Rrsig>)> = Vec::new();
+    for record in records {
+        if record.qtype() == QType::RRSIG {
+            continue;
+        }
+        let rrset = rrsets.iter_mut().find(|(rrset, _)| {
+            rrset[0].name == record.name
+                && rrset[0].class == record.class
+                && rrset[0].qtype() == record.qtype()
+        });
+        match rrset {
+            Some((rrset, _)) => rrset.push(record.clone()),
+            None => rrsets.push((vec![record.clone()], Vec::new())),
+        }
+    }
+
+    for record in records {
+        let RData::RRSIG(rrsig) = &record.rdata else {
+            continue;
+        };
+        let rrset = rrsets.iter_mut().find(|(rrset, _)| {
+            rrset[0].name == record.name
+                && rrset[0].class == record.class
+                && rrset[0].qtype() == rrsig.type_covered
+        });
+        if let Some((_, rrsigs)) = rrset {
+            rrsigs.push(rrsig.clone());
+        }
+    }
+
+    rrsets
+}
+
+// One of `rrsigs` made by the keys of `zone` over `rrset` (RFC 4035 5.3.1),
+// or the most telling reason none is
+fn verify_rrset<'a>(
+    rrset: &[ResourceRecord],
+    rrsigs: &'a [Rrsig],
+    zone: &Zone,
+    now: u32,
+) -> Result<&'a Rrsig, DnssecError> {
+    let owner = &rrset[0].name;
+    let mut error = DnssecError::RrsigsMissing(owner.clone(), rrset[0].qtype());
+
+    for rrsig in rrsigs {
+        if rrsig.signer != zone.apex
+            || !owner.is_subdomain_of(&zone.apex)
+            || rrsig.type_covered != rrset[0].qtype()
+            || rrsig.labels as usize > label_count(owner)
+        {
+            continue;
+        }
+        if serial_before(rrsig.expiration, now) {
+            error = DnssecError::SignatureExpired;
+            continue;
+        }
+        if serial_before(now, rrsig.inception) {
+            error = DnssecError::SignatureNotYetValid;
+            continue;
+        }
+
+        let keys = zone
+            .keys
+            .iter()
+            .filter(|key| key.algorithm == rrsig.algorithm && key.key_tag() == rrsig.key_tag);
+        let Ok(data) = signed_data(rrset, rrsig) else {
+            continue;
+        };
+        for key in keys {
+            if crypto::verify(key.algorithm, &key.public_key, &data, &rrsig.signature) {
+                return Ok(rrsig);
+            }
+            error = DnssecError::BadSignature;
+        }
+    }
+
+    Err(error)
+}
+
+// The RRSIG fields then the RRset in canonical form and order, with the
+// original TTL and the wildcard owner it was expanded from (RFC 4034 3.1.8.1)
+fn signed_data(rrset: &[ResourceRecord], rrsig: &Rrsig) -> Result<Vec<u8>, EncodeError> {
+    let mut encoder = Encoder::uncompressed();
+    Rrsig {
+        signer: rrsig.signer.to_lowercase(),
+        ..rrsig.clone()
+    }
+    .encode_header(&mut encoder);
+
+    let owner = &rrset[0].name;
+    let labels = rrsig.labels as usize;
+    let owner = if labels < label_count(owner) {
+        wildcard(&owner.suffix(owner.labels().len() - labels)).unwrap_or_else(|| owner.clone())
+    } else {
+        owner.clone()
+    };
+
+    let mut records: Vec<ResourceRecord> = rrset
+        .iter()
+        .map(|record| ResourceRecord {
+            name: owner.clone(),
+            ttl: rrsig.original_ttl,
+            ..record.to_canonical()
+        })
+        .collect();
+    records.sort_by(|a, b| a.canonical_cmp(b));
+    records.dedup();
+    for record in &records {
+        record.encode(&mut encoder)?;
+    }
+
+    Ok(encoder.finish())
+}
+
+// The NSEC and NSEC3 records among `records` for names in `zone`, after
+// checking their signatures
+fn proofs(records: &[ResourceRecord], zone: &Zone, now: u32) -> Result<Proofs, DnssecError> {
+    let mut proofs = Proofs::default();
+    for (rrset, rrsigs) in rrsets(records) {
+        let owner = &rrset[0].name;
+        let qtype = rrset[0].qtype();
+        if !owner.is_subdomain_of(&zone.apex) || !matches!(qtype, QType::NSEC | QType::NSEC3) {
+            continue;
+        }
+
+        // one expanded from a wildcard says nothing about its owner
+        let rrsig = verify_rrset(&rrset, &rrsigs, zone, now)?;
+        if (rrsig.labels as usize) < label_count(owner) {
+            continue;
+        }
+
+        for record in rrset.iter() {
+            match &record.rdata {
+                RData::NSEC(nsec) => proofs.nsec.push((owner.clone(), nsec.clone())),
+                RData::NSEC3(nsec3) => proofs.nsec3.push((owner.clone(), nsec3.clone())),
+                _ => {}
+            }
+        }
+    }
+    Ok(proofs)
+}
+
+// The name at the end of the CNAME chain in `reply` when the reply has no
+// answer for it
+fn denied_name(question: &Question, reply: &Packet) -> Option<Name> {
+    let mut name = question.name.clone();
+    if question.qtype != QType::CNAME {
+        for _ in 0..MAX_CNAME_CHAIN {
+            let target = reply.answers.iter().find_map(|record| match &record.rdata {
+                RData::CNAME(target) if record.name == name => Some(target.clone()),
+                _ => None,
+            });
+            match target {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+    }
+
+    let answered = reply.answers.iter().any(|record| {
+        record.name == name && (question.qtype == QType::ANY || record.qtype() == question.qtype)
+    });
+    (reply.rcode() == NXDOMAIN || !answered).then_some(name)
+}
+
+// Whether the NSEC records prove there is no `qtype` RRset at `name`, or with
+// `nxdomain` no `name` at all (RFC 4035 5.4)
+fn nsec_denies(name: &Name, qtype: QType, nxdomain: bool, nsecs: &[(Name, Nsec)]) -> bool {
+    let nsecs: Vec<&(Name, Nsec)> = nsecs
+        .iter()
+        .filter(|(owner, nsec)| speaks_for(owner, &nsec.types, name))
+        .collect();
+    let covering = nsecs
+        .iter()
+        .find(|(owner, nsec)| covers(owner, &nsec.next, name));
+    // names that exist lie between the NSEC owner and next name, so the
+    // closest of them to `name` is as close as it gets
+    let encloser = covering.map(|(owner, nsec)| {
+        let a = common_ancestor(name, owner);
+        let b = common_ancestor(name, &nsec.next);
+        if a.labels().len() > b.labels().len() {
+            a
+        } else {
+            b
+        }
+    });
+    let wildcard = encloser.as_ref().and_then(wildcard);
+
+    if nxdomain {
+        return wildcard.is_some_and(|wildcard| {
+            nsecs
+                .iter()
+                .any(|(owner, nsec)| covers(owner, &nsec.next, &wildcard))
+        });
+    }
+
+    if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| owner == name) {
+        return lacks(&nsec.types, qtype);
+    }
+    // an empty non-terminal, with names below it
+    if covering.is_some_and(|(_, nsec)| nsec.next.is_subdomain_of(name)) {
+        return true;
+    }
+    // the wildcard the name would be expanded from lacks the type too
+    wildcard.is_some_and(|wildcard| {
+        nsecs
+            .iter()
+            .any(|(owner, nsec)| *owner == wildcard && lacks(&nsec.types, qtype))
+    })
+}
+
+// Whether the NSEC or NSEC3 record at `owner` with `types` can deny
+// anything at `name`: one of a delegation or a DNAME says nothing about the
+// names below it (RFC 6840 4.1)
+fn speaks_for(owner: &Name, types: &[QType], name: &Name) -> bool {
+    let delegation = types.contains(&QType::NS) && !types.contains(&QType::SOA);
+    let below = name != owner && name.is_subdomain_of(owner);
+    !below || !(delegation || types.contains(&QType::DNAME))
+}
+
+// A type bitmap without `qtype` or a CNAME. At a zone cut the parent's
+// records only speak for the DS RRset and the child's for the rest
+// (RFC 6840 4.1).
+fn lacks(types: &[QType], qtype: QType) -> bool {
+    let apex = types.contains(&QType::SOA);
+    let delegation = types.contains(&QType::NS) && !apex;
+    let right_side = if qtype == QType::DS {
+        !apex
+    } else {
+        !delegation
+    };
+    right_side && !types.contains(&qtype) && !types.contains(&QType::CNAME)
+}
+
+// The NSEC3 records of one zone with their owner hashes
+struct Nsec3Chain<'a> {
+    zone: &'a Name,
+    records: Vec<(Vec<u8>, &'a Nsec3)>,
+}
+
+impl<'a> Nsec3Chain<'a> {
+    fn new(zone: &'a Name, nsec3s: &'a [(Name, Nsec3)]) -> Self {
+        let records = nsec3s
+            .iter()
+            .filter(|(owner, nsec3)| {
+                nsec3.hash_algorithm == NSEC3_SHA1 && !owner.is_root() && owner.suffix(1) == *zone
+            })
+            .filter_map(|(owner, nsec3)| {
+                let label = std::str::from_utf8(&owner.labels()[0]).ok()?;
+                Some((from_base32hex(label).ok()?, nsec3))
+            })
+            .collect();
+        Self { zone, records }
+    }
+
+    fn too_costly(&self) -> bool {
+        self.records
+            .iter()
+            .any(|(_, nsec3)| nsec3.iterations > MAX_NSEC3_ITERATIONS)
+    }
+
+    fn matching(&self, name: &Name) -> Option<&'a Nsec3> {
+        self.records
+            .iter()
+            .find(|(hash, nsec3)| *hash == nsec3_hash(name, &nsec3.salt, nsec3.iterations))
+            .map(|(_, nsec3)| *nsec3)
+    }
+
+    fn covering(&self, name: &Name) -> Option<&'a Nsec3> {
+        self.records
+            .iter()
+            .find(|(hash, nsec3)| {
+                let target = nsec3_hash(name, &nsec3.salt, nsec3.iterations);
+                hash_covers(hash, &nsec3.next_hashed, &target)
+            })
+            .map(|(_, nsec3)| *nsec3)
+    }
+
+    // The closest encloser of `name` with the record covering the next
+    // closer name (RFC 5155 8.3)
+    fn closest_encloser(&self, name: &Name) -> Option<(Name, &'a Nsec3)> {
+        if !name.is_subdomain_of(self.zone) {
+            return None;
+        }
+        let depth = name.labels().len() - self.zone.labels().len();
+        let skip = (1..=depth).find(|&skip| self.matching(&name.suffix(skip)).is_some())?;
+        let encloser = name.suffix(skip);
+        if !speaks_for(&encloser, &self.matching(&encloser)?.types, name) {
+            return None;
+        }
+        let next_closer = self.covering(&name.suffix(skip - 1))?;
+        Some((encloser, next_closer))
+    }
+
+    // Like `nsec_denies`, but insecure for Opt-Out spans and costly hashes
+    // (RFC 5155 8.4 to 8.7)
+    fn denies(&self, name: &Name, qtype: QType, nxdomain: bool) -> Option<Security> {
+        if self.too_costly() {
+            return Some(Security::Insecure);
+        }
+        let security = |nsec3: &Nsec3| {
+            if nsec3.opt_out() {
+                Security::Insecure
+            } else {
+                Security::Secure
+            }
+        };
+
+        if nxdomain {
+            let (encloser, next_closer) = self.closest_encloser(name)?;
+            self.covering(&wildcard(&encloser)?)?;
+            return Some(security(next_closer));
+        }
+
+        if let Some(nsec3) = self.matching(name) {
+            return lacks(&nsec3.types, qtype).then_some(Security::Secure);
+        }
+        let (encloser, next_closer) = self.closest_encloser(name)?;
+        if qtype == QType::DS && next_closer.opt_out() {
+            return Some(Security::Insecure);
+        }
+        let wildcard = self.matching(&wildcard(&encloser)?)?;
+        lacks(&wildcard.types, qtype).then_some(Security::Secure)
+    }
+}
+
+// Whether `name` lies strictly between an NSEC owner and its next name, the
+// last NSEC of the zone wrapping around to the apex
+fn covers(owner: &Name, next: &Name, name: &Name) -> bool {
+    let after_owner = owner.canonical_cmp(name) == Ordering::Less;
+    let before_next = name.canonical_cmp(next) == Ordering::Less;
+    if owner.canonical_cmp(next) == Ordering::Less {
+        after_owner && before_next
+    } else {
+        after_owner || before_next
+    }
+}
+
+fn hash_covers(owner: &[u8], next: &[u8], hash: &[u8]) -> bool {
+    if owner < next {
+        owner < hash && hash < next
+    } else {
+        owner < hash || hash < next
+    }
+}
+
+fn common_ancestor(a: &Name, b: &Name) -> Name {
+    let mut shared = a.labels().len().min(b.labels().len());
+    while a.suffix(a.labels().len() - shared) != b.suffix(b.labels().len() - shared) {
+        shared -= 1;
+    }
+    a.suffix(a.labels().len() - shared)
+}
+
+fn wildcard(encloser: &Name) -> Option<Name> {
+    Name::from_labels(vec![b"*".to_vec()])
+        .and_then(|star| star.append(encloser))
+        .ok()
+}
+
+// Labels of `name` as counted in RRSIG records, a leading `*` left out
+fn label_count(name: &Name) -> usize {
+    match name.labels().first() {
+        Some(label) if label == b"*" => name.labels().len() - 1,
+        _ => name.labels().len(),
+    }
+}
+
+// Serial number arithmetic on 32-bit times (RFC 1982)
+fn serial_before(a: u32, b: u32) -> bool {
+    (b.wrapping_sub(a) as i32) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{
+        crypto::{ed25519_public_key, ed25519_sign, ED25519},
+        dnssec::{DNSKEY_PROTOCOL, SECURE_ENTRY_POINT, ZONE_KEY},
+        header::Header,
+        testing::{a, name, question, record, soa, TTL},
+    };
+
+    const NOW: u32 = 1_700_000_000;
+
+    // A zone served from memory, signed with NSEC and an Ed25519 key made
+    // from `seed` unless it is `None`
+    struct TestZone {
+        apex: Name,
+        seed: Option<[u8; 32]>,
+        records: Vec<ResourceRecord>,
+    }
+
+    impl TestZone {
+        fn new(apex: &str, seed: Option<u8>, mut records: Vec<ResourceRecord>) -> Self {
+            records.push(soa(apex));
+            let mut zone = TestZone {
+                apex: name(apex),
+                seed: seed.map(|byte| [byte; 32]),
+                records,
+            };
+            if zone.seed.is_some() {
+                zone.sign();
+            }
+            zone
+        }
+
+        fn dnskey(&self) -> Option<Dnskey> {
+            Some(Dnskey {
+                flags: ZONE_KEY | SECURE_ENTRY_POINT,
+                protocol: DNSKEY_PROTOCOL,
+                algorithm: ED25519,
+                public_key: ed25519_public_key(self.seed.as_ref()?),
+            })
+        }
+
+        fn ds(&self) -> ResourceRecord {
+            let ds = self.dnskey().unwrap().to_ds(&self.apex, 2).unwrap();
+            ResourceRecord::new(self.apex.clone(), Class::IN, TTL, RData::DS(ds))
+        }
+
+        fn rrsig(
+            &self,
+            rrset: &[ResourceRecord],
+            inception: u32,
+            expiration: u32,
+        ) -> ResourceRecord {
+            let mut rrsig = Rrsig {
+                type_covered: rrset[0].qtype(),
+                algorithm: ED25519,
+                labels: label_count(&rrset[0].name) as u8,
+                original_ttl: TTL,
+                expiration,
+                inception,
+                key_tag: self.dnskey().unwrap().key_tag(),
+                signer: self.apex.clone(),
+                signature: Vec::new(),
+            };
+            let data = signed_data(rrset, &rrsig).unwrap();
+            rrsig.signature = ed25519_sign(self.seed.as_ref().unwrap(), &data);
+            ResourceRecord::new(rrset[0].name.clone(), Class::IN, TTL, RData::RRSIG(rrsig))
+        }
+
+        // Adds the DNSKEY, the NSEC chain and an RRSIG for every RRset the
+        // zone is authoritative for
+        fn sign(&mut self) {
+            let dnskey = RData::DNSKEY(self.dnskey().unwrap());
+            self.records.push(ResourceRecord::new(
+                self.apex.clone(),
+                Class::IN,
+                TTL,
+                dnskey,
+            ));
+
+            let mut owners: Vec<Name> = self.records.iter().map(|r| r.name.clone()).collect();
+            owners.sort_by(|a, b| a.canonical_cmp(b));
+            owners.dedup();
+            for (i, owner) in owners.iter().enumerate() {
+                let mut types: Vec<QType> = self
+                    .records
+                    .iter()
+                    .filter(|record| record.name == *owner)
+                    .map(|record| record.qtype())
+                    .chain([QType::RRSIG, QType::NSEC])
+                    .collect();
+                types.sort_by_key(|qtype| qtype.to_u16());
+                types.dedup();
+                let next = owners[(i + 1) % owners.len()].clone();
+                let nsec = RData::NSEC(Nsec { next, types });
+                self.records
+                    .push(ResourceRecord::new(owner.clone(), Class::IN, TTL, nsec));
+            }
+
+            for (rrset, _) in rrsets(&self.records) {
+                let delegation = rrset[0].qtype() == QType::NS && rrset[0].name != self.apex;
+                if !delegation {
+                    let rrsig = self.rrsig(&rrset, NOW - 3600, NOW + 3600);
+                    self.records.push(rrsig);
+                }
+            }
+        }
+
+        // The RRset of `qtype` at `owner` with its RRSIGs
+        fn rrset(&self, owner: &Name, qtype: QType) -> Vec<ResourceRecord> {
+            self.records
+                .iter()
+                .filter(|record| record.name == *owner)
+                .filter(|record| match &record.rdata {
+                    RData::RRSIG(rrsig) => rrsig.type_covered == qtype,
+                    _ => record.qtype() == qtype,
+                })
+                .cloned()
+                .collect()
+        }
+
+        fn covering_nsec(&self, target: &Name) -> Vec<ResourceRecord> {
+            let owner = self.records.iter().find_map(|record| match &record.rdata {
+                RData::NSEC(nsec) if covers(&record.name, &nsec.next, target) => {
+                    Some(record.name.clone())
+                }
+                _ => None,
+            });
+            owner
+                .map(|owner| self.rrset(&owner, QType::NSEC))
+                .unwrap_or_default()
+        }
+
+        fn answer(&self, question: &Question) -> Packet {
+            let qname = &question.name;
+            let mut reply = Packet::new(Header::default());
+            reply.questions.push(question.clone());
+
+            let exact = self.rrset(qname, question.qtype);
+            let cname = self.rrset(qname, QType::CNAME);
+            let exists = self
+                .records
+                .iter()
+                .any(|record| record.name.is_subdomain_of(qname));
+            let star = wildcard(&qname.suffix(1)).unwrap();
+            let wild = self.rrset(&star, question.qtype);
+
+            if !exact.is_empty() {
+                reply.answers = exact;
+            } else if !cname.is_empty() {
+                reply.answers = cname;
+            } else if exists {
+                reply.authorities = self.rrset(&self.apex, QType::SOA);
+                let matching = self.rrset(qname, QType::NSEC);
+                if matching.is_empty() {
+                    reply.authorities.extend(self.covering_nsec(qname));
+                } else {
+                    reply.authorities.extend(matching);
+                }
+            } else if !wild.is_empty() {
+                reply.answers = wild
+                    .into_iter()
+                    .map(|record| ResourceRecord {
+                        name: qname.clone(),
+                        ..record
+                    })
+                    .collect();
+                reply.authorities = self.covering_nsec(qname);
+            } else {
+                reply.set_rcode(NXDOMAIN);
+                reply.authorities = self.rrset(&self.apex, QType::SOA);
+                for record in self
+                    .covering_nsec(qname)
+                    .into_iter()
+                    .chain(self.covering_nsec(&star))
+                {
+                    if !reply.authorities.contains(&record) {
+                        reply.authorities.push(record);
+                    }
+                }
+            }
+            reply
+        }
+    }
+
+    // The root delegating the signed `example` and the unsigned `insecure`
+    fn zones() -> Vec<TestZone> {
+        let example = TestZone::new(
+            "example",
+            Some(2),
+            vec![
+                record("example", RData::NS(name("ns.example"))),
+                a("www.example", "10.0.0.1"),
+                record("alias.example", RData::CNAME(name("www.example"))),
+                a("host.ent.example", "10.0.0.2"),
+                a("*.wild.example", "10.0.0.3"),
+            ],
+        );
+        let insecure = TestZone::new(
+            "insecure",
+            None,
+            vec![
+                record("insecure", RData::NS(name("ns.insecure"))),
+                a("www.insecure", "10.0.1.1"),
+            ],
+        );
+        let root = TestZone::new(
+            ".",
+            Some(1),
+            vec![
+                record(".", RData::NS(name("ns.root"))),
+                record("example", RData::NS(name("ns.example"))),
+                example.ds(),
+                record("insecure", RData::NS(name("ns.insecure"))),
+            ],
+        );
+        vec![root, example, insecure]
+    }
+
+    fn validator(zones: &[TestZone]) -> Validator {
+        let anchor = match zones[0].ds().rdata {
+            RData::DS(ds) => ds,
+            _ => unreachable!(),
+        };
+        Validator::new(vec![anchor])
+    }
+
+    // Answers from the zone holding the name, the parent for DS records
+    fn resolve(zones: &[TestZone], question: &Question) -> Packet {
+        zones
+            .iter()
+            .filter(|zone| question.name.is_subdomain_of(&zone.apex))
+            .filter(|zone| question.qtype != QType::DS || question.name != zone.apex)
+            .max_by_key(|zone| zone.apex.labels().len())
+            .unwrap()
+            .answer(question)
+    }
+
+    fn validate(
+        zones: &[TestZone],
+        question: &Question,
+        reply: &Packet,
+    ) -> Result<Security, DnssecError> {
+        let lookup = |q: &Question| Ok(resolve(zones, q));
+        validator(zones).validate(question, reply, &lookup, NOW)
+    }
+
+    fn check(zones: &[TestZone], owner: &str, qtype: QType) -> Result<Security, DnssecError> {
+        let question = question(owner, qtype);
+        validate(zones, &question, &resolve(zones, &question))
+    }
+
+    #[test]
+    fn test_secure_answers() {
+        let zones = zones();
+        assert_eq!(check(&zones, "www.example", QType::A), Ok(Security::Secure));
+        assert_eq!(
+            check(&zones, "example", QType::DNSKEY),
+            Ok(Security::Secure)
+        );
+        assert_eq!(check(&zones, "example", QType::DS), Ok(Security::Secure));
+
+        // the CNAME and its target are each checked
+        let q = question("alias.example", QType::A);
+        let mut reply = resolve(&zones, &q);
+        reply
+            .answers
+            .extend(resolve(&zones, &question("www.example", QType::A)).answers);
+        assert_eq!(validate(&zones, &q, &reply), Ok(Security::Secure));
+    }
+
+    #[test]
+    fn test_insecure_delegation() {
+        let zones = zones();
+        assert_eq!(
+            check(&zones, "www.insecure", QType::A),
+            Ok(Security::Insecure)
+        );
+        assert_eq!(
+            check(&zones, "missing.insecure", QType::A),
+            Ok(Security::Insecure)
+        );
+    }
+
+    #[test]
+    fn test_secure_denial() {
+        let zones = zones();
+        let nxdomain = question("missing.example", QType::A);
+        assert_eq!(resolve(&zones, &nxdomain).rcode(), NXDOMAIN);
+        assert_eq!(
+            check(&zones, "missing.example", QType::A),
+            Ok(Security::Secure)
+        );
+        assert_eq!(
+            check(&zones, "www.example", QType::AAAA),
+            Ok(Security::Secure)
+        );
+        // an empty non-terminal
+        assert_eq!(check(&zones, "ent.example", QType::A), Ok(Security::Secure));
+        // a wildcard answer, with the proof that the name was not there
+        let reply = resolve(&zones, &question("a.wild.example", QType::A));
+        assert_eq!(reply.answers[0].name, name("a.wild.example"));
+        assert_eq!(
+            check(&zones, "a.wild.example", QType::A),
+            Ok(Security::Secure)
+        );
+    }
+
+    #[test]
+    fn test_bogus_answers() {
+        let zones = zones();
+        let q = question("www.example", QType::A);
+        let reply = resolve(&zones, &q);
+
+        let mut forged = reply.clone();
+        forged.answers[0].rdata = RData::A("10.6.6.6".parse().unwrap());
+        assert_eq!(
+            validate(&zones, &q, &forged),
+            Err(DnssecError::BadSignature)
+        );
+
+        let mut stripped = reply.clone();
+        stripped
+            .answers
+            .retain(|record| record.qtype() != QType::RRSIG);
+        assert_eq!(
+            validate(&zones, &q, &stripped),
+            Err(DnssecError::RrsigsMissing(name("www.example"), QType::A))
+        );
+
+        // a signer out of the chain of trust does not make it insecure
+        let mut resigned = forged.clone();
+        for record in &mut resigned.answers {
+            if let RData::RRSIG(rrsig) = &mut record.rdata {
+                rrsig.signer = name("insecure");
+            }
+        }
+        assert_eq!(
+            validate(&zones, &q, &resigned),
+            Err(DnssecError::RrsigsMissing(name("www.example"), QType::A))
+        );
+
+        let lookup = |q: &Question| Ok(resolve(&zones, q));
+        assert_eq!(
+            validator(&zones).validate(&q, &reply, &lookup, NOW + 7200),
+            Err(DnssecError::SignatureExpired)
+        );
+        assert_eq!(
+            validator(&zones).validate(&q, &reply, &lookup, NOW - 7200),
+            Err(DnssecError::SignatureNotYetValid)
+        );
+
+        // a trust anchor for some other key
+        let other = TestZone::new(".", Some(9), vec![]);
+        let anchor = match other.ds().rdata {
+            RData::DS(ds) => ds,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            Validator::new(vec![anchor]).validate(&q, &reply, &lookup, NOW),
+            Err(DnssecError::DnskeyMissing(Name::root()))
+        );
+
+        let failing = |q: &Question| Err(ResolveError::TooManyQueries(q.name.labels().len()));
+        assert_eq!(
+            validator(&zones).validate(&q, &reply, &failing, NOW),
+            Err(DnssecError::Lookup(Name::root(), QType::DNSKEY))
+        );
+    }
+
+    #[test]
+    fn test_bogus_denial() {
+        let zones = zones();
+
+        // an NXDOMAIN for a name that exists, with the proofs it came with
+        let q = question("www.example", QType::A);
+        let mut forged = resolve(&zones, &question("missing.example", QType::A));
+        forged.questions = vec![q.clone()];
+        assert_eq!(
+            validate(&zones, &q, &forged),
+            Err(DnssecError::NsecMissing(name("www.example")))
+        );
+
+        let q = question("missing.example", QType::A);
+        let mut stripped = resolve(&zones, &q);
+        stripped
+            .authorities
+            .retain(|record| record.qtype() == QType::SOA);
+        assert_eq!(
+            validate(&zones, &q, &stripped),
+            Err(DnssecError::NsecMissing(name("missing.example")))
+        );
+
+        // a wildcard answer without the proof
+        let q = question("a.wild.example", QType::A);
+        let mut reply = resolve(&zones, &q);
+        reply.authorities.clear();
+        assert_eq!(
+            validate(&zones, &q, &reply),
+            Err(DnssecError::NsecMissing(name("a.wild.example")))
+        );
+
+        // the root's NSEC for the delegation to example runs up to insecure,
+        // past www.example, but it only speaks for the parent side of the cut
+        let q = question("www.example", QType::A);
+        let mut forged = Packet::new(Header::default());
+        forged.questions.push(q.clone());
+        forged.set_rcode(NXDOMAIN);
+        forged.authorities = zones[0].rrset(&name("example"), QType::NSEC);
+        assert_eq!(
+            validate(&zones, &q, &forged),
+            Err(DnssecError::RrsigsMissing(name("example"), QType::NSEC))
+        );
+        let delegation = Nsec {
+            next: name("insecure"),
+            types: vec![QType::NS, QType::DS, QType::RRSIG, QType::NSEC],
+        };
+        let nsecs = [(name("example"), delegation)];
+        assert!(!nsec_denies(&name("www.example"), QType::A, true, &nsecs));
+    }
+
+    #[test]
+    fn test_parent_side_proofs() {
+        let zones = zones();
+        let example = &zones[1];
+
+        // a wildcard answer with the root's NSEC for the delegation to example
+        let q = question("a.wild.example", QType::A);
+        let mut reply = resolve(&zones, &q);
+        reply.authorities = zones[0].rrset(&name("example"), QType::NSEC);
+        assert_eq!(
+            validate(&zones, &q, &reply),
+            Err(DnssecError::RrsigsMissing(name("example"), QType::NSEC))
+        );
+
+        // the DS of a.sub.example denied with the Opt-Out NSEC3 chain of
+        // example, whose closest encloser is the signed delegation to
+        // sub.example: that is no unsigned delegation in example
+        let hashed = |owner: &str| {
+            let label = crate::presentation::to_base32hex(&nsec3_hash(&name(owner), &[], 0));
+            name(&label).append(&example.apex).unwrap()
+        };
+        let nsec3 = |types: Vec<QType>, next: &str| Nsec3 {
+            hash_algorithm: NSEC3_SHA1,
+            flags: 1,
+            iterations: 0,
+            salt: Vec::new(),
+            next_hashed: nsec3_hash(&name(next), &[], 0),
+            types,
+        };
+        let mut reply = Packet::new(Header::default());
+        for (owner, types, next) in [
+            (
+                "example",
+                vec![QType::NS, QType::SOA, QType::DNSKEY],
+                "sub.example",
+            ),
+            ("sub.example", vec![QType::NS, QType::DS], "example"),
+        ] {
+            let rrset = [ResourceRecord::new(
+                hashed(owner),
+                Class::IN,
+                TTL,
+                RData::NSEC3(nsec3(types, next)),
+            )];
+            reply
+                .authorities
+                .push(example.rrsig(&rrset, NOW - 3600, NOW + 3600));
+            reply.authorities.extend(rrset);
+        }
+        let parent = Zone {
+            apex: example.apex.clone(),
+            keys: vec![example.dnskey().unwrap()],
+        };
+        let lookup = |_: &Question| Ok(reply.clone());
+        let (cut, _) = validator(&zones)
+            .delegation(&name("a.sub.example"), &parent, &lookup, NOW)
+            .unwrap();
+        assert!(matches!(cut, Cut::Interior));
+    }
+
+    #[test]
+    fn test_chain_of_trust_is_cached() {
+        let zones = zones();
+        let lookups = Cell::new(0);
+        let lookup = |q: &Question| {
+            lookups.set(lookups.get() + 1);
+            Ok(resolve(&zones, q))
+        };
+        let validator = validator(&zones);
+
+        let q = question("www.example", QType::A);
+        let reply = resolve(&zones, &q);
+        assert_eq!(
+            validator.validate(&q, &reply, &lookup, NOW),
+            Ok(Security::Secure)
+        );
+        // DNSKEY for the root, DS and DNSKEY for example, and DS for
+        // www.example to show it is no zone cut
+        assert_eq!(lookups.get(), 4);
+        assert_eq!(
+            validator.validate(&q, &reply, &lookup, NOW),
+            Ok(Security::Secure)
+        );
+        assert_eq!(lookups.get(), 4);
+    }
+
+    #[test]
+    fn test_nsec3_denial() {
+        // example with the apex, www.example, a wildcard below the empty
+        // non-terminal wild.example, and the empty non-terminal ent.example
+        let zone = name("example");
+        let hash = |owner: &str| nsec3_hash(&name(owner), &[], 0);
+        let mut names = [
+            "example",
+            "www.example",
+            "ent.example",
+            "wild.example",
+            "*.wild.example",
+        ]
+        .map(|owner| (hash(owner), owner));
+        names.sort();
+        let nsec3s: Vec<(Name, Nsec3)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, (hashed, owner))| {
+                let types = match *owner {
+                    "example" => vec![QType::NS, QType::SOA, QType::RRSIG, QType::DNSKEY],
+                    "www.example" | "*.wild.example" => vec![QType::A, QType::RRSIG],
+                    _ => vec![],
+                };
+                let label = crate::presentation::to_base32hex(hashed);
+                let nsec3 = Nsec3 {
+                    hash_algorithm: NSEC3_SHA1,
+                    flags: 0,
+                    iterations: 0,
+                    salt: Vec::new(),
+                    next_hashed: names[(i + 1) % names.len()].0.clone(),
+                    types,
+                };
+                (name(&label).append(&zone).unwrap(), nsec3)
+            })
+            .collect();
+        let chain = Nsec3Chain::new(&zone, &nsec3s);
+
+        assert_eq!(
+            chain.denies(&name("missing.example"), QType::A, true),
+            Some(Security::Secure)
+        );
+        assert_eq!(
+            chain.denies(&name("www.example"), QType::AAAA, false),
+            Some(Security::Secure)
+        );
+        assert_eq!(chain.denies(&name("www.example"), QType::A, false), None);
+        assert_eq!(chain.denies(&name("www.example"), QType::A, true), None);
+        assert_eq!(
+            chain.denies(&name("x.wild.example"), QType::AAAA, false),
+            Some(Security::Secure)
+        );
+
+        // an unsigned delegation in an Opt-Out span
+        let opt_out: Vec<(Name, Nsec3)> = nsec3s
+            .iter()
+            .cloned()
+            .map(|(owner, nsec3)| (owner, Nsec3 { flags: 1, ..nsec3 }))
+            .collect();
+        let chain = Nsec3Chain::new(&zone, &opt_out);
+        assert_eq!(
+            chain.denies(&name("child.example"), QType::DS, false),
+            Some(Security::Insecure)
+        );
+
+        let costly: Vec<(Name, Nsec3)> = nsec3s
+            .iter()
+            .cloned()
+            .map(|(owner, nsec3)| {
+                (
+                    owner,
+                    Nsec3 {
+                        iterations: 500,
+                        ..nsec3
+                    },
+                )
+            })
+            .collect();
+        let chain = Nsec3Chain::new(&zone, &costly);
+        assert_eq!(
+            chain.denies(&name("missing.example"), QType::A, true),
+            Some(Security::Insecure)
+        );
+    }
+}
@@ -0,0 +1,756 @@
+// Zones served authoritatively, loaded from master files (RFC 1035 5)
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{ParseError, ZoneError},
+    field::{Class, QType},
+    header::Header,
+    name::Name,
+    packet::Packet,
+    presentation::{parse_name, parse_ttl, tokenize},
+    question::Question,
+    rdata::RData,
+    resource_records::ResourceRecord,
+};
+
+const NXDOMAIN: u16 = 3;
+
+// $INCLUDE files including each other stop here
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_CNAME_CHAIN: usize = 8;
+
+#[derive(Debug)]
+pub struct Zone {
+    origin: Name,
+    class: Class,
+    // records by owner, with empty entries for the empty non-terminals
+    nodes: HashMap<Name, Vec<ResourceRecord>>,
+}
+
+// What a master file entry is read against
+#[derive(Debug, Clone)]
+struct Context {
+    apex: Name,
+    // $ORIGIN, completing relative names
+    origin: Name,
+    // $TTL, for records without one
+    default_ttl: Option<u32>,
+    // the last TTL and class given, and the last owner, for entries that
+    // leave them out
+    last_ttl: Option<u32>,
+    class: Option<Class>,
+    owner: Option<Name>,
+}
+
+// One entry of a master file, which may span lines in parentheses
+#[derive(Debug)]
+struct Entry {
+    line: usize,
+    tokens: Vec<String>,
+    // starts with blank space, so the owner is the previous entry's
+    same_owner: bool,
+}
+
+impl Zone {
+    // Reads the zone at `origin` from the master file at `path`
+    pub fn load(origin: &Name, path: &Path) -> Result<Zone, ZoneError> {
+        let mut context = Context {
+            apex: origin.clone(),
+            origin: origin.clone(),
+            default_ttl: None,
+            last_ttl: None,
+            class: None,
+            owner: None,
+        };
+        let mut records = Vec::new();
+        read(path, &mut context, &mut records, 0)?;
+        Zone::new(origin.clone(), records)
+    }
+
+    fn new(origin: Name, records: Vec<ResourceRecord>) -> Result<Zone, ZoneError> {
+        let class = records
+            .iter()
+            .find(|record| record.name == origin && record.qtype() == QType::SOA)
+            .map(|soa| soa.class)
+            .ok_or_else(|| ZoneError::NoSoa(origin.clone()))?;
+
+        let mut nodes: HashMap<Name, Vec<ResourceRecord>> = HashMap::new();
+        for record in records {
+            let depth = record.name.labels().len() - origin.labels().len();
+            for skip in 1..=depth {
+                nodes.entry(record.name.suffix(skip)).or_default();
+            }
+            nodes.entry(record.name.clone()).or_default().push(record);
+        }
+
+        Ok(Zone {
+            origin,
+            class,
+            nodes,
+        })
+    }
+
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    // The authoritative answer to `question`, whose name is in the zone: the
+    // records, a referral to a delegated child, or NXDOMAIN or NODATA with
+    // the SOA record (RFC 1034 4.3.2)
+    pub fn answer(&self, question: &Question) -> Packet {
+        let header = Header::default()
+            .query_response(true)
+            .authoritative_answer(true)
+            .build();
+        let mut packet = Packet::new(header);
+        packet.questions.push(question.clone());
+        let qtype = question.qtype;
+
+        let mut name = question.name.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            if let Some(cut) = self.cut(&name, qtype) {
+                // the child's servers are the authority from here on
+                packet.header.authoritative_answer = !packet.answers.is_empty();
+                let servers = self.rrset(&cut, QType::NS);
+                packet.additionals = self.glue(&servers);
+                packet.authorities = servers;
+                packet.authorities.extend(self.rrset(&cut, QType::DS));
+                return packet;
+            }
+
+            let Some(records) = self.records(&name) else {
+                packet.set_rcode(NXDOMAIN);
+                packet.authorities = self.negative_soa();
+                return packet;
+            };
+
+            if records
+                .iter()
+                .any(|record| qtype == QType::ANY || record.qtype() == qtype)
+            {
+                packet
+                    .answers
+                    .extend(records.into_iter().filter(|record| covers(record, qtype)));
+                return packet;
+            }
+
+            let Some(RData::CNAME(target)) = records
+                .iter()
+                .map(|record| &record.rdata)
+                .find(|rdata| rdata.qtype() == QType::CNAME)
+                .cloned()
+            else {
+                packet.authorities = self.negative_soa();
+                return packet;
+            };
+            packet.answers.extend(
+                records
+                    .into_iter()
+                    .filter(|record| covers(record, QType::CNAME)),
+            );
+            // the rest of the chain is for the client to follow
+            if !target.is_subdomain_of(&self.origin) {
+                return packet;
+            }
+            name = target;
+        }
+
+        packet
+    }
+
+    // The highest delegation at or above `name`. The parent side answers for
+    // DS records at the cut itself (RFC 4035 3.1.4.1).
+    fn cut(&self, name: &Name, qtype: QType) -> Option<Name> {
+        let depth = name.labels().len() - self.origin.labels().len();
+        (0..depth)
+            .rev()
+            .filter(|&skip| skip > 0 || qtype != QType::DS)
+            .map(|skip| name.suffix(skip))
+            .find(|ancestor| {
+                self.nodes
+                    .get(ancestor)
+                    .is_some_and(|records| records.iter().any(|record| record.qtype() == QType::NS))
+            })
+    }
+
+    // The records at `name`, or those of the wildcard that stands in for it
+    // (RFC 4592 3.3.1). None when the name does not exist.
+    fn records(&self, name: &Name) -> Option<Vec<ResourceRecord>> {
+        if let Some(records) = self.nodes.get(name) {
+            return Some(records.clone());
+        }
+
+        let encloser = (1..name.labels().len())
+            .map(|skip| name.suffix(skip))
+            .find(|ancestor| self.nodes.contains_key(ancestor))?;
+        let wildcard = Name::from_labels(vec![b"*".to_vec()])
+            .and_then(|star| star.append(&encloser))
+            .ok()?;
+        let records = self.nodes.get(&wildcard)?;
+
+        Some(
+            records
+                .iter()
+                .map(|record| ResourceRecord {
+                    name: name.clone(),
+                    ..record.clone()
+                })
+                .collect(),
+        )
+    }
+
+    fn rrset(&self, name: &Name, qtype: QType) -> Vec<ResourceRecord> {
+        self.nodes
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|record| covers(record, qtype))
+            .cloned()
+            .collect()
+    }
+
+    // Addresses of the servers in `servers` that are inside the zone
+    fn glue(&self, servers: &[ResourceRecord]) -> Vec<ResourceRecord> {
+        servers
+            .iter()
+            .filter_map(|record| match &record.rdata {
+                RData::NS(server) => self.nodes.get(server),
+                _ => None,
+            })
+            .flatten()
+            .filter(|record| matches!(record.qtype(), QType::A | QType::AAAA))
+            .cloned()
+            .collect()
+    }
+
+    // The SOA record of negative answers, with the TTL they are cached for
+    // (RFC 2308 3)
+    fn negative_soa(&self) -> Vec<ResourceRecord> {
+        let records = self.rrset(&self.origin, QType::SOA);
+        let ttl = records
+            .iter()
+            .find_map(|record| match record.rdata {
+                RData::SOA { minimum, .. } => Some(record.ttl.min(minimum)),
+                _ => None,
+            })
+            .unwrap_or(0);
+
+        records
+            .into_iter()
+            .map(|record| ResourceRecord { ttl, ..record })
+            .collect()
+    }
+}
+
+// The zones served, a question goes to the one with the longest origin
+// that contains its name
+#[derive(Debug, Default)]
+pub struct Zones {
+    zones: Vec<Zone>,
+}
+
+impl Zones {
+    pub fn new(zones: Vec<Zone>) -> Self {
+        Self { zones }
+    }
+
+    pub fn find(&self, question: &Question) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| question.class == zone.class || question.class == Class::ANY)
+            .filter(|zone| question.name.is_subdomain_of(&zone.origin))
+            .max_by_key(|zone| zone.origin.labels().len())
+    }
+}
+
+// Whether `record` belongs in an answer for `qtype`, signatures included
+fn covers(record: &ResourceRecord, qtype: QType) -> bool {
+    match &record.rdata {
+        _ if qtype == QType::ANY || record.qtype() == qtype => true,
+        RData::RRSIG(rrsig) => rrsig.type_covered == qtype,
+        _ => false,
+    }
+}
+
+fn read(
+    path: &Path,
+    context: &mut Context,
+    records: &mut Vec<ResourceRecord>,
+    depth: usize,
+) -> Result<(), ZoneError> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(ZoneError::IncludeDepth(MAX_INCLUDE_DEPTH));
+    }
+
+    let text = fs::read_to_string(path).map_err(|source| ZoneError::Io {
+        file: path.to_path_buf(),
+        source,
+    })?;
+    let syntax = |line: usize| {
+        move |source: ParseError| ZoneError::Syntax {
+            file: path.to_path_buf(),
+            line,
+            source,
+        }
+    };
+
+    for entry in entries(&text).map_err(|(line, e)| syntax(line)(e))? {
+        let line = entry.line;
+        match entry.tokens[0].to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let [_, origin] = &entry.tokens[..] else {
+                    return Err(syntax(line)(ParseError::InvalidValue(
+                        entry.tokens.join(" "),
+                    )));
+                };
+                context.origin = parse_name(origin, &context.origin).map_err(syntax(line))?;
+            }
+            "$TTL" => {
+                let [_, ttl] = &entry.tokens[..] else {
+                    return Err(syntax(line)(ParseError::InvalidValue(
+                        entry.tokens.join(" "),
+                    )));
+                };
+                context.default_ttl = Some(parse_ttl(ttl).map_err(syntax(line))?);
+            }
+            // the included file starts from the current context, and leaves
+            // it as it was (RFC 1035 5.1)
+            "$INCLUDE" => {
+                let mut included = context.clone();
+                let file = match &entry.tokens[..] {
+                    [_, file] => file,
+                    [_, file, origin] => {
+                        included.origin =
+                            parse_name(origin, &context.origin).map_err(syntax(line))?;
+                        file
+                    }
+                    _ => {
+                        return Err(syntax(line)(ParseError::InvalidValue(
+                            entry.tokens.join(" "),
+                        )))
+                    }
+                };
+                let relative_to = path.parent().unwrap_or(Path::new(""));
+                let file: PathBuf = relative_to.join(file);
+                read(&file, &mut included, records, depth + 1)?;
+            }
+            directive if directive.starts_with('$') => {
+                return Err(syntax(line)(ParseError::InvalidValue(
+                    entry.tokens[0].clone(),
+                )));
+            }
+            _ => {
+                let record = record(&entry, context).map_err(syntax(line))?;
+                let record = match record {
+                    Some(record) => record,
+                    None => {
+                        return Err(ZoneError::NoTtl {
+                            file: path.to_path_buf(),
+                            line,
+                        })
+                    }
+                };
+                if !record.name.is_subdomain_of(&context.apex) {
+                    return Err(ZoneError::OutOfZone {
+                        file: path.to_path_buf(),
+                        line,
+                        name: record.name,
+                    });
+                }
+                records.push(record);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// `[<owner>] [<ttl>] [<class>] <type> <rdata>`, with the TTL and class in
+// either order. None when there is no TTL to give the record.
+fn record(entry: &Entry, context: &mut Context) -> Result<Option<ResourceRecord>, ParseError> {
+    let mut tokens = &entry.tokens[..];
+    let name = if entry.same_owner {
+        context
+            .owner
+            .clone()
+            .ok_or_else(|| ParseError::InvalidValue(tokens.join(" ")))?
+    } else {
+        let owner = parse_name(&tokens[0], &context.origin)?;
+        tokens = &tokens[1..];
+        owner
+    };
+
+    let mut ttl = None;
+    let mut class = None;
+    while let Some(token) = tokens.first() {
+        if ttl.is_none() && token.starts_with(|c: char| c.is_ascii_digit()) {
+            ttl = Some(parse_ttl(token)?);
+        } else if let (None, Ok(parsed)) = (class, token.parse::<Class>()) {
+            class = Some(parsed);
+        } else {
+            break;
+        }
+        tokens = &tokens[1..];
+    }
+
+    let (qtype, fields) = tokens
+        .split_first()
+        .ok_or_else(|| ParseError::InvalidValue(entry.tokens.join(" ")))?;
+    let rdata = RData::from_tokens(qtype.parse()?, fields, &context.origin)?;
+
+    // every record has the class of the first one
+    let class = match (class, context.class) {
+        (Some(class), Some(zone_class)) if class != zone_class => {
+            return Err(ParseError::UnknownClass(class.to_string()))
+        }
+        (class, zone_class) => class.or(zone_class).unwrap_or(Class::IN),
+    };
+    context.class = Some(class);
+    context.owner = Some(name.clone());
+    if ttl.is_some() {
+        context.last_ttl = ttl;
+    }
+
+    // without $TTL the SOA minimum was the default in RFC 1035
+    let ttl = ttl
+        .or(context.default_ttl)
+        .or(context.last_ttl)
+        .or(match rdata {
+            RData::SOA { minimum, .. } => Some(minimum),
+            _ => None,
+        });
+
+    Ok(ttl.map(|ttl| ResourceRecord::new(name, class, ttl, rdata)))
+}
+
+// Splits a master file into entries, joining the lines inside parentheses
+// and dropping comments. Errors come with their line number.
+fn entries(text: &str) -> Result<Vec<Entry>, (usize, ParseError)> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut comment = false;
+
+    let mut finish = |current: &mut String, start: usize| -> Result<(), (usize, ParseError)> {
+        let same_owner = current.starts_with([' ', '\t']);
+        let tokens = tokenize(current).map_err(|e| (start, e))?;
+        if !tokens.is_empty() {
+            entries.push(Entry {
+                line: start,
+                tokens,
+                same_owner,
+            });
+        }
+        current.clear();
+        Ok(())
+    };
+
+    for c in text.chars() {
+        if comment && c != '\n' {
+            continue;
+        }
+        comment = false;
+
+        if escaped {
+            escaped = false;
+            current.push(c);
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                escaped = true;
+                current.push(c);
+            }
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            '\n' if quoted => {
+                return Err((line, ParseError::Unterminated(current.trim().to_string())))
+            }
+            '\n' => {
+                line += 1;
+                if depth > 0 {
+                    current.push(' ');
+                } else {
+                    finish(&mut current, start)?;
+                    start = line;
+                }
+            }
+            ';' if !quoted => comment = true,
+            '(' if !quoted => {
+                depth += 1;
+                current.push(' ');
+            }
+            ')' if !quoted => {
+                if depth == 0 {
+                    return Err((line, ParseError::InvalidValue(")".to_string())));
+                }
+                depth -= 1;
+                current.push(' ');
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if depth > 0 {
+        return Err((start, ParseError::InvalidValue("(".to_string())));
+    }
+    finish(&mut current, start)?;
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const ZONE: &str = r#"$ORIGIN example.com.
+$TTL 1h
+@       IN  SOA ns1 hostmaster (
+                2024010101 ; serial
+                7200 3600 1209600
+                300 )
+        IN  NS  ns1
+            NS  ns2.other.net.
+ns1         A   192.0.2.1
+www     300 IN  A   192.0.2.10
+            A   192.0.2.11
+alias       CNAME www
+out         CNAME www.other.net.
+txt         TXT "a ; b" "c\"d"
+a\.b        TXT dotted
+*.wild      MX  10 mail
+sub         NS  ns.sub
+ns.sub      A   192.0.2.53
+deep.ent    A   192.0.2.20
+$INCLUDE extra.zone lab
+after       A   192.0.2.30
+"#;
+
+    // Writes `files` into a directory of the test's own
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dns-rs-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            fs::write(dir.join(name), text).unwrap();
+        }
+        dir
+    }
+
+    fn load(test: &str, files: &[(&str, &str)]) -> Result<Zone, ZoneError> {
+        let dir = write_files(test, files);
+        let origin: Name = "example.com".parse().unwrap();
+        let zone = Zone::load(&origin, &dir.join(files[0].0));
+        fs::remove_dir_all(dir).unwrap();
+        zone
+    }
+
+    fn example() -> Zone {
+        load(
+            "example",
+            &[
+                ("example.zone", ZONE),
+                ("extra.zone", "host A 192.0.2.40\n"),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn ask(zone: &Zone, name: &str, qtype: QType) -> Packet {
+        zone.answer(&Question::new(name.parse().unwrap(), qtype, Class::IN))
+    }
+
+    fn a(name: &str, ttl: u32, address: [u8; 4]) -> ResourceRecord {
+        ResourceRecord::new(
+            name.parse().unwrap(),
+            Class::IN,
+            ttl,
+            RData::A(Ipv4Addr::from(address)),
+        )
+    }
+
+    #[test]
+    fn test_load_master_file() {
+        let zone = example();
+        let at = |name: &str| zone.nodes.get(&name.parse::<Name>().unwrap()).unwrap();
+
+        assert_eq!(
+            at("example.com")[0],
+            ResourceRecord::new(
+                "example.com".parse().unwrap(),
+                Class::IN,
+                3600,
+                RData::parse(
+                    QType::SOA,
+                    "ns1.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 300"
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(at("example.com").len(), 3);
+        assert_eq!(
+            at("www.example.com"),
+            &vec![
+                a("www.example.com", 300, [192, 0, 2, 10]),
+                a("www.example.com", 3600, [192, 0, 2, 11]),
+            ]
+        );
+        assert_eq!(
+            at("txt.example.com")[0].rdata,
+            RData::TXT(vec![b"a ; b".to_vec(), b"c\"d".to_vec()])
+        );
+        assert_eq!(at("a\\.b.example.com")[0].qtype(), QType::TXT);
+        assert_eq!(
+            at("host.lab.example.com"),
+            &vec![a("host.lab.example.com", 3600, [192, 0, 2, 40])]
+        );
+        // the origin is back to what it was after $INCLUDE
+        assert_eq!(
+            at("after.example.com"),
+            &vec![a("after.example.com", 3600, [192, 0, 2, 30])]
+        );
+        assert!(at("ent.example.com").is_empty());
+    }
+
+    #[test]
+    fn test_answers() {
+        let zone = example();
+
+        let reply = ask(&zone, "www.example.com", QType::A);
+        assert!(reply.header.authoritative_answer);
+        assert_eq!(reply.rcode(), 0);
+        assert_eq!(reply.answers.len(), 2);
+
+        let reply = ask(&zone, "alias.example.com", QType::A);
+        assert_eq!(reply.answers.len(), 3);
+        assert_eq!(reply.answers[0].qtype(), QType::CNAME);
+
+        // the chain leaves the zone
+        let reply = ask(&zone, "out.example.com", QType::A);
+        assert_eq!(reply.rcode(), 0);
+        assert_eq!(reply.answers.len(), 1);
+
+        let reply = ask(&zone, "foo.wild.example.com", QType::MX);
+        assert_eq!(reply.answers.len(), 1);
+        assert_eq!(reply.answers[0].name.to_string(), "foo.wild.example.com");
+    }
+
+    #[test]
+    fn test_negative_answers() {
+        let zone = example();
+
+        let reply = ask(&zone, "missing.example.com", QType::A);
+        assert!(reply.header.authoritative_answer);
+        assert_eq!(reply.rcode(), NXDOMAIN);
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities.len(), 1);
+        assert_eq!(reply.authorities[0].qtype(), QType::SOA);
+        assert_eq!(reply.authorities[0].ttl, 300);
+
+        for name in ["www.example.com", "ent.example.com"] {
+            let reply = ask(&zone, name, QType::MX);
+            assert!(reply.header.authoritative_answer);
+            assert_eq!(reply.rcode(), 0);
+            assert!(reply.answers.is_empty());
+            assert_eq!(reply.authorities[0].qtype(), QType::SOA);
+        }
+    }
+
+    #[test]
+    fn test_referral() {
+        let zone = example();
+
+        let reply = ask(&zone, "www.sub.example.com", QType::A);
+        assert!(!reply.header.authoritative_answer);
+        assert_eq!(reply.rcode(), 0);
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities.len(), 1);
+        assert_eq!(reply.authorities[0].qtype(), QType::NS);
+        assert_eq!(
+            reply.additionals,
+            vec![a("ns.sub.example.com", 3600, [192, 0, 2, 53])]
+        );
+
+        // the parent answers for the DS records of its child
+        let reply = ask(&zone, "sub.example.com", QType::DS);
+        assert!(reply.header.authoritative_answer);
+        assert_eq!(reply.authorities[0].qtype(), QType::SOA);
+    }
+
+    #[test]
+    fn test_zones_find() {
+        let zones = Zones::new(vec![example()]);
+        let find = |name: &str, class: Class| {
+            zones
+                .find(&Question::new(name.parse().unwrap(), QType::A, class))
+                .map(|zone| zone.origin().to_string())
+        };
+
+        assert_eq!(
+            find("www.example.com", Class::IN).as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            find("www.example.com", Class::ANY).as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(find("www.example.com", Class::CH), None);
+        assert_eq!(find("example.net", Class::IN), None);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let soa = "@ 3600 SOA ns1 hostmaster 1 2 3 4 5\n";
+
+        assert!(matches!(
+            load("no-soa", &[("a.zone", "www 3600 A 192.0.2.1\n")]),
+            Err(ZoneError::NoSoa(_))
+        ));
+        assert!(matches!(
+            load(
+                "out-of-zone",
+                &[("a.zone", &format!("{soa}www.example.net. A 192.0.2.1\n"))]
+            ),
+            Err(ZoneError::OutOfZone { line: 2, .. })
+        ));
+        assert!(matches!(
+            load("no-ttl", &[("a.zone", "www A 192.0.2.1\n")]),
+            Err(ZoneError::NoTtl { line: 1, .. })
+        ));
+        assert!(matches!(
+            load(
+                "parentheses",
+                &[("a.zone", &format!("{soa}www A (\n192.0.2.1\n"))]
+            ),
+            Err(ZoneError::Syntax { line: 2, .. })
+        ));
+        assert!(matches!(
+            load(
+                "bad-rdata",
+                &[("a.zone", &format!("{soa}\nwww A 192.0.2\n"))]
+            ),
+            Err(ZoneError::Syntax { line: 3, .. })
+        ));
+        assert!(matches!(
+            load("directive", &[("a.zone", "$GENERATE 1-2 $ A 192.0.2.$\n")]),
+            Err(ZoneError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            load("include-loop", &[("a.zone", "$INCLUDE a.zone\n")]),
+            Err(ZoneError::IncludeDepth(_))
+        ));
+    }
+}